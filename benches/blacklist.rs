@@ -0,0 +1,93 @@
+//! Compares [`is_blacklisted`] (re-parses every rule per post) against [`CompiledBlacklist`].
+//!
+//! Run with `cargo bench --bench blacklist`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use msg::blacklist::{is_blacklisted, Blacklist, CompiledBlacklist};
+use msg::fixture::post;
+use msg::model::Post;
+use serde_json::json;
+
+const POSTS: usize = 320;
+const TAGS_PER_POST: usize = 60;
+const RULES: usize = 150;
+const ITERATIONS: u32 = 20;
+
+fn make_post(id: usize) -> Post {
+    let general = (0..TAGS_PER_POST)
+        .map(|t| format!("general_tag_{}", (id * 7919 + t * 104_729) % 20_000))
+        .collect::<Vec<_>>();
+
+    let rating = ["s", "q", "e"][id % 3];
+
+    post(json!({
+        "id": id,
+        "file": { "ext": if id.is_multiple_of(5) { "webm" } else { "png" }, "url": null },
+        "score": { "total": (id % 50) as i32 - 10 },
+        "tags": {
+            "general": general,
+            "artist": [format!("artist_{}", id % 40)],
+            "species": ["canine"]
+        },
+        "rating": rating,
+        "fav_count": id % 100
+    }))
+}
+
+fn make_blacklist() -> Blacklist {
+    let rules = (0..RULES)
+        .map(|i| match i % 5 {
+            0 => format!("general_tag_{} general_tag_{}", i * 11, i * 17),
+            1 => format!("general_tag_{} -rating:s", i * 3),
+            2 => format!("~general_tag_{} ~general_tag_{} canine", i * 19, i * 23),
+            3 => format!("artist_{} score:<0", i % 40),
+            _ => format!("type:webm general_tag_{}", i * 29),
+        })
+        .collect();
+    Blacklist { rules }
+}
+
+fn time<F: FnMut() -> usize>(name: &str, mut f: F) -> Duration {
+    // warm up
+    black_box(f());
+
+    let start = Instant::now();
+    let mut hidden = 0;
+    for _ in 0..ITERATIONS {
+        hidden = black_box(f());
+    }
+    let per_page = start.elapsed() / ITERATIONS;
+    println!("{name:<28} {per_page:>12.2?} per page ({hidden} of {POSTS} hidden)");
+    per_page
+}
+
+fn main() {
+    let posts = (0..POSTS).map(make_post).collect::<Vec<_>>();
+    let blacklist = make_blacklist();
+
+    println!("{POSTS} posts x {TAGS_PER_POST} tags, {RULES} rules, {ITERATIONS} iterations");
+
+    let naive = time("is_blacklisted", || {
+        posts
+            .iter()
+            .filter(|p| is_blacklisted(p, &blacklist))
+            .count()
+    });
+
+    let compiled = CompiledBlacklist::compile(&blacklist);
+    let precompiled = time("CompiledBlacklist", || {
+        posts.iter().filter(|p| compiled.is_blacklisted(p)).count()
+    });
+
+    time("CompiledBlacklist + compile", || {
+        let compiled = CompiledBlacklist::compile(&blacklist);
+        posts.iter().filter(|p| compiled.is_blacklisted(p)).count()
+    });
+
+    println!(
+        "speedup: {:.1}x",
+        naive.as_secs_f64() / precompiled.as_secs_f64()
+    );
+}
@@ -0,0 +1,132 @@
+//! Compares saving the whole [`PostStore`] as one rmp-serde snapshot, the same snapshot encoded
+//! with bitcode, and journaling only the posts that changed.
+//!
+//! Run with `cargo bench --bench store`.
+
+use std::fs;
+use std::hint::black_box;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use msg::fixture::post;
+use msg::model::{Post, Vote};
+use msg::store::{write_atomically, PostStore, PostStoreData};
+use serde_json::json;
+
+const POSTS: usize = 20_000;
+const TAGS_PER_POST: usize = 60;
+/// Posts favorited or voted on between flushes.
+const CHANGED: usize = 50;
+const ITERATIONS: u32 = 5;
+
+fn make_post(id: usize) -> Post {
+    let general = (0..TAGS_PER_POST)
+        .map(|t| format!("general_tag_{}", (id * 7919 + t * 104_729) % 20_000))
+        .collect::<Vec<_>>();
+
+    post(json!({
+        "id": id,
+        "file": {
+            "width": 1920, "height": 1080, "ext": "png", "size": 1_048_576,
+            "md5": format!("{id:032x}"),
+            "url": format!("https://static1.e621.net/data/{id:032x}.png")
+        },
+        "preview": { "width": 150, "height": 150 },
+        "score": { "up": id % 50, "down": 0, "total": id % 50 },
+        "tags": {
+            "general": general,
+            "artist": [format!("artist_{}", id % 40)],
+            "species": ["canine"]
+        },
+        "fav_count": id % 100,
+        "description": "a description of the post"
+    }))
+}
+
+/// Times `f`, which returns a count of `unit`s to print alongside.
+fn time<F: FnMut() -> usize>(name: &str, unit: &str, mut f: F) -> Duration {
+    // warm up
+    black_box(f());
+
+    let start = Instant::now();
+    let mut count = 0;
+    for _ in 0..ITERATIONS {
+        count = black_box(f());
+    }
+    let per_run = start.elapsed() / ITERATIONS;
+    println!("{name:<28} {per_run:>12.2?} ({count} {unit})");
+    per_run
+}
+
+fn size(path: &Path) -> usize {
+    fs::metadata(path).map_or(0, |meta| meta.len() as usize)
+}
+
+fn main() {
+    let dir = tempfile::TempDir::new().expect("Couldn't make TempDir");
+    let snapshot = dir.path().join("store.mpk");
+    let bitcode_snapshot = dir.path().join("store.bitcode");
+
+    let mut store = PostStore::new();
+    store.insert_posts((0..POSTS).map(make_post));
+    store.save_to(&snapshot).expect("Couldn't save store");
+
+    println!("{POSTS} posts x {TAGS_PER_POST} tags, {CHANGED} changed, {ITERATIONS} iterations");
+
+    let rmp = time("rmp snapshot save", "bytes", || {
+        store.save_to(&snapshot).expect("Couldn't save store");
+        size(&snapshot)
+    });
+    time("rmp snapshot load", "posts", || {
+        PostStore::load_from(&snapshot)
+            .expect("Couldn't load store")
+            .posts
+            .len()
+    });
+
+    let data = store.data();
+    match bitcode::serialize(&data) {
+        Ok(_) => {
+            time("bitcode snapshot save", "bytes", || {
+                let bytes = bitcode::serialize(&store.data()).expect("Couldn't encode store");
+                write_atomically(&bitcode_snapshot, |writer| {
+                    std::io::Write::write_all(writer, &bytes)?;
+                    Ok(())
+                })
+                .expect("Couldn't save store");
+                bytes.len()
+            });
+            time("bitcode snapshot load", "posts", || {
+                let bytes = fs::read(&bitcode_snapshot).expect("Couldn't read store");
+                let data: PostStoreData =
+                    bitcode::deserialize(&bytes).expect("Couldn't decode store");
+                data.posts.len()
+            });
+        }
+        Err(err) => println!("{:<28} {err}", "bitcode snapshot"),
+    }
+
+    let mut round = 0;
+    let journal = time("journal flush", "bytes", || {
+        for id in (0..CHANGED).map(|i| (round * CHANGED + i) % POSTS) {
+            let id = id as u32;
+            store.set_favorite(id, !store.is_favorited(id));
+            store.set_vote(id, Some(Vote::Upvote));
+        }
+        round += 1;
+        store.flush(&snapshot).expect("Couldn't flush store");
+        size(&snapshot.with_extension("journal"))
+    });
+
+    time("rmp snapshot + journal load", "posts", || {
+        PostStore::load_from(&snapshot)
+            .expect("Couldn't load store")
+            .posts
+            .len()
+    });
+
+    println!(
+        "flushing {CHANGED} changes is {:.0}x faster than a snapshot",
+        rmp.as_secs_f64() / journal.as_secs_f64()
+    );
+}
@@ -1,11 +1,16 @@
+use iced::keyboard::{Key, Modifiers};
 use iced::widget::image::Handle;
+use iced::widget::scrollable::Viewport;
 use iced::widget::text_editor::Action;
 use rustc_hash::FxHashMap;
 use url::Url;
 
 use crate::app::state::ViewMode;
-use crate::core::config::MsgTheme;
-use crate::core::model::{Comment, Post, Vote};
+use crate::core::api::{FetchPoint, TagWiki};
+use crate::core::config::{ApiHost, MsgTheme};
+use crate::core::dtext::cache::DTextSource;
+use crate::core::followed::PoolUpdate;
+use crate::core::model::{Comment, Pool, Post, TagSuggestion, Vote};
 use crate::gui::video_player::VideoPlayerMessage;
 
 #[derive(Debug, Clone)]
@@ -17,6 +22,8 @@ pub enum Message {
     Detail(DetailMessage),
     Settings(SettingsMessage),
     Followed(FollowedMessage),
+    Pool(PoolMessage),
+    Wiki(WikiMessage),
     View(ViewMessage),
 
     Exit,
@@ -27,16 +34,36 @@ pub enum Message {
 pub enum SearchMessage {
     LoadPosts(String),
     LoadMorePosts,
-    PostsLoaded(Vec<Post>),
+    /// Posts for a query, from its first page when `point` is `None`.
+    PostsLoaded {
+        query: String,
+        point: Option<FetchPoint>,
+        posts: Vec<Post>,
+    },
+    /// Fetching posts failed, with a message for the user.
+    LoadFailed(String),
     InputChanged(String),
     Submitted,
     GetFavorites,
+    /// Show or hide posts filtered out by the blacklist.
+    ToggleHidden,
+    /// Typing paused, look up suggestions if nothing was typed since the given keystroke.
+    AutocompleteDue(u64),
+    /// Tag suggestions for a prefix.
+    SuggestionsLoaded(String, Vec<TagSuggestion>),
+    /// Move the highlighted suggestion up or down.
+    SelectSuggestion(isize),
+    /// Put a suggestion into the search bar. `None` takes the highlighted one, or the first.
+    AcceptSuggestion(Option<usize>),
+    DismissSuggestions,
 }
 
 /// Manages post loading
 #[derive(Debug, Clone)]
 pub enum PostMessage {
     View(u32),
+    /// A post that wasn't in the store, fetched to be viewed.
+    Loaded(Post),
     Vote(u32, Option<Vote>),
     VoteResult(u32, Option<Vote>),
     Favorite(u32),
@@ -47,6 +74,7 @@ pub enum PostMessage {
 #[derive(Debug, Clone)]
 pub enum MediaMessage {
     ThumbnailLoaded(u32, Handle),
+    ThumbnailFailed(u32),
     SampleLoaded(u32, Handle),
     ImageLoaded(u32, Handle),
     GifLoaded(u32, Vec<u8>),
@@ -59,7 +87,25 @@ pub enum MediaMessage {
 pub enum DetailMessage {
     AddTagToSearch(String),
     NegateTagFromSearch(String),
-    CommentsLoaded(Vec<Comment>),
+    /// Every comment on a post, freshly fetched.
+    CommentsLoaded(u32, Vec<Comment>),
+    /// Fetch the selected post's comments even if they're recent.
+    RefreshComments,
+    CommentEdited(Action),
+    /// Load one of the user's comments into the editor.
+    EditComment(u32),
+    CancelComment,
+    ToggleCommentPreview,
+    SubmitComment,
+    /// e621 accepted a new or edited comment.
+    CommentSaved(Comment),
+    CommentFailed(String),
+    /// Post ID, comment ID and the vote.
+    VoteComment(u32, u32, Option<Vote>),
+    CommentVoteResult(u32, u32, Option<Vote>),
+    /// Go to the next post in the list the current one was opened from.
+    NextPost,
+    PreviousPost,
     /// Open the post's file in the default OS app.
     OpenFile,
     CopyURL,
@@ -75,9 +121,28 @@ pub enum SettingsMessage {
     FullsizeToggled(bool),
     SampleToggled(bool),
     PPRChanged(usize),
+    HostSelected(ApiHost),
+    CustomHostChanged(String),
     TileSizeChanged(usize),
     PurgeCache,
     Save,
+    /// Fetch the blacklist saved on the e621 account, to review before applying.
+    FetchAccountBlacklist,
+    AccountBlacklistLoaded(Vec<String>),
+    /// Add any rules from the fetched account blacklist that are missing locally.
+    MergeAccountBlacklist,
+    /// Replace the local blacklist with the fetched account blacklist.
+    ReplaceWithAccountBlacklist,
+    /// Drop the pending sync, either way.
+    DiscardAccountBlacklist,
+    /// Fetch the account's blacklist, to review what uploading the local one would change.
+    PushBlacklist,
+    /// The account's blacklist, for reviewing an upload.
+    UploadReviewed(Vec<String>),
+    /// Upload the local blacklist to the e621 account, once reviewed.
+    ConfirmPushBlacklist,
+    BlacklistPushed,
+    BlacklistSyncFailed(String),
 }
 
 /// Messages to manage followed tags.
@@ -85,12 +150,43 @@ pub enum SettingsMessage {
 pub enum FollowedMessage {
     CheckUpdates,
     UpdatesReceived(FxHashMap<String, Vec<Post>>),
+    PoolUpdatesReceived(Vec<PoolUpdate>),
     AddTag,
     FollowTag(String),
     RemoveTag(String),
     ClearSeenPosts,
 }
 
+/// Messages for browsing, reading and following pools.
+#[derive(Debug, Clone)]
+pub enum PoolMessage {
+    SearchInputChanged(String),
+    Search,
+    SearchResults(Vec<Pool>),
+    /// Fetch a pool and its posts, then show it.
+    Open(u32),
+    Loaded(Pool, Vec<Post>),
+    /// Searching for or loading a pool failed, with why.
+    Failed(String),
+    /// Go to the next page of the current pool, from the selected post.
+    NextPage,
+    PreviousPage,
+    /// Preload upcoming pages while reading through a pool.
+    ToggleReadMode,
+    Follow,
+    Unfollow(u32),
+}
+
+/// Messages for tag wiki pages.
+#[derive(Debug, Clone)]
+pub enum WikiMessage {
+    /// Fetch a tag's wiki page and relations, then show them.
+    Open(String),
+    Loaded(TagWiki),
+    /// Loading a tag's wiki failed, with why.
+    Failed(String, String),
+}
+
 /// Messages to manage view states (settings, followed, etc.)
 #[derive(Debug, Clone)]
 pub enum ViewMessage {
@@ -102,4 +198,18 @@ pub enum ViewMessage {
     /// For mouse forward button
     Forward,
     UpdateTheme(MsgTheme),
+    /// Show an error banner with a message for the user.
+    ShowError(String),
+    DismissError,
+    /// Open a link in the browser. Paths like `/wiki_pages/help` are on the current host.
+    OpenUrl(String),
+    /// Open or close a `[section]`, by its position in the DText it's in.
+    ToggleSection(DTextSource, usize),
+    /// A key press no widget used, to look up in the keymap.
+    KeyPressed(Key, Modifiers),
+    /// Show or hide the keyboard shortcuts overlay.
+    ToggleHelp,
+    GridScrolled(Viewport),
+    /// Horizontal scrolling, like a two finger swipe on a touchpad.
+    Swiped(f32),
 }
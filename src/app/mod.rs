@@ -6,3 +6,4 @@ mod view;
 
 pub use message::Message;
 pub use state::App;
+pub use view::dtext::render_dtext;
@@ -1,15 +1,24 @@
-use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Instant;
 
+use iced::widget::scrollable::{AbsoluteOffset, Viewport};
 use iced::widget::text_editor::Content;
 use iced::Task;
 use rustc_hash::FxHashMap;
 use tracing::{debug, error, info};
 
 use crate::app::message::SearchMessage;
-use crate::core::api::fetch_posts;
-use crate::core::config::Config;
-use crate::core::model::Post;
-use crate::core::store::{poststore_path, PostStore};
+use crate::core::api::{fetch_posts, FetchPoint, TagWiki};
+use crate::core::blacklist::{Blacklist, BlacklistDiff, CompiledBlacklist};
+use crate::core::config::{self, config_path, ApiHost, Config, ConfigError};
+use crate::core::dtext::cache::DTextCache;
+use crate::core::followed::PoolUpdate;
+use crate::core::http::set_retry_config;
+use crate::core::media::ThumbnailScheduler;
+use crate::core::model::{Pool, Post, TagSuggestion};
+use crate::core::store::{self, poststore_path, PostStore, StoreWriter};
+use crate::gui::post_tile;
 use crate::gui::video_player::VideoPlayerWidget;
 
 use super::Message;
@@ -23,6 +32,45 @@ pub struct Settings {
     pub api_key: String,
     /// `Content` for the blacklist editor.
     pub blacklist_content: Content,
+    /// Blacklist sync with the e621 account, waiting on the user to confirm it.
+    pub blacklist_sync: Option<BlacklistSync>,
+    /// Result of the last blacklist sync action.
+    pub sync_status: Option<String>,
+    /// Text in the custom API host input.
+    pub custom_host: String,
+}
+
+/// The account's blacklist, next to the one in the editor.
+#[derive(Debug)]
+pub struct BlacklistSync {
+    /// Blacklist saved on the e621 account.
+    pub account: Blacklist,
+    /// Whether the editor's blacklist is to replace the account's, rather than the other way
+    /// around.
+    pub upload: bool,
+    /// What the sync would change on the receiving side, kept up to date with the editor.
+    pub diff: BlacklistDiff,
+}
+
+impl BlacklistSync {
+    pub fn new(account: Blacklist, editor: &Blacklist, upload: bool) -> Self {
+        let mut sync = Self {
+            account,
+            upload,
+            diff: BlacklistDiff::default(),
+        };
+        sync.update(editor);
+        sync
+    }
+
+    /// Works out the diff again after the editor's blacklist changed.
+    pub fn update(&mut self, editor: &Blacklist) {
+        self.diff = if self.upload {
+            self.account.diff(editor)
+        } else {
+            editor.diff(&self.account)
+        };
+    }
 }
 
 #[derive(Debug)]
@@ -31,6 +79,28 @@ pub struct UiState {
     pub window_width: u32,
     pub window_height: u32,
     pub history: ViewHistory,
+    /// Last error to show the user, until dismissed.
+    pub error: Option<String>,
+    /// Post picked in the grid with the keyboard.
+    pub highlighted: Option<u32>,
+    /// Where the grid is scrolled to, to build only the rows in view and keep the highlighted
+    /// post in view.
+    pub grid_viewport: Option<Viewport>,
+    /// The last search scrolled through and how far, to return to it from a post.
+    pub grid_scroll: Option<(String, AbsoluteOffset)>,
+    /// Show the keyboard shortcuts overlay.
+    pub show_help: bool,
+    pub swipe: Swipe,
+}
+
+/// Horizontal scrolling in the detail view, added up into a swipe to the next or previous post.
+#[derive(Debug, Default)]
+pub struct Swipe {
+    pub distance: f32,
+    /// Last horizontal scroll, to tell separate gestures apart.
+    pub last_event: Option<Instant>,
+    /// Last time a swipe changed posts, so one gesture only changes one.
+    pub triggered: Option<Instant>,
 }
 
 /// Stacks for back/forward buttons.
@@ -72,24 +142,82 @@ impl ViewHistory {
     }
 }
 
+/// Widget ID of the search bar.
+pub const SEARCH_INPUT: &str = "search-input";
+/// Widget ID of the search results' scrollable.
+pub const GRID_SCROLLABLE: &str = "post-grid";
+/// Space around the search results grid.
+pub const GRID_PADDING: f32 = 16.0;
+
 #[derive(Debug)]
 pub struct SearchState {
     /// Text in search bar.
     pub input: String,
     /// Current search query.
     pub query: String,
-    /// Queue for thumbnail fetching.
-    pub thumbnail_queue: VecDeque<u32>,
     /// Current page for pagination.
     /// Note that e6 pages start at 1.
     pub page: Option<usize>,
+    /// Temporarily show posts hidden by the blacklist.
+    pub show_hidden: bool,
+    /// Autocomplete suggestions for the tag being typed.
+    pub suggestions: Vec<TagSuggestion>,
+    /// Suggestion highlighted with the arrow keys.
+    pub selected_suggestion: Option<usize>,
+    /// Counts keystrokes, so only the lookup after the last one goes through.
+    pub keystrokes: u64,
+    /// Where the page of results for `query` being fetched starts, if one is.
+    pub loading_more: Option<FetchPoint>,
+    /// The last page fetched for `query` was empty.
+    pub exhausted: bool,
+    /// ID of the last post fetched for `query`, hidden or not, to fetch the posts after it.
+    pub last_id: Option<u32>,
 }
 
 #[derive(Debug)]
 pub struct FollowedState {
     pub new_followed_tag: String,
+    /// New posts for each followed tag, minus anything the blacklist hides.
     pub new_followed_posts: FxHashMap<String, Vec<Post>>,
+    /// Every post received for each followed tag, including blacklisted ones.
+    pub received_posts: FxHashMap<String, Vec<Post>>,
     pub tags: FxHashMap<String, Option<u32>>,
+    /// New pages in followed pools, minus anything the blacklist hides.
+    pub new_pool_pages: Vec<PoolUpdate>,
+    /// Every new page received for each followed pool, including blacklisted posts.
+    pub pool_updates: Vec<PoolUpdate>,
+}
+
+#[derive(Debug, Default)]
+pub struct PoolState {
+    /// Text in the pool search bar.
+    pub search_input: String,
+    pub search_results: Vec<Pool>,
+    /// Pool being viewed or read, with its posts in the store.
+    pub current: Option<Pool>,
+    /// Preload the next pages when viewing a post in `current`.
+    pub read_mode: bool,
+}
+
+/// A list of posts that the detail view steps through with next and previous.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResultList {
+    /// Results for a search query.
+    Search(String),
+    /// New posts for a followed tag.
+    Followed(String),
+}
+
+/// The comment being written or edited in the detail view.
+#[derive(Debug, Default)]
+pub struct CommentDraft {
+    /// ID of the comment being edited, or `None` for a new comment.
+    pub editing: Option<u32>,
+    pub content: Content,
+    /// Show the rendered DText instead of the editor.
+    pub preview: bool,
+    /// Waiting for e621 to accept it.
+    pub sending: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -100,6 +228,12 @@ pub enum ViewMode {
     Detail(u32),
     Settings,
     Followed,
+    /// Pool view with pool ID.
+    Pool(u32),
+    /// Pool search.
+    Pools,
+    /// Wiki view with tag name.
+    Wiki(String),
 }
 
 #[derive(Debug)]
@@ -110,13 +244,35 @@ pub struct App {
     pub ui: UiState,
     pub search: SearchState,
     pub followed: FollowedState,
+    pub pools: PoolState,
+    /// Wiki page being viewed.
+    pub wiki: Option<TagWiki>,
+    /// Tag whose wiki last failed to load, with why.
+    pub wiki_error: Option<(String, String)>,
+    pub comment_draft: CommentDraft,
+    /// DText on screen, parsed when it loads rather than on every redraw.
+    pub dtext: DTextCache,
     pub config: Config,
+    /// Whether `config` is saved. Off when the config file couldn't be read, so it isn't
+    /// overwritten with defaults.
+    pub config_writable: bool,
+    /// Compiled form of `config.blacklist`, rebuilt whenever the blacklist is saved.
+    pub blacklist: CompiledBlacklist,
     pub store: PostStore,
+    /// Where `store` is saved. `None` if there's nowhere to save it, or the file there couldn't be
+    /// read and mustn't be overwritten.
+    pub store_path: Option<PathBuf>,
+    /// Journals changes to `store` off the UI thread. `None` along with `store_path`.
+    pub store_writer: Option<StoreWriter>,
+    /// Thumbnails waiting to download for the grid, followed and pool views.
+    pub thumbnails: ThumbnailScheduler,
 
     /// Posts loaded in grid view.
     pub posts: Vec<Post>,
     /// None means grid view, Some(u32) is post ID.
     pub selected_post: Option<u32>,
+    /// List the selected post was opened from.
+    pub browsing: Option<ResultList>,
 
     /// Shows "loading" screen.
     pub loading: bool,
@@ -131,18 +287,48 @@ impl App {
         let search = SearchState {
             input: "order:rank".into(),
             query: "order:rank".into(),
-            thumbnail_queue: VecDeque::new(),
             page: None,
+            show_hidden: false,
+            suggestions: Vec::new(),
+            selected_suggestion: None,
+            keystrokes: 0,
+            loading_more: None,
+            exhausted: false,
+            last_id: None,
         };
 
-        let cache = if let Some(path) = poststore_path() {
+        // Files that can't be decoded are moved aside rather than overwritten on the next save. Ones
+        // that couldn't be read at all, or were written by a newer msg, are left alone, and not
+        // saved over either.
+        let mut errors = Vec::new();
+        let mut store_path = poststore_path();
+        let mut cache = if let Some(path) = store_path.clone() {
             match PostStore::load_from(&path) {
                 Ok(store) => {
                     info!("Loaded PostStore from {path:?}");
                     store
                 }
+                Err(err) if err.is_undecodable() => {
+                    error!("Couldn't load PostStore from file: {err}");
+                    match store::set_aside(&path) {
+                        Ok(aside) => errors.push(format!(
+                            "Couldn't read saved posts ({err}), so they were moved to {}.",
+                            aside.display()
+                        )),
+                        Err(move_err) => {
+                            error!("Couldn't move {path:?} aside: {move_err}");
+                            errors.push(format!("Couldn't read saved posts: {err}"));
+                            store_path = None;
+                        }
+                    }
+                    PostStore::new()
+                }
                 Err(err) => {
                     error!("Couldn't load PostStore from file: {err}");
+                    errors.push(format!(
+                        "Couldn't read saved posts ({err}), so nothing will be saved this session."
+                    ));
+                    store_path = None;
                     PostStore::new()
                 }
             }
@@ -151,17 +337,61 @@ impl App {
             PostStore::new()
         };
 
-        let app = Self {
-            config: Config::new(),
+        let mut config_writable = true;
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(ConfigError::IOError(err)) if err.kind() == ErrorKind::NotFound => {
+                Config::default()
+            }
+            Err(err) if err.is_undecodable() => {
+                error!("Couldn't load config: {err}");
+                match config_path().and_then(|dir| config::set_aside(&dir.join("config.toml"))) {
+                    Ok(aside) => errors.push(format!(
+                        "Couldn't read the config ({err}), so it was moved to {}.",
+                        aside.display()
+                    )),
+                    Err(move_err) => {
+                        error!("Couldn't move config aside: {move_err}");
+                        errors.push(format!("Couldn't read the config: {err}"));
+                        config_writable = false;
+                    }
+                }
+                Config::default()
+            }
+            Err(err) => {
+                error!("Couldn't load config: {err}");
+                errors.push(format!(
+                    "Couldn't read the config ({err}), so settings won't be saved this session."
+                ));
+                config_writable = false;
+                Config::default()
+            }
+        };
+        set_retry_config(config.retry);
+        cache.media.set_memory_budget(config.cache.memory_budget());
+        let mut app = Self {
+            blacklist: CompiledBlacklist::compile(&config.blacklist),
+            config,
             search,
             store: cache,
+            store_writer: store_path.clone().map(StoreWriter::new),
+            store_path,
+            config_writable,
             ..Default::default()
         };
+        if !errors.is_empty() {
+            app.ui.error = Some(errors.join("\n"));
+        }
 
+        let base_url = app.config.host.base_url().to_string();
         let cmd = Task::perform(
-            fetch_posts(None, String::from("order:rank"), None), // should fix
+            async move { fetch_posts(&base_url, None, String::from("order:rank"), None).await }, // should fix
             move |res| match res {
-                Ok(posts) => Message::Search(SearchMessage::PostsLoaded(posts)),
+                Ok(posts) => Message::Search(SearchMessage::PostsLoaded {
+                    query: String::from("order:rank"),
+                    point: None,
+                    posts,
+                }),
                 Err(err) => {
                     error!("getting posts failed: {err}");
                     Message::Tick
@@ -179,6 +409,72 @@ impl App {
         }
         total
     }
+
+    /// IDs of the posts in the grid for `query`, in order.
+    pub fn shown_results(&self, query: &str) -> Vec<u32> {
+        self.store
+            .get_results(query)
+            .map(|ids| {
+                ids.iter()
+                    .copied()
+                    .filter(|&id| self.store.get_post(id).is_some())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// IDs of the posts in `list`, in the order they're shown.
+    pub fn list_posts(&self, list: &ResultList) -> Vec<u32> {
+        match list {
+            ResultList::Search(query) => self.shown_results(query),
+            ResultList::Followed(tag) => self
+                .followed
+                .new_followed_posts
+                .get(tag)
+                .map(|posts| posts.iter().map(|post| post.id).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Pixels of the grid in view, from the top of the first row. Before the first scroll, the
+    /// grid starts at the top and fills the window at most.
+    pub fn grid_in_view(&self) -> (f32, f32) {
+        let (offset, height) = self
+            .ui
+            .grid_viewport
+            .map_or((0.0, self.ui.window_height as f32), |viewport| {
+                (viewport.absolute_offset().y, viewport.bounds().height)
+            });
+        let top = offset - GRID_PADDING;
+        (top, top + height)
+    }
+
+    /// IDs of the posts in the grid for `query` that are on screen.
+    pub fn posts_in_view(&self, query: &str) -> Vec<u32> {
+        let ids = self.store.get_results(query).map_or(&[][..], Vec::as_slice);
+        let columns = post_tile::columns(
+            self.ui.window_width as usize,
+            self.config.view.posts_per_row,
+            self.config.view.tile_width,
+        );
+        let row_height = post_tile::tile_height(self.config.view.tile_width as f32);
+        let (top, bottom) = self.grid_in_view();
+        let rows = post_tile::visible_rows(top, bottom, row_height, ids.len().div_ceil(columns));
+        ids[(rows.start * columns).min(ids.len())..(rows.end * columns).min(ids.len())].to_vec()
+    }
+
+    /// The posts before and after the selected one, in the list it was opened from.
+    pub fn neighbours(&self) -> (Option<u32>, Option<u32>) {
+        let (Some(list), Some(selected)) = (&self.browsing, self.selected_post) else {
+            return (None, None);
+        };
+        let ids = self.list_posts(list);
+        let Some(position) = ids.iter().position(|&id| id == selected) else {
+            return (None, None);
+        };
+        let previous = position.checked_sub(1).map(|i| ids[i]);
+        (previous, ids.get(position + 1).copied())
+    }
 }
 
 impl Default for App {
@@ -199,6 +495,11 @@ impl Default for App {
         }
 
         let blacklist = config.blacklist.rules.join("\n").clone();
+        let custom_host = match &config.host {
+            ApiHost::Custom(url) => url.clone(),
+            _ => String::new(),
+        };
+        let compiled_blacklist = CompiledBlacklist::compile(&config.blacklist);
 
         let store = PostStore::new();
 
@@ -210,29 +511,58 @@ impl Default for App {
                 username: username,
                 api_key: api_key,
                 blacklist_content: Content::with_text(&blacklist).into(),
+                blacklist_sync: None,
+                sync_status: None,
+                custom_host,
             },
             ui: UiState {
                 view_mode: ViewMode::Grid(String::from("order:rank"), Some(1)),
                 window_width: 480,
                 window_height: 640,
                 history: ViewHistory::default(),
+                error: None,
+                highlighted: None,
+                grid_viewport: None,
+                grid_scroll: None,
+                show_help: false,
+                swipe: Swipe::default(),
             },
             search: SearchState {
                 input: String::new(),
                 query: String::new(),
-                thumbnail_queue: VecDeque::new(),
                 page: None,
+                show_hidden: false,
+                suggestions: Vec::new(),
+                selected_suggestion: None,
+                keystrokes: 0,
+                loading_more: None,
+                exhausted: false,
+                last_id: None,
             },
             followed: FollowedState {
                 new_followed_tag: String::new(),
                 new_followed_posts: FxHashMap::default(),
+                received_posts: FxHashMap::default(),
                 tags: tag_map,
+                new_pool_pages: Vec::new(),
+                pool_updates: Vec::new(),
             },
+            pools: PoolState::default(),
+            wiki: None,
+            wiki_error: None,
+            comment_draft: CommentDraft::default(),
+            dtext: DTextCache::default(),
             config,
+            config_writable: true,
+            blacklist: compiled_blacklist,
             debug: false,
             store: store,
+            store_path: None,
+            store_writer: None,
+            thumbnails: ThumbnailScheduler::default(),
             posts: Vec::new(),
             selected_post: None,
+            browsing: None,
             loading: false,
             video_player: None,
             /*
@@ -1,14 +1,15 @@
-use super::{message::ViewMessage, App, Message};
+use super::{
+    message::{SearchMessage, ViewMessage},
+    App, Message,
+};
+use iced::keyboard::{self, key::Named, Key};
 use iced::{event, mouse, window, Event, Subscription};
 
 impl App {
     pub fn subscription(&self) -> Subscription<Message> {
-        use iced::time;
-        use std::time::Duration;
-
         let mut subs = vec![];
 
-        subs.push(event::listen_with(|event, _, _| match event {
+        subs.push(event::listen_with(|event, status, _| match event {
             Event::Window(window::Event::CloseRequested) => Some(Message::Exit),
             Event::Window(window::Event::Resized(size)) => Some(Message::View(
                 ViewMessage::WindowResized(size.width.floor() as u32, size.height.floor() as u32),
@@ -19,11 +20,38 @@ impl App {
             Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Forward)) => {
                 Some(Message::View(ViewMessage::Forward))
             }
+            Event::Mouse(mouse::Event::WheelScrolled {
+                delta: mouse::ScrollDelta::Pixels { x, y },
+            }) if x.abs() > y.abs() => Some(Message::View(ViewMessage::Swiped(x))),
+            // Keys typed into a text input are captured, so they aren't taken as shortcuts.
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                modified_key,
+                modifiers,
+                ..
+            }) if status == event::Status::Ignored => Some(Message::View(ViewMessage::KeyPressed(
+                modified_key,
+                modifiers,
+            ))),
             _ => None,
         }));
 
-        if !self.search.thumbnail_queue.is_empty() {
-            subs.push(time::every(Duration::from_millis(50)).map(|_| Message::Tick));
+        if !self.search.suggestions.is_empty() {
+            subs.push(event::listen_with(|event, _, _| match event {
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key: Key::Named(key),
+                    modifiers,
+                    ..
+                }) => match key {
+                    Named::ArrowDown => Some(SearchMessage::SelectSuggestion(1)),
+                    Named::ArrowUp => Some(SearchMessage::SelectSuggestion(-1)),
+                    Named::Tab if modifiers.shift() => Some(SearchMessage::SelectSuggestion(-1)),
+                    Named::Tab => Some(SearchMessage::AcceptSuggestion(None)),
+                    Named::Escape => Some(SearchMessage::DismissSuggestions),
+                    _ => None,
+                }
+                .map(Message::Search),
+                _ => None,
+            }));
         }
 
         Subscription::batch(subs)
@@ -1,36 +1,81 @@
 use crate::app::message::{
-    DetailMessage, FollowedMessage, MediaMessage, Message, PostMessage, SearchMessage,
-    SettingsMessage, ViewMessage,
+    DetailMessage, FollowedMessage, MediaMessage, Message, PoolMessage, PostMessage, SearchMessage,
+    SettingsMessage, ViewMessage, WikiMessage,
+};
+use crate::app::state::{
+    App, BlacklistSync, CommentDraft, ResultList, ViewMode, GRID_PADDING, GRID_SCROLLABLE,
+    SEARCH_INPUT,
 };
-use crate::app::state::{App, ViewMode};
 use crate::core::api::{
-    favorite_post, fetch_comments, fetch_posts, unfavorite_post, vote_post, FetchPoint,
+    autocomplete_tags, create_comment, favorite_post, fetch_all_comments, fetch_blacklisted_tags,
+    fetch_pool, fetch_pool_posts, fetch_post, fetch_posts, fetch_wiki, search_pools,
+    unfavorite_post, update_blacklisted_tags, update_comment, vote_comment, vote_post, ApiError,
+    FetchPoint,
 };
-use crate::core::config::Auth;
-use crate::core::followed::compose_vec;
+use crate::core::autocomplete::{complete, partial_tag};
+use crate::core::blacklist::{Blacklist, CompiledBlacklist};
+use crate::core::config::{ApiHost, Auth, ConfigError};
+use crate::core::dtext::cache::DTextSource;
+use crate::core::dtext::link_target;
+use crate::core::followed::{compose_vec, FollowedPool, PoolUpdate};
+use crate::core::keymap::Action;
 use crate::core::media::{fetch_gif, fetch_image, fetch_video};
 use crate::core::media::{fetch_preview, fetch_sample};
-use crate::core::model::{Post, PostType};
-use crate::core::store::poststore_path;
-use crate::core::{blacklist, followed, media};
-use crate::gui::video_player::VideoPlayerWidget;
+use crate::core::model::{Comment, Post, PostType, Vote};
+use crate::core::{followed, media};
+use crate::gui::post_tile;
+use crate::gui::video_player::{VideoPlayerMessage, VideoPlayerWidget};
+use iced::widget::operation::{focus, move_cursor_to_end, scroll_to, AbsoluteOffset};
+use iced::widget::text_editor::Content;
 use iced::{clipboard, window, Task};
-use tracing::{debug, error, info, instrument, trace, warn};
+use rustc_hash::FxHashMap;
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, instrument, warn};
+
+/// How long typing has to pause before looking up tag suggestions.
+const AUTOCOMPLETE_DELAY: Duration = Duration::from_millis(250);
+/// How close the detail view gets to the end of a search before fetching the next page.
+const PREFETCH_DISTANCE: usize = 3;
+/// How close to the bottom of the grid scrolling gets before fetching the next page, in pixels.
+const LOAD_MORE_DISTANCE: f32 = 600.0;
+/// Horizontal scrolling needed to swipe to another post.
+const SWIPE_DISTANCE: f32 = 150.0;
+/// A pause in scrolling longer than this starts a new swipe.
+const SWIPE_GAP: Duration = Duration::from_millis(200);
+/// Scrolling is ignored for this long after a swipe, so its momentum doesn't swipe again.
+const SWIPE_COOLDOWN: Duration = Duration::from_millis(600);
+/// Thumbnail owner prefix for a search's results, followed by the query.
+const SEARCH_OWNER: &str = "search:";
+/// Thumbnail owner prefix for a pool's posts, followed by its ID.
+const POOL_OWNER: &str = "pool:";
+/// Thumbnail owner for posts in the followed view.
+const FOLLOWED_OWNER: &str = "followed";
+
+fn search_owner(query: &str) -> String {
+    format!("{SEARCH_OWNER}{query}")
+}
 
 impl App {
     #[instrument(skip_all)]
     pub fn update(&mut self, message: Message) -> Task<Message> {
-        match message {
+        let task = match message {
             Message::Search(msg) => self.update_search(msg),
             Message::Post(msg) => self.update_post(msg),
             Message::Media(msg) => self.update_media(msg),
             Message::Detail(msg) => self.update_detail(msg),
             Message::Settings(msg) => self.update_settings(msg),
             Message::Followed(msg) => self.update_followed(msg),
+            Message::Pool(msg) => self.update_pool(msg),
+            Message::Wiki(msg) => self.update_wiki(msg),
             Message::View(msg) => self.update_view(msg),
-            Message::Tick => self.tick(),
+            // Only wakes the app up, to start thumbnail retries that are due.
+            Message::Tick => Task::none(),
             Message::Exit => self.exit(),
-        }
+        };
+        self.flush_store();
+        self.parse_dtext();
+        Task::batch([task, self.start_thumbnails()])
     }
 
     fn update_search(&mut self, msg: SearchMessage) -> Task<Message> {
@@ -41,72 +86,102 @@ impl App {
                 self.ui.view_mode = ViewMode::Grid(query.clone(), self.search.page);
                 if query != self.search.query {
                     self.posts.clear();
+                    self.search.show_hidden = false;
                 }
+                // Thumbnails for other searches can wait until they're shown again.
+                let owner = search_owner(&query);
+                self.thumbnails
+                    .retain_owners(|o| !o.starts_with(SEARCH_OWNER) || o == owner);
+                // Paging starts over from the first page.
+                self.search.loading_more = None;
+                self.search.exhausted = false;
+                self.search.last_id = None;
                 self.search.query = query.clone();
                 self.search.input = query.clone();
-                let auth = self.config.auth.clone();
-                return Task::perform(
-                    async move { fetch_posts(auth.as_ref(), query.clone(), None).await },
-                    move |res| match res {
-                        Ok(posts) => Message::Search(SearchMessage::PostsLoaded(posts)),
-                        Err(err) => {
-                            error!("Error fetching posts: {err}");
-                            Message::Tick
-                        }
-                    },
-                );
-            }
-            SearchMessage::LoadMorePosts => {
-                let fetch_point = match self.search.page {
-                    Some(page_number) => {
-                        self.search.page = Some(page_number + 1);
-                        self.ui.view_mode =
-                            ViewMode::Grid(self.search.query.clone(), self.search.page);
-                        Some(FetchPoint::Page(page_number + 1))
+
+                // Coming back to a grid from a post, go back to where it was scrolled to.
+                let restore_scroll = match &self.ui.grid_scroll {
+                    Some((scrolled, offset)) if *scrolled == query => {
+                        scroll_to(GRID_SCROLLABLE, *offset)
                     }
-                    None => {
-                        self.search.page = Some(2);
-                        self.ui.view_mode = ViewMode::Grid(self.search.query.clone(), Some(2));
-                        Some(FetchPoint::Page(2))
+                    _ => {
+                        self.ui.grid_viewport = None;
+                        Task::none()
                     }
                 };
+
+                return Task::batch([self.fetch_search(query, None), restore_scroll]);
+            }
+            SearchMessage::LoadMorePosts => {
+                let Some(task) = self.fetch_next_page() else {
+                    return Task::none();
+                };
+                self.ui.view_mode = ViewMode::Grid(self.search.query.clone(), self.search.page);
                 self.loading = true;
-                let query = self.search.input.clone();
-                let auth = self.config.auth.clone();
-                return Task::perform(
-                    async move { fetch_posts(auth.as_ref(), query.clone(), fetch_point).await },
-                    move |res| match res {
-                        Ok(posts) => Message::Search(SearchMessage::PostsLoaded(posts)),
-                        Err(err) => {
-                            error!("Error fetching posts: {err}");
-                            Message::Tick
-                        }
-                    },
-                );
+                return task;
             }
-            SearchMessage::PostsLoaded(posts) => {
+            SearchMessage::PostsLoaded {
+                query,
+                point,
+                posts,
+            } => {
+                // Results for a search that's since been left, or paging that's since started over,
+                // would land in the wrong list.
+                if query != self.search.query
+                    || point.is_some_and(|point| self.search.loading_more != Some(point))
+                {
+                    debug!(%query, ?point, "Ignoring results for an old search");
+                    return Task::none();
+                }
                 self.loading = false;
-                let filtered = posts
-                    .into_iter()
-                    .filter(|p| !blacklist::is_blacklisted(p, &self.config.blacklist))
-                    .collect::<Vec<Post>>();
+                if point.is_some() {
+                    // Only counted once it's arrived, so a failed page is asked for again.
+                    self.search.page = Some(self.search.page.unwrap_or(1) + 1);
+                    self.search.loading_more = None;
+                    self.search.exhausted = posts.is_empty();
+                }
+                let fetched_ids = posts.iter().map(|p| p.id).collect::<Vec<u32>>();
+                self.store.record_fetched(&self.search.query, &fetched_ids);
+                // A first page arriving after later ones mustn't move paging back.
+                if let Some(&last) = fetched_ids.last() {
+                    if point.is_some() || self.search.last_id.is_none() {
+                        self.search.last_id = Some(last);
+                    }
+                }
+
+                let mut hidden: Vec<(u32, String)> = Vec::new();
+                let mut filtered: Vec<Post> = Vec::new();
+                for post in posts {
+                    if let Some(rule) = self.blacklist.matching_rule(&post) {
+                        hidden.push((post.id, rule.to_string()));
+                        self.store.insert_post(post);
+                    } else {
+                        filtered.push(post);
+                    }
+                }
+                if !hidden.is_empty() {
+                    debug!("{} posts hidden by blacklist", hidden.len());
+                    self.store.update_hidden(&self.search.query, &hidden);
+                }
                 self.store.insert_posts(filtered.clone());
 
                 let mut post_ids: Vec<u32> = Vec::new();
 
+                let owner = search_owner(&self.search.query);
                 let mut queued_post_count: usize = 0;
                 for post in &filtered {
                     post_ids.push(post.id);
                     if post.is_favorited {
                         self.store.set_favorite(post.id, true);
                     }
-                    if post.preview.url.is_some() && !self.store.thumbnails.contains_key(&post.id) {
-                        self.search.thumbnail_queue.push_back(post.id);
+                    if self.queue_thumbnail(post.id, &owner) {
                         queued_post_count += 1;
                     }
                 }
 
                 self.store.update_results(&self.search.query, &post_ids);
+                self.thumbnails
+                    .set_visible(self.posts_in_view(&self.search.query));
 
                 let new_posts = filtered
                     .into_iter()
@@ -116,28 +191,103 @@ impl App {
 
                 info!("Loading thumbnails for {queued_post_count} posts");
             }
+            SearchMessage::LoadFailed(message) => {
+                self.loading = false;
+                self.search.loading_more = None;
+                self.ui.error = Some(message);
+            }
             SearchMessage::InputChanged(text) => {
                 self.search.input = text;
+                self.search.selected_suggestion = None;
+                self.search.keystrokes += 1;
+
+                let Some(prefix) = partial_tag(&self.search.input).map(|p| p.name.to_lowercase())
+                else {
+                    self.search.suggestions.clear();
+                    return Task::none();
+                };
+                if let Some(cached) = self.store.get_suggestions(&prefix) {
+                    self.search.suggestions = cached.to_vec();
+                    return Task::none();
+                }
+
+                let keystroke = self.search.keystrokes;
+                return Task::perform(tokio::time::sleep(AUTOCOMPLETE_DELAY), move |_| {
+                    Message::Search(SearchMessage::AutocompleteDue(keystroke))
+                });
+            }
+            SearchMessage::AutocompleteDue(keystroke) => {
+                let prefix = partial_tag(&self.search.input).map(|p| p.name.to_lowercase());
+                let Some(prefix) = prefix.filter(|_| keystroke == self.search.keystrokes) else {
+                    return Task::none();
+                };
+
+                let base_url = self.config.host.base_url().to_string();
+                let auth = self.config.auth.clone();
+                return Task::perform(
+                    async move {
+                        let tags = autocomplete_tags(&base_url, auth.as_ref(), &prefix).await;
+                        (prefix, tags)
+                    },
+                    |(prefix, res)| match res {
+                        Ok(tags) => Message::Search(SearchMessage::SuggestionsLoaded(prefix, tags)),
+                        Err(err) => {
+                            warn!("Autocomplete for {prefix} failed: {err}");
+                            Message::Tick
+                        }
+                    },
+                );
+            }
+            SearchMessage::SuggestionsLoaded(prefix, tags) => {
+                let current = partial_tag(&self.search.input).map(|p| p.name.to_lowercase());
+                if current.as_deref() == Some(prefix.as_str()) {
+                    self.search.suggestions = tags.clone();
+                    self.search.selected_suggestion = None;
+                }
+                self.store.insert_suggestions(&prefix, tags);
+            }
+            SearchMessage::SelectSuggestion(offset) => {
+                let count = self.search.suggestions.len();
+                if count == 0 {
+                    return Task::none();
+                }
+                self.search.selected_suggestion = Some(match self.search.selected_suggestion {
+                    Some(i) => (i as isize + offset).rem_euclid(count as isize) as usize,
+                    None if offset < 0 => count - 1,
+                    None => 0,
+                });
+            }
+            SearchMessage::AcceptSuggestion(index) => {
+                let index = index.or(self.search.selected_suggestion).unwrap_or(0);
+                if let Some(suggestion) = self.search.suggestions.get(index) {
+                    self.search.input = complete(&self.search.input, &suggestion.name);
+                }
+                self.search.suggestions.clear();
+                self.search.selected_suggestion = None;
+                return move_cursor_to_end(SEARCH_INPUT);
+            }
+            SearchMessage::DismissSuggestions => {
+                self.search.suggestions.clear();
+                self.search.selected_suggestion = None;
             }
             SearchMessage::Submitted => {
+                if self.search.selected_suggestion.is_some() {
+                    return Task::done(Message::Search(SearchMessage::AcceptSuggestion(None)));
+                }
+                self.search.suggestions.clear();
+                self.search.keystrokes += 1;
                 self.ui.history.proceed(self.ui.view_mode.clone());
                 let query = self.search.input.trim().to_string();
                 self.search.page = Some(1);
+                self.search.show_hidden = false;
+                self.search.loading_more = None;
+                self.search.exhausted = false;
+                self.search.last_id = None;
                 self.search.query = query.clone();
                 self.ui.view_mode = ViewMode::Grid(query.clone(), self.search.page);
                 if !query.is_empty() {
                     info!("Submitting search for {query}");
-                    let auth = self.config.auth.clone();
-                    return Task::perform(
-                        async move { fetch_posts(auth.as_ref(), query.clone(), None).await },
-                        move |res| match res {
-                            Ok(posts) => Message::Search(SearchMessage::PostsLoaded(posts)),
-                            Err(err) => {
-                                error!("{err}");
-                                Message::Search(SearchMessage::LoadPosts(String::new()))
-                            }
-                        },
-                    );
+                    return self.fetch_search(query, None);
                 }
             }
             SearchMessage::GetFavorites => {
@@ -151,6 +301,20 @@ impl App {
                     .proceed(ViewMode::Grid(query.clone(), self.search.page));
                 return Task::done(Message::Search(SearchMessage::LoadPosts(query)));
             }
+            SearchMessage::ToggleHidden => {
+                self.search.show_hidden = !self.search.show_hidden;
+                if self.search.show_hidden {
+                    let hidden = self
+                        .store
+                        .get_hidden(&self.search.query)
+                        .map(|hidden| hidden.iter().map(|(id, _)| *id).collect::<Vec<u32>>())
+                        .unwrap_or_default();
+                    let owner = search_owner(&self.search.query);
+                    for id in hidden {
+                        self.queue_thumbnail(id, &owner);
+                    }
+                }
+            }
         }
         Task::none()
     }
@@ -158,112 +322,86 @@ impl App {
     fn update_post(&mut self, msg: PostMessage) -> Task<Message> {
         match msg {
             PostMessage::View(id) => {
+                if self.selected_post != Some(id) {
+                    self.comment_draft = CommentDraft::default();
+                }
                 self.ui.view_mode = ViewMode::Detail(id);
                 self.selected_post = Some(id);
+                self.ui.highlighted = Some(id);
                 info!("Selected post {id}");
 
+                if self.store.get_post(id).is_none() {
+                    // Linked from a description or comment, so it may not have been loaded yet.
+                    let base_url = self.config.host.base_url().to_string();
+                    return Task::perform(
+                        async move { fetch_post(&base_url, None, id).await },
+                        move |res| match res {
+                            Ok(post) => Message::Post(PostMessage::Loaded(post)),
+                            Err(err) => {
+                                error!("Getting post {id} failed: {err}");
+                                Message::View(ViewMessage::ShowError(format!(
+                                    "Couldn't load post #{id}: {err}"
+                                )))
+                            }
+                        },
+                    );
+                }
+
                 // Build task batch
                 let mut commands = vec![];
 
-                if self.store.is_favorited(id) {
+                let unmarked = self
+                    .store
+                    .get_post(id)
+                    .is_some_and(|post| !post.is_favorited);
+                if unmarked && self.store.is_favorited(id) {
                     self.store.get_post_mut(id).unwrap().is_favorited = true;
                 }
 
-                if let Some(post) = self.store.get_post(id) {
-                    // TODO: Deal with .swfs for compatiblity.
-                    // *Maaaaaaaybe* ruffle support? Doubt it.
-                    match post.file.ext.as_deref() {
-                        Some("gif") => {
-                            if !self.store.has_gif(id) {
-                                let url = post.file.url.clone().unwrap();
-                                commands.push(Task::perform(
-                                    fetch_gif(id, url),
-                                    move |res| match res {
-                                        Ok(gif) => Message::Media(MediaMessage::GifLoaded(id, gif)),
-                                        Err(err) => {
-                                            error!("Gif {id} failed: {err}");
-                                            Message::Tick
-                                        }
-                                    },
-                                ));
-                            }
-                        }
-                        Some("webm") | Some("mp4") => {
-                            let url: String = if !self.store.has_video(id) {
-                                post.file.url.clone().unwrap()
-                            } else {
-                                self.store.get_video(id).unwrap().to_string()
-                            };
-                            commands.push(Task::perform(
-                                fetch_video(id, url, post.file.ext.clone().unwrap()),
-                                move |res| match res {
-                                    Ok(url) => Message::Media(MediaMessage::VideoLoaded(id, url)),
-                                    Err(err) => {
-                                        error!("Video {id} failed: {err}");
-                                        Message::Tick
-                                    }
-                                },
-                            ));
-                        }
-                        Some("swf") => {}
-                        _ => {
-                            if !self.store.has_image(id) {
-                                if self.config.view.download_sample {
-                                    commands.push(Task::perform(
-                                        fetch_sample(id, post.sample.clone()),
-                                        move |res| match res {
-                                            Ok(handle) => Message::Media(
-                                                MediaMessage::SampleLoaded(id, handle),
-                                            ),
-                                            Err(err) => {
-                                                error!("Sample {id} failed: {err}");
-                                                Message::Tick
-                                            }
-                                        },
-                                    ));
-                                }
-                                if self.config.view.download_fullsize {
-                                    commands.push(Task::perform(
-                                        fetch_image(id, post.file.clone()),
-                                        move |res| match res {
-                                            Ok(handle) => Message::Media(
-                                                MediaMessage::ImageLoaded(id, handle),
-                                            ),
-                                            Err(err) => {
-                                                error!("Image {id} failed: {err}");
-                                                Message::Tick
-                                            }
-                                        },
-                                    ));
-                                }
+                commands.extend(self.media_tasks(id, false));
+                if self.store.comments_need_refresh(id) {
+                    commands.push(self.comments_task(id));
+                }
+
+                // Get the posts on either side ready, and more results before running out.
+                let (previous, next) = self.neighbours();
+                for neighbour in [next, previous].into_iter().flatten() {
+                    commands.extend(self.media_tasks(neighbour, true));
+                }
+                if let Some(task) = self.fetch_more_near_end() {
+                    commands.push(task);
+                }
+
+                // Reading a pool: get the next pages ready.
+                if self.pools.read_mode {
+                    if let Some(pool) = &self.pools.current {
+                        if let Some(position) = pool.position(id) {
+                            for &next in pool.post_ids.iter().skip(position + 1).take(2) {
+                                commands.extend(self.media_tasks(next, true));
                             }
                         }
                     }
-                    commands.push(Task::perform(
-                        fetch_comments(None, id, None),
-                        move |res| match res {
-                            Ok(comments) => {
-                                Message::Detail(DetailMessage::CommentsLoaded(comments))
-                            }
-                            Err(err) => {
-                                error!("Getting comments for {id} failed: {err}");
-                                Message::Tick
-                            }
-                        },
-                    ));
                 }
 
                 return Task::batch(commands);
             }
+            PostMessage::Loaded(post) => {
+                let id = post.id;
+                self.store.insert_post(post);
+                if self.selected_post == Some(id) {
+                    return Task::done(Message::Post(PostMessage::View(id)));
+                }
+            }
             PostMessage::Vote(id, vote) => {
                 let auth = self.config.auth.clone().unwrap_or_default();
+                let base_url = self.config.host.base_url().to_string();
                 return Task::perform(
-                    async move { vote_post(&auth, id, vote).await },
+                    async move { vote_post(&base_url, &auth, id, vote).await },
                     move |res| match res {
                         Ok(v) => Message::Post(PostMessage::VoteResult(id, v)),
                         Err(err) => {
-                            error!("{err}");
-                            Message::Tick
+                            error!("Voting on {id} failed: {err}");
+                            Message::View(ViewMessage::ShowError(format!("Couldn't vote: {err}")))
                         }
                     },
                 );
@@ -271,25 +409,30 @@ impl App {
             PostMessage::Favorite(id) => {
                 let is_favorite = self.store.is_favorited(id);
                 let auth = self.config.auth.clone().unwrap_or_default();
+                let base_url = self.config.host.base_url().to_string();
                 if is_favorite {
                     return Task::perform(
-                        async move { unfavorite_post(&auth, id).await },
+                        async move { unfavorite_post(&base_url, &auth, id).await },
                         move |res| match res {
                             Ok(()) => Message::Post(PostMessage::FavoriteResult(id, false)),
                             Err(err) => {
-                                error!("{err}");
-                                Message::Tick
+                                error!("Favoriting {id} failed: {err}");
+                                Message::View(ViewMessage::ShowError(format!(
+                                    "Couldn't unfavorite: {err}"
+                                )))
                             }
                         },
                     );
                 } else {
                     return Task::perform(
-                        async move { favorite_post(&auth, id).await },
+                        async move { favorite_post(&base_url, &auth, id).await },
                         move |res| match res {
                             Ok(()) => Message::Post(PostMessage::FavoriteResult(id, true)),
                             Err(err) => {
-                                error!("{err}");
-                                Message::Tick
+                                error!("Favoriting {id} failed: {err}");
+                                Message::View(ViewMessage::ShowError(format!(
+                                    "Couldn't favorite: {err}"
+                                )))
                             }
                         },
                     );
@@ -310,8 +453,14 @@ impl App {
         match msg {
             MediaMessage::ThumbnailLoaded(id, handle) => {
                 debug!("Storing thumbnail for {id}");
+                self.thumbnails.finished(id, true, Instant::now());
                 self.store.insert_thumbnail(id, handle);
             }
+            MediaMessage::ThumbnailFailed(id) => {
+                if let Some(delay) = self.thumbnails.finished(id, false, Instant::now()) {
+                    return Task::perform(tokio::time::sleep(delay), |_| Message::Tick);
+                }
+            }
             MediaMessage::SampleLoaded(id, handle) => {
                 debug!("Storing sample for {id}");
                 self.store.insert_sample(id, handle);
@@ -321,7 +470,7 @@ impl App {
                 self.store.insert_image(id, handle);
             }
             MediaMessage::GifLoaded(id, gif) => {
-                let frames = iced_gif::Frames::from_bytes(gif.clone());
+                let frames = iced_gif::Frames::from_bytes(gif);
                 if let Ok(f) = frames {
                     debug!("Storing gif for {id}");
                     self.store.gif_frames.insert(id, f);
@@ -329,7 +478,7 @@ impl App {
                     error!("Couldn't decode gif into frames");
                 }
 
-                self.store.insert_gif(id, gif);
+                self.store.insert_gif(id);
             }
             MediaMessage::VideoLoaded(id, url) => {
                 debug!("Storing video for {id}");
@@ -383,19 +532,126 @@ impl App {
                     Some(1),
                 ))));
             }
-            DetailMessage::CommentsLoaded(comments) => {
-                for comment in comments {
-                    if let Some(comment_vec) = self.store.get_comments(comment.post_id) {
-                        if comment_vec.contains(&comment) {
-                            continue;
-                        }
+            DetailMessage::CommentsLoaded(post_id, comments) => {
+                self.store.set_comments(post_id, comments);
+            }
+            DetailMessage::RefreshComments => {
+                if let Some(id) = self.selected_post {
+                    return self.comments_task(id);
+                }
+            }
+            DetailMessage::CommentEdited(action) => {
+                self.comment_draft.content.perform(action);
+            }
+            DetailMessage::EditComment(id) => {
+                let comment = self
+                    .selected_post
+                    .and_then(|post_id| self.store.get_comments(post_id))
+                    .and_then(|comments| comments.iter().find(|comment| comment.id == id));
+                if let Some(comment) = comment {
+                    self.comment_draft = CommentDraft {
+                        editing: Some(id),
+                        content: Content::with_text(&comment.body),
+                        ..Default::default()
+                    };
+                }
+            }
+            DetailMessage::CancelComment => {
+                self.comment_draft = CommentDraft::default();
+            }
+            DetailMessage::ToggleCommentPreview => {
+                self.comment_draft.preview = !self.comment_draft.preview;
+            }
+            DetailMessage::SubmitComment => {
+                let Some(post_id) = self.selected_post else {
+                    return Task::none();
+                };
+                let Some(auth) = self.config.auth.clone() else {
+                    return Task::done(Message::View(ViewMessage::ShowError(
+                        "Log in to comment".into(),
+                    )));
+                };
+                let body = self.comment_draft.content.text().trim_end().to_string();
+                if body.is_empty() || self.comment_draft.sending {
+                    return Task::none();
+                }
+
+                self.comment_draft.sending = true;
+                let base_url = self.config.host.base_url().to_string();
+                let on_result = |res: Result<Comment, ApiError>| match res {
+                    Ok(comment) => Message::Detail(DetailMessage::CommentSaved(comment)),
+                    Err(err) => {
+                        error!("Saving comment failed: {err}");
+                        Message::Detail(DetailMessage::CommentFailed(format!(
+                            "Couldn't save comment: {err}"
+                        )))
                     }
-                    self.store.insert_comment(comment);
+                };
+
+                let editing = self.comment_draft.editing.and_then(|id| {
+                    let comments = self.store.get_comments(post_id)?;
+                    comments.iter().find(|comment| comment.id == id).cloned()
+                });
+                return match editing {
+                    Some(mut comment) => Task::perform(
+                        async move {
+                            update_comment(&base_url, &auth, comment.id, &body).await?;
+                            comment.body = body;
+                            Ok(comment)
+                        },
+                        on_result,
+                    ),
+                    None => Task::perform(
+                        async move { create_comment(&base_url, &auth, post_id, &body).await },
+                        on_result,
+                    ),
+                };
+            }
+            DetailMessage::CommentSaved(comment) => {
+                info!("Saved comment {} on post {}", comment.id, comment.post_id);
+                self.store.insert_comment(comment);
+                self.comment_draft = CommentDraft::default();
+            }
+            DetailMessage::CommentFailed(message) => {
+                self.comment_draft.sending = false;
+                self.ui.error = Some(message);
+            }
+            DetailMessage::VoteComment(post_id, id, vote) => {
+                let auth = self.config.auth.clone().unwrap_or_default();
+                let base_url = self.config.host.base_url().to_string();
+                return Task::perform(
+                    async move { vote_comment(&base_url, &auth, id, vote).await },
+                    move |res| match res {
+                        Ok(v) => Message::Detail(DetailMessage::CommentVoteResult(post_id, id, v)),
+                        Err(err) => {
+                            error!("Voting on comment {id} failed: {err}");
+                            Message::View(ViewMessage::ShowError(format!("Couldn't vote: {err}")))
+                        }
+                    },
+                );
+            }
+            DetailMessage::CommentVoteResult(post_id, id, vote) => {
+                let score = |vote: Option<Vote>| vote.map_or(0, |vote| vote as i32);
+                let change = score(vote) - score(self.store.comment_vote_for(id));
+                if let Some(comment) = self.store.get_comment_mut(post_id, id) {
+                    comment.score += change;
+                }
+                self.store.set_comment_vote(id, vote);
+            }
+            DetailMessage::NextPost | DetailMessage::PreviousPost => {
+                let (previous, next) = self.neighbours();
+                let target = match msg {
+                    DetailMessage::NextPost => next,
+                    _ => previous,
+                };
+                if let Some(id) = target {
+                    self.video_player = None;
+                    return Task::done(Message::Post(PostMessage::View(id)));
                 }
             }
             DetailMessage::CopyURL => {
                 if let Some(post) = self.selected_post {
-                    let url = format!("https://e621.net/posts/{}", post);
+                    let url = format!("{}/posts/{post}", self.config.host.base_url());
                     info!("Copying {url} to clipboard");
                     return clipboard::write(url);
                 }
@@ -479,7 +735,14 @@ impl App {
                 self.settings.api_key = key;
             }
             SettingsMessage::BlacklistEdited(action) => {
+                let edited = action.is_edit();
                 self.settings.blacklist_content.perform(action);
+                if edited {
+                    let editor = self.editor_blacklist();
+                    if let Some(sync) = &mut self.settings.blacklist_sync {
+                        sync.update(&editor);
+                    }
+                }
             }
             SettingsMessage::FollowFieldChanged(field) => {
                 self.followed.new_followed_tag = field;
@@ -497,20 +760,11 @@ impl App {
                     api_key: self.settings.api_key.clone(),
                 });
 
-                let blacklist = self
-                    .settings
-                    .blacklist_content
-                    .text()
-                    .lines()
-                    .map(str::trim)
-                    .filter(|line| !line.is_empty())
-                    .map(str::to_string)
-                    .collect::<Vec<String>>();
-                self.config.blacklist.rules = blacklist;
+                self.set_blacklist(self.editor_blacklist().rules);
 
                 self.config.followed_tags = compose_vec(self.followed.tags.clone());
 
-                if let Err(err) = self.config.save() {
+                if let Err(err) = self.save_config() {
                     warn!("Failed to save config: {err}");
                 }
 
@@ -524,6 +778,123 @@ impl App {
             SettingsMessage::PurgeCache => {
                 let _purge_result = self.store.purge();
             }
+            SettingsMessage::FetchAccountBlacklist => {
+                let Some(auth) = self.config.auth.clone() else {
+                    self.settings.sync_status = Some("Save your e621 login first.".into());
+                    return Task::none();
+                };
+                self.settings.sync_status = Some("Fetching account blacklist...".into());
+                let base_url = self.config.host.base_url().to_string();
+                return Task::perform(
+                    async move { fetch_blacklisted_tags(&base_url, &auth).await },
+                    |res| match res {
+                        Ok(rules) => {
+                            Message::Settings(SettingsMessage::AccountBlacklistLoaded(rules))
+                        }
+                        Err(err) => {
+                            error!("Fetching account blacklist failed: {err}");
+                            Message::Settings(SettingsMessage::BlacklistSyncFailed(err.to_string()))
+                        }
+                    },
+                );
+            }
+            SettingsMessage::AccountBlacklistLoaded(rules) => {
+                self.review_sync(rules, false);
+            }
+            SettingsMessage::MergeAccountBlacklist => {
+                if let Some(BlacklistSync {
+                    account: remote, ..
+                }) = self.settings.blacklist_sync.take()
+                {
+                    let merged = self.editor_blacklist().merged(&remote);
+                    info!("Merging account blacklist ({} rules)", merged.rules.len());
+                    self.apply_account_blacklist(merged.rules);
+                    self.settings.sync_status = Some("Merged account blacklist.".into());
+                }
+            }
+            SettingsMessage::ReplaceWithAccountBlacklist => {
+                if let Some(BlacklistSync {
+                    account: remote, ..
+                }) = self.settings.blacklist_sync.take()
+                {
+                    info!(
+                        "Replacing blacklist with account's ({} rules)",
+                        remote.rules.len()
+                    );
+                    self.apply_account_blacklist(remote.rules);
+                    self.settings.sync_status = Some("Replaced with account blacklist.".into());
+                }
+            }
+            SettingsMessage::DiscardAccountBlacklist => {
+                self.settings.blacklist_sync = None;
+                self.settings.sync_status = None;
+            }
+            SettingsMessage::PushBlacklist => {
+                let Some(auth) = self.config.auth.clone() else {
+                    self.settings.sync_status = Some("Save your e621 login first.".into());
+                    return Task::none();
+                };
+                self.settings.sync_status = Some("Fetching account blacklist...".into());
+                let base_url = self.config.host.base_url().to_string();
+                return Task::perform(
+                    async move { fetch_blacklisted_tags(&base_url, &auth).await },
+                    |res| match res {
+                        Ok(rules) => Message::Settings(SettingsMessage::UploadReviewed(rules)),
+                        Err(err) => {
+                            error!("Fetching account blacklist failed: {err}");
+                            Message::Settings(SettingsMessage::BlacklistSyncFailed(err.to_string()))
+                        }
+                    },
+                );
+            }
+            SettingsMessage::UploadReviewed(rules) => {
+                self.review_sync(rules, true);
+            }
+            SettingsMessage::ConfirmPushBlacklist => {
+                let Some(auth) = self.config.auth.clone() else {
+                    self.settings.sync_status = Some("Save your e621 login first.".into());
+                    return Task::none();
+                };
+                if !self
+                    .settings
+                    .blacklist_sync
+                    .take()
+                    .is_some_and(|sync| sync.upload)
+                {
+                    return Task::none();
+                }
+                let rules = self.editor_blacklist().rules;
+                self.settings.sync_status = Some("Uploading blacklist...".into());
+                let base_url = self.config.host.base_url().to_string();
+                return Task::perform(
+                    async move { update_blacklisted_tags(&base_url, &auth, &rules).await },
+                    |res| match res {
+                        Ok(()) => Message::Settings(SettingsMessage::BlacklistPushed),
+                        Err(err) => {
+                            error!("Pushing blacklist failed: {err}");
+                            Message::Settings(SettingsMessage::BlacklistSyncFailed(err.to_string()))
+                        }
+                    },
+                );
+            }
+            SettingsMessage::BlacklistPushed => {
+                self.settings.sync_status = Some("Uploaded blacklist to account.".into());
+            }
+            SettingsMessage::BlacklistSyncFailed(err) => {
+                self.settings.sync_status = Some(format!("Sync failed: {err}"));
+            }
+            SettingsMessage::HostSelected(host) => {
+                self.config.host = match host {
+                    ApiHost::Custom(_) => ApiHost::Custom(self.settings.custom_host.clone()),
+                    preset => preset,
+                };
+            }
+            SettingsMessage::CustomHostChanged(url) => {
+                self.settings.custom_host = url.clone();
+                if let ApiHost::Custom(_) = self.config.host {
+                    self.config.host = ApiHost::Custom(url);
+                }
+            }
             SettingsMessage::PPRChanged(ppr) => {
                 self.config.view.posts_per_row = ppr;
             }
@@ -541,38 +912,83 @@ impl App {
                 self.ui.view_mode = ViewMode::Followed;
 
                 let tags = compose_vec(self.followed.tags.clone());
+                let pools = self.config.followed_pools.clone();
+                let base_url = self.config.host.base_url().to_string();
                 let auth = self.config.auth.clone();
 
-                return Task::perform(
-                    async move { followed::check_for_updates(&tags, auth.as_ref()).await },
+                let pool_task = {
+                    let base_url = base_url.clone();
+                    let auth = auth.clone();
+                    Task::perform(
+                        async move {
+                            followed::check_pool_updates(&base_url, &pools, auth.as_ref()).await
+                        },
+                        move |res| match res {
+                            Ok(updates) => {
+                                Message::Followed(FollowedMessage::PoolUpdatesReceived(updates))
+                            }
+                            Err(err) => {
+                                error!("Checking followed pools failed: {err}");
+                                Message::View(ViewMessage::ShowError(format!(
+                                    "Couldn't check followed pools: {err}"
+                                )))
+                            }
+                        },
+                    )
+                };
+
+                let tag_task = Task::perform(
+                    async move { followed::check_for_updates(&base_url, &tags, auth.as_ref()).await },
                     move |res| match res {
                         Ok(updates) => Message::Followed(FollowedMessage::UpdatesReceived(updates)),
                         Err(err) => {
-                            error!("{err}");
-                            Message::Tick
+                            error!("Checking followed tags failed: {err}");
+                            Message::View(ViewMessage::ShowError(format!(
+                                "Couldn't check followed tags: {err}"
+                            )))
                         }
                     },
                 );
+                return Task::batch([tag_task, pool_task]);
+            }
+            FollowedMessage::PoolUpdatesReceived(updates) => {
+                // Keep blacklisted posts in the store too, like those from followed tags.
+                for post in updates.iter().flat_map(|update| &update.posts) {
+                    self.store.insert_post(post.clone());
+                }
+                self.followed.pool_updates = updates;
+                self.followed.new_pool_pages = self.filter_pool_updates();
+
+                let ids = self
+                    .followed
+                    .new_pool_pages
+                    .iter()
+                    .flat_map(|update| &update.posts)
+                    .map(|post| post.id)
+                    .collect::<Vec<u32>>();
+                for id in ids {
+                    self.queue_thumbnail(id, FOLLOWED_OWNER);
+                }
             }
             FollowedMessage::UpdatesReceived(updates) => {
-                for (_, posts) in &updates {
-                    for post in posts {
-                        if blacklist::is_blacklisted(post, &self.config.blacklist) {
-                            continue;
-                        }
-                        let id = post.id;
-                        self.store.insert_post(post.clone());
-                        trace!("{post:?}");
-                        if post.preview.url.is_some()
-                            && !self.store.thumbnails.contains_key(&post.id)
-                        {
-                            trace!("Queueing thumbnail for {id}");
-                            self.search.thumbnail_queue.push_back(post.id);
-                        }
-                    }
+                // Keep blacklisted posts in the store too, so they can be restored if the
+                // blacklist changes later.
+                for post in updates.values().flatten() {
+                    self.store.insert_post(post.clone());
                 }
+                self.followed.received_posts = updates;
+                self.followed.new_followed_posts = self.filter_followed_posts();
 
-                self.followed.new_followed_posts = updates;
+                let ids = self
+                    .followed
+                    .new_followed_posts
+                    .values()
+                    .flatten()
+                    .map(|post| post.id)
+                    .collect::<Vec<u32>>();
+                for id in ids {
+                    self.queue_thumbnail(id, FOLLOWED_OWNER);
+                }
             }
             FollowedMessage::AddTag => {
                 let tag = self.followed.new_followed_tag.trim();
@@ -581,7 +997,7 @@ impl App {
                     self.followed.tags.insert(tag.to_string(), None);
 
                     self.config.followed_tags = compose_vec(self.followed.tags.clone());
-                    let _ = self.config.save();
+                    let _ = self.save_config();
                 }
                 self.followed.new_followed_tag.clear();
             }
@@ -589,22 +1005,210 @@ impl App {
                 self.followed.tags.insert(tag.to_string(), None);
 
                 self.config.followed_tags = compose_vec(self.followed.tags.clone());
-                let _ = self.config.save();
+                let _ = self.save_config();
             }
             FollowedMessage::RemoveTag(tag) => {
                 self.followed.tags.remove(&tag);
 
                 self.config.followed_tags = compose_vec(self.followed.tags.clone());
-                let _ = self.config.save();
+                let _ = self.save_config();
             }
             FollowedMessage::ClearSeenPosts => {
-                for (tag, posts) in self.followed.new_followed_posts.drain() {
+                self.followed.new_followed_posts.clear();
+                self.followed.new_pool_pages.clear();
+                for (tag, posts) in self.followed.received_posts.drain() {
                     if let Some(latest_post) = posts.first() {
                         if let Some(seen) = self.followed.tags.get_mut(&tag) {
                             *seen = Some(latest_post.id);
                         }
                     }
                 }
+                for update in self.followed.pool_updates.drain(..) {
+                    let Some(latest) = update.posts.last().map(|post| post.id) else {
+                        continue;
+                    };
+                    if let Some(followed) = self
+                        .config
+                        .followed_pools
+                        .iter_mut()
+                        .find(|followed| followed.id == update.pool.id)
+                    {
+                        followed.last_seen = Some(latest);
+                    }
+                }
+            }
+        }
+        Task::none()
+    }
+
+    fn update_pool(&mut self, msg: PoolMessage) -> Task<Message> {
+        match msg {
+            PoolMessage::SearchInputChanged(input) => {
+                self.pools.search_input = input;
+            }
+            PoolMessage::Search => {
+                let name = self.pools.search_input.clone();
+                let base_url = self.config.host.base_url().to_string();
+                let auth = self.config.auth.clone();
+                self.loading = true;
+                return Task::perform(
+                    async move { search_pools(&base_url, auth.as_ref(), &name, None).await },
+                    |res| match res {
+                        Ok(pools) => Message::Pool(PoolMessage::SearchResults(pools)),
+                        Err(err) => {
+                            error!("Searching pools failed: {err}");
+                            Message::Pool(PoolMessage::Failed(format!(
+                                "Couldn't search pools: {err}"
+                            )))
+                        }
+                    },
+                );
+            }
+            PoolMessage::SearchResults(pools) => {
+                self.loading = false;
+                self.pools.search_results = pools;
+            }
+            PoolMessage::Open(id) => {
+                self.selected_post = None;
+                self.video_player = None;
+                self.ui.view_mode = ViewMode::Pool(id);
+                if self
+                    .pools
+                    .current
+                    .as_ref()
+                    .is_some_and(|pool| pool.id == id)
+                {
+                    return Task::none();
+                }
+
+                let base_url = self.config.host.base_url().to_string();
+                let auth = self.config.auth.clone();
+                self.loading = true;
+                return Task::perform(
+                    async move {
+                        let pool = fetch_pool(&base_url, auth.as_ref(), id).await?;
+                        let posts = fetch_pool_posts(&base_url, auth.as_ref(), &pool).await?;
+                        Ok((pool, posts))
+                    },
+                    move |res| match res {
+                        Ok((pool, posts)) => Message::Pool(PoolMessage::Loaded(pool, posts)),
+                        Err(err) => {
+                            error!("Loading pool #{id} failed: {err}");
+                            Message::Pool(PoolMessage::Failed(format!("Couldn't load pool: {err}")))
+                        }
+                    },
+                );
+            }
+            PoolMessage::Loaded(pool, posts) => {
+                self.loading = false;
+                let owner = format!("{POOL_OWNER}{}", pool.id);
+                self.thumbnails
+                    .retain_owners(|o| !o.starts_with(POOL_OWNER) || o == owner);
+                for post in posts {
+                    let id = post.id;
+                    self.store.insert_post(post);
+                    self.queue_thumbnail(id, &owner);
+                }
+                info!("Loaded pool #{} ({} posts)", pool.id, pool.post_ids.len());
+                self.pools.current = Some(pool);
+            }
+            PoolMessage::Failed(message) => {
+                self.loading = false;
+                self.ui.error = Some(message);
+            }
+            PoolMessage::NextPage | PoolMessage::PreviousPage => {
+                let (Some(pool), Some(selected)) = (&self.pools.current, self.selected_post) else {
+                    return Task::none();
+                };
+                let Some(position) = pool.position(selected) else {
+                    return Task::none();
+                };
+                let visible = |id: &&u32| {
+                    self.store
+                        .get_post(**id)
+                        .is_some_and(|post| !self.blacklist.is_blacklisted(post))
+                };
+                let target = if matches!(msg, PoolMessage::NextPage) {
+                    pool.post_ids[position + 1..].iter().find(visible)
+                } else {
+                    pool.post_ids[..position].iter().rev().find(visible)
+                };
+                if let Some(&id) = target {
+                    self.video_player = None;
+                    return Task::done(Message::Post(PostMessage::View(id)));
+                }
+            }
+            PoolMessage::ToggleReadMode => {
+                self.pools.read_mode = !self.pools.read_mode;
+            }
+            PoolMessage::Follow => {
+                if let Some(pool) = &self.pools.current {
+                    if !self.config.followed_pools.iter().any(|f| f.id == pool.id) {
+                        info!("Following pool #{}", pool.id);
+                        self.config.followed_pools.push(FollowedPool::new(pool));
+                        let _ = self.save_config();
+                    }
+                }
+            }
+            PoolMessage::Unfollow(id) => {
+                self.config
+                    .followed_pools
+                    .retain(|followed| followed.id != id);
+                self.followed
+                    .pool_updates
+                    .retain(|update| update.pool.id != id);
+                self.followed
+                    .new_pool_pages
+                    .retain(|update| update.pool.id != id);
+                let _ = self.save_config();
+            }
+        }
+        Task::none()
+    }
+
+    fn update_wiki(&mut self, msg: WikiMessage) -> Task<Message> {
+        match msg {
+            WikiMessage::Open(tag) => {
+                self.selected_post = None;
+                self.video_player = None;
+                self.ui.view_mode = ViewMode::Wiki(tag.clone());
+                if self.wiki.as_ref().is_some_and(|wiki| wiki.tag == tag) {
+                    return Task::none();
+                }
+
+                let base_url = self.config.host.base_url().to_string();
+                let auth = self.config.auth.clone();
+                self.loading = true;
+                self.wiki_error = None;
+                return Task::perform(
+                    async move {
+                        let res = fetch_wiki(&base_url, auth.as_ref(), &tag).await;
+                        (tag, res)
+                    },
+                    |(tag, res)| match res {
+                        Ok(wiki) => Message::Wiki(WikiMessage::Loaded(wiki)),
+                        Err(err) => {
+                            error!("Loading wiki for {tag} failed: {err}");
+                            Message::Wiki(WikiMessage::Failed(tag, err.to_string()))
+                        }
+                    },
+                );
+            }
+            // A wiki requested earlier can arrive after the one now in view.
+            WikiMessage::Loaded(wiki) => {
+                if !self.viewing_wiki(&wiki.tag) {
+                    debug!(tag = %wiki.tag, "Ignoring wiki for a tag no longer in view");
+                    return Task::none();
+                }
+                self.loading = false;
+                self.wiki = Some(wiki);
+            }
+            WikiMessage::Failed(tag, err) => {
+                if !self.viewing_wiki(&tag) {
+                    return Task::none();
+                }
+                self.loading = false;
+                self.wiki_error = Some((tag, err));
             }
         }
         Task::none()
@@ -616,7 +1220,12 @@ impl App {
                 self.ui.history.proceed(self.ui.view_mode.clone());
                 match &mode {
                     ViewMode::Detail(id) => {
-                        return Task::done(Message::Post(PostMessage::View(*id)))
+                        self.browsing = self.opened_from(*id);
+                        return Task::done(Message::Post(PostMessage::View(*id)));
+                    }
+                    ViewMode::Pool(id) => return Task::done(Message::Pool(PoolMessage::Open(*id))),
+                    ViewMode::Wiki(tag) => {
+                        return Task::done(Message::Wiki(WikiMessage::Open(tag.clone())))
                     }
                     ViewMode::Grid(query, page) => {
                         self.search.query = query.clone();
@@ -642,6 +1251,10 @@ impl App {
                     ViewMode::Detail(id) => {
                         return Task::done(Message::Post(PostMessage::View(*id)))
                     }
+                    ViewMode::Pool(id) => return Task::done(Message::Pool(PoolMessage::Open(*id))),
+                    ViewMode::Wiki(tag) => {
+                        return Task::done(Message::Wiki(WikiMessage::Open(tag.clone())))
+                    }
                     ViewMode::Grid(query, page) => {
                         self.search.query = query.clone();
                         self.search.input = query.clone();
@@ -690,6 +1303,12 @@ impl App {
                         ViewMode::Detail(id) => {
                             return Task::done(Message::Post(PostMessage::View(*id)))
                         }
+                        ViewMode::Pool(id) => {
+                            return Task::done(Message::Pool(PoolMessage::Open(*id)))
+                        }
+                        ViewMode::Wiki(tag) => {
+                            return Task::done(Message::Wiki(WikiMessage::Open(tag.clone())))
+                        }
                         ViewMode::Grid(query, page) => {
                             self.search.query = query.clone();
                             self.search.input = query.clone();
@@ -705,54 +1324,616 @@ impl App {
             ViewMessage::UpdateTheme(theme) => {
                 self.config.view.theme = theme;
             }
+            ViewMessage::ShowError(message) => {
+                self.ui.error = Some(message);
+            }
+            ViewMessage::DismissError => {
+                self.ui.error = None;
+            }
+            ViewMessage::OpenUrl(href) => {
+                let Some(url) = link_target(self.config.host.base_url(), &href) else {
+                    warn!("Not opening {href:?}, which isn't a web link");
+                    return Task::none();
+                };
+                info!("Opening {url}");
+                if let Err(err) = open::that_detached(url.as_str()) {
+                    error!("Couldn't open {url}: {err}");
+                }
+            }
+            ViewMessage::ToggleSection(source, index) => self.dtext.toggle_section(&source, index),
+            ViewMessage::KeyPressed(key, modifiers) => {
+                // The suggestion list has its own arrow key handling.
+                if !self.search.suggestions.is_empty() {
+                    return Task::none();
+                }
+                if let Some(action) = self.config.keymap.action(&key, modifiers) {
+                    debug!(?action, "Shortcut");
+                    return self.shortcut(action);
+                }
+            }
+            ViewMessage::ToggleHelp => {
+                self.ui.show_help = !self.ui.show_help;
+            }
+            ViewMessage::GridScrolled(viewport) => {
+                self.ui.grid_viewport = Some(viewport);
+                if let ViewMode::Grid(query, _) = &self.ui.view_mode {
+                    self.ui.grid_scroll = Some((query.clone(), viewport.absolute_offset()));
+                    self.thumbnails.set_visible(self.posts_in_view(query));
+                }
+                let bottom = viewport.absolute_offset().y + viewport.bounds().height;
+                let near_end = viewport.content_bounds().height - bottom < LOAD_MORE_DISTANCE;
+                if near_end && matches!(self.ui.view_mode, ViewMode::Grid(..)) {
+                    return Task::done(Message::Search(SearchMessage::LoadMorePosts));
+                }
+            }
+            ViewMessage::Swiped(distance) => {
+                if !matches!(self.ui.view_mode, ViewMode::Detail(_)) {
+                    return Task::none();
+                }
+                let now = Instant::now();
+                let swipe = &mut self.ui.swipe;
+                if swipe.triggered.is_some_and(|at| now - at < SWIPE_COOLDOWN) {
+                    return Task::none();
+                }
+                let new_gesture = swipe.last_event.is_none_or(|at| now - at > SWIPE_GAP)
+                    || swipe.distance.signum() != distance.signum();
+                if new_gesture {
+                    swipe.distance = 0.0;
+                }
+                swipe.last_event = Some(now);
+                swipe.distance += distance;
+
+                if swipe.distance.abs() >= SWIPE_DISTANCE {
+                    // Content follows the fingers, so swiping left scrolls towards the next post.
+                    let message = match swipe.distance < 0.0 {
+                        true => DetailMessage::NextPost,
+                        false => DetailMessage::PreviousPost,
+                    };
+                    swipe.distance = 0.0;
+                    swipe.triggered = Some(now);
+                    return Task::done(Message::Detail(message));
+                }
+            }
         }
         Task::none()
     }
 
-    fn tick(&mut self) -> Task<Message> {
-        if let Some(post_id) = self.search.thumbnail_queue.pop_front() {
-            if let Some(post) = self.store.get_post(post_id) {
-                if let Some(url) = &post.preview.url {
-                    return Task::perform(
-                        fetch_preview(post_id, url.clone()),
-                        move |res| match res {
-                            Ok(thumb) => {
-                                Message::Media(MediaMessage::ThumbnailLoaded(post_id, thumb))
-                            }
-                            Err(err) => {
-                                error!("Failed to fetch thumbnail for {post_id}: {err}");
-                                Message::Tick
-                            }
-                        },
-                    );
+    /// Carries out a keyboard shortcut in the current view.
+    fn shortcut(&mut self, action: Action) -> Task<Message> {
+        if self.ui.show_help {
+            if matches!(action, Action::Back | Action::Help) {
+                self.ui.show_help = false;
+            }
+            return Task::none();
+        }
+
+        match action {
+            Action::Help => self.ui.show_help = true,
+            Action::Back => return Task::done(Message::View(ViewMessage::Back)),
+            Action::Forward => return Task::done(Message::View(ViewMessage::Forward)),
+            Action::Next | Action::Previous | Action::Up | Action::Down => {
+                return self.move_highlight(action)
+            }
+            Action::Open => {
+                if let (ViewMode::Grid(..), Some(id)) = (&self.ui.view_mode, self.ui.highlighted) {
+                    return Task::done(Message::View(ViewMessage::Show(ViewMode::Detail(id))));
+                }
+            }
+            Action::Favorite | Action::Upvote | Action::Downvote => {
+                let id = match self.ui.view_mode {
+                    ViewMode::Detail(id) => id,
+                    ViewMode::Grid(..) => match self.ui.highlighted {
+                        Some(id) => id,
+                        None => return Task::none(),
+                    },
+                    _ => return Task::none(),
+                };
+                let toggle = |vote| (self.store.vote_for(id) != Some(vote)).then_some(vote);
+                let message = match action {
+                    Action::Upvote => PostMessage::Vote(id, toggle(Vote::Upvote)),
+                    Action::Downvote => PostMessage::Vote(id, toggle(Vote::Downvote)),
+                    _ => PostMessage::Favorite(id),
+                };
+                return Task::done(Message::Post(message));
+            }
+            Action::FocusSearch => {
+                if matches!(self.ui.view_mode, ViewMode::Grid(..)) {
+                    return focus(SEARCH_INPUT);
+                }
+                let grid = ViewMode::Grid(self.search.query.clone(), self.search.page);
+                let show = self.update_view(ViewMessage::Show(grid));
+                return Task::batch([show, focus(SEARCH_INPUT)]);
+            }
+            Action::TogglePlayback => {
+                if self.video_player.is_some() {
+                    return Task::done(Message::Media(MediaMessage::VideoPlayerMsg(
+                        VideoPlayerMessage::TogglePause,
+                    )));
                 }
-                warn!("Queue entry has no preview");
             }
         }
         Task::none()
     }
 
+    /// Moves the highlighted tile in the grid, or goes to the neighbouring post in the detail
+    /// view.
+    fn move_highlight(&mut self, action: Action) -> Task<Message> {
+        let columns = post_tile::columns(
+            self.ui.window_width as usize,
+            self.config.view.posts_per_row,
+            self.config.view.tile_width,
+        ) as isize;
+        let step = match action {
+            Action::Next => 1,
+            Action::Previous => -1,
+            Action::Down => columns,
+            Action::Up => -columns,
+            _ => return Task::none(),
+        };
+
+        match &self.ui.view_mode {
+            ViewMode::Grid(query, _) => {
+                let ids = self.shown_results(query);
+                if ids.is_empty() {
+                    return Task::none();
+                }
+                let current = self
+                    .ui
+                    .highlighted
+                    .and_then(|id| ids.iter().position(|&shown| shown == id));
+                let index = match current {
+                    Some(i) => (i as isize + step).clamp(0, ids.len() as isize - 1) as usize,
+                    None => 0,
+                };
+                self.ui.highlighted = Some(ids[index]);
+                self.scroll_to_row(index / columns as usize)
+            }
+            ViewMode::Detail(_) if step == 1 => {
+                Task::done(Message::Detail(DetailMessage::NextPost))
+            }
+            ViewMode::Detail(_) if step == -1 => {
+                Task::done(Message::Detail(DetailMessage::PreviousPost))
+            }
+            _ => Task::none(),
+        }
+    }
+
+    /// The result list in the current view that post `id` is being opened from.
+    fn opened_from(&self, id: u32) -> Option<ResultList> {
+        match &self.ui.view_mode {
+            ViewMode::Grid(query, _) => Some(ResultList::Search(query.clone())),
+            ViewMode::Followed => self
+                .followed
+                .new_followed_posts
+                .iter()
+                .find(|(_, posts)| posts.iter().any(|post| post.id == id))
+                .map(|(tag, _)| ResultList::Followed(tag.clone())),
+            // A post linked from another one keeps the list, though it may not be in it.
+            ViewMode::Detail(_) => self.browsing.clone(),
+            _ => None,
+        }
+    }
+
+    /// Whether `tag`'s wiki is the one in view.
+    fn viewing_wiki(&self, tag: &str) -> bool {
+        matches!(&self.ui.view_mode, ViewMode::Wiki(viewed) if viewed == tag)
+    }
+
+    /// Fetches the next page of the current search, unless it's already loading or ran out.
+    fn fetch_next_page(&mut self) -> Option<Task<Message>> {
+        if self.search.loading_more.is_some() || self.search.exhausted {
+            return None;
+        }
+        let page = self.search.page.unwrap_or(1);
+        let point = FetchPoint::following(&self.search.query, page, self.search.last_id);
+        self.search.loading_more = Some(point);
+        debug!(?point, "Fetching more results");
+
+        Some(self.fetch_search(self.search.query.clone(), Some(point)))
+    }
+
+    /// Fetches results for `query` from `point`, or its first page.
+    fn fetch_search(&self, query: String, point: Option<FetchPoint>) -> Task<Message> {
+        let base_url = self.config.host.base_url().to_string();
+        let auth = self.config.auth.clone();
+        let fetched = query.clone();
+        Task::perform(
+            async move { fetch_posts(&base_url, auth.as_ref(), fetched, point).await },
+            move |res| match res {
+                Ok(posts) => Message::Search(SearchMessage::PostsLoaded {
+                    query,
+                    point,
+                    posts,
+                }),
+                Err(err) => {
+                    error!("Error fetching posts: {err}");
+                    Message::Search(SearchMessage::LoadFailed(err.to_string()))
+                }
+            },
+        )
+    }
+
+    /// Fetches more results when the detail view gets near the end of the search it came from.
+    fn fetch_more_near_end(&mut self) -> Option<Task<Message>> {
+        let Some(ResultList::Search(query)) = &self.browsing else {
+            return None;
+        };
+        // Fetched pages go to the current search.
+        if *query != self.search.query {
+            return None;
+        }
+        let ids = self.shown_results(query);
+        let position = ids.iter().position(|&id| Some(id) == self.selected_post)?;
+        if position + PREFETCH_DISTANCE < ids.len() {
+            return None;
+        }
+        self.fetch_next_page()
+    }
+
+    /// Scrolls the grid just enough for a row of tiles to be in view.
+    fn scroll_to_row(&self, row: usize) -> Task<Message> {
+        let height = post_tile::tile_height(self.config.view.tile_width as f32);
+        let top = GRID_PADDING + row as f32 * height;
+        let (offset, visible) = self
+            .ui
+            .grid_viewport
+            .map_or((0.0, self.ui.window_height as f32), |viewport| {
+                (viewport.absolute_offset().y, viewport.bounds().height)
+            });
+
+        let y = if top < offset {
+            top
+        } else if top + height > offset + visible {
+            top + height - visible
+        } else {
+            return Task::none();
+        };
+        scroll_to(GRID_SCROLLABLE, AbsoluteOffset { x: 0.0, y })
+    }
+
+    /// Fetches every comment on post `id`.
+    fn comments_task(&self, id: u32) -> Task<Message> {
+        let base_url = self.config.host.base_url().to_string();
+        Task::perform(
+            async move { fetch_all_comments(&base_url, None, id).await },
+            move |res| match res {
+                Ok(comments) => Message::Detail(DetailMessage::CommentsLoaded(id, comments)),
+                Err(err) => {
+                    error!("Getting comments for {id} failed: {err}");
+                    Message::View(ViewMessage::ShowError(format!(
+                        "Couldn't load comments: {err}"
+                    )))
+                }
+            },
+        )
+    }
+
+    /// Tasks to download the media for post `id`. When `preload`ing, videos are skipped since
+    /// loading one replaces the player.
+    fn media_tasks(&self, id: u32, preload: bool) -> Vec<Task<Message>> {
+        let mut commands = vec![];
+        let Some(post) = self.store.get_post(id) else {
+            return commands;
+        };
+
+        // TODO: Deal with .swfs for compatiblity.
+        // *Maaaaaaaybe* ruffle support? Doubt it.
+        match post.file.ext.as_deref() {
+            Some("gif") => {
+                // GIFs saved in an earlier session are decoded again from disk.
+                if self.store.get_gif_frames(id).is_none() {
+                    // Deleted and restricted posts come without a file URL.
+                    let Some(url) = post.file.url.clone() else {
+                        debug!("Post {id} has no file URL");
+                        return commands;
+                    };
+                    commands.push(Task::perform(fetch_gif(id, url), move |res| match res {
+                        Ok(gif) => Message::Media(MediaMessage::GifLoaded(id, gif)),
+                        Err(err) => {
+                            error!("Gif {id} failed: {err}");
+                            Message::Tick
+                        }
+                    }));
+                }
+            }
+            Some(ext @ ("webm" | "mp4")) if !preload => {
+                let Some(url) = self
+                    .store
+                    .get_video(id)
+                    .map(|url| url.to_string())
+                    .or_else(|| post.file.url.clone())
+                else {
+                    debug!("Post {id} has no file URL");
+                    return commands;
+                };
+                commands.push(Task::perform(
+                    fetch_video(id, url, ext.to_string()),
+                    move |res| match res {
+                        Ok(url) => Message::Media(MediaMessage::VideoLoaded(id, url)),
+                        Err(err) => {
+                            error!("Video {id} failed: {err}");
+                            Message::Tick
+                        }
+                    },
+                ));
+            }
+            Some("webm") | Some("mp4") | Some("swf") => {}
+            None => debug!("Post {id} has no file extension"),
+            Some(_) => {
+                if !self.store.has_image(id) {
+                    if self.config.view.download_sample && post.sample.url.is_some() {
+                        commands.push(Task::perform(
+                            fetch_sample(id, post.sample.clone()),
+                            move |res| match res {
+                                Ok(handle) => {
+                                    Message::Media(MediaMessage::SampleLoaded(id, handle))
+                                }
+                                Err(err) => {
+                                    error!("Sample {id} failed: {err}");
+                                    Message::Tick
+                                }
+                            },
+                        ));
+                    }
+                    if self.config.view.download_fullsize && post.file.url.is_some() {
+                        commands.push(Task::perform(
+                            fetch_image(id, post.file.clone()),
+                            move |res| match res {
+                                Ok(handle) => Message::Media(MediaMessage::ImageLoaded(id, handle)),
+                                Err(err) => {
+                                    error!("Image {id} failed: {err}");
+                                    Message::Tick
+                                }
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+
+        commands
+    }
+
+    /// Queues a thumbnail fetch for `id` on behalf of `owner`, unless it's already loaded.
+    /// Returns whether the post still needs one.
+    fn queue_thumbnail(&mut self, id: u32, owner: &str) -> bool {
+        let Some(url) = self
+            .store
+            .get_post(id)
+            .and_then(|post| post.preview.url.clone())
+        else {
+            return false;
+        };
+        if self.store.has_thumbnail(id) {
+            return false;
+        }
+        self.thumbnails.enqueue(id, url, owner);
+        true
+    }
+
+    /// Starts as many queued thumbnail downloads as the scheduler allows.
+    fn start_thumbnails(&mut self) -> Task<Message> {
+        let jobs = self.thumbnails.next_jobs(Instant::now());
+        Task::batch(jobs.into_iter().map(|job| {
+            let id = job.id;
+            Task::perform(fetch_preview(id, job.url), move |res| match res {
+                Ok(thumb) => Message::Media(MediaMessage::ThumbnailLoaded(id, thumb)),
+                Err(err) => {
+                    error!("Failed to fetch thumbnail for {id}: {err}");
+                    Message::Media(MediaMessage::ThumbnailFailed(id))
+                }
+            })
+        }))
+    }
+
+    /// Rules currently in the settings blacklist editor.
+    fn editor_blacklist(&self) -> Blacklist {
+        let rules = self
+            .settings
+            .blacklist_content
+            .text()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        Blacklist { rules }
+    }
+
+    /// Shows what syncing with the account's blacklist `rules` would change, to be confirmed, or
+    /// says there's nothing to do.
+    fn review_sync(&mut self, rules: Vec<String>, upload: bool) {
+        let sync = BlacklistSync::new(Blacklist { rules }, &self.editor_blacklist(), upload);
+        if sync.diff.is_empty() {
+            self.settings.sync_status = Some("Account blacklist is already in sync.".into());
+            self.settings.blacklist_sync = None;
+        } else {
+            self.settings.sync_status = None;
+            self.settings.blacklist_sync = Some(sync);
+        }
+    }
+
+    /// Replaces the blacklist, recompiling and refiltering if it actually changed.
+    fn set_blacklist(&mut self, rules: Vec<String>) {
+        if rules != self.config.blacklist.rules {
+            self.config.blacklist.rules = rules;
+            self.blacklist = CompiledBlacklist::compile(&self.config.blacklist);
+            self.apply_blacklist();
+        }
+    }
+
+    /// Applies a blacklist from the account sync to the editor and config, and saves it.
+    fn apply_account_blacklist(&mut self, rules: Vec<String>) {
+        self.settings.blacklist_content = Content::with_text(&rules.join("\n"));
+        self.set_blacklist(rules);
+        if let Err(err) = self.save_config() {
+            warn!("Failed to save config: {err}");
+        }
+    }
+
+    /// Re-checks everything already loaded against the current blacklist, after it changes.
+    fn apply_blacklist(&mut self) {
+        info!("Blacklist changed, refiltering cached results");
+        self.store.refilter_results(&self.blacklist);
+
+        let visible = self
+            .store
+            .get_results(&self.search.query)
+            .cloned()
+            .unwrap_or_default();
+        self.posts = visible
+            .iter()
+            .filter_map(|&id| self.store.get_post(id).cloned())
+            .collect();
+
+        self.followed.new_followed_posts = self.filter_followed_posts();
+        self.followed.new_pool_pages = self.filter_pool_updates();
+
+        // Drop thumbnails nobody can see any more, and queue ones for restored posts.
+        let blacklist = &self.blacklist;
+        let store = &self.store;
+        self.thumbnails.retain_posts(|id| {
+            store
+                .get_post(id)
+                .is_some_and(|post| !blacklist.is_blacklisted(post))
+        });
+
+        let owner = search_owner(&self.search.query);
+        for id in visible {
+            self.queue_thumbnail(id, &owner);
+        }
+        let followed_ids = self
+            .followed
+            .new_followed_posts
+            .values()
+            .flatten()
+            .chain(
+                self.followed
+                    .new_pool_pages
+                    .iter()
+                    .flat_map(|update| &update.posts),
+            )
+            .map(|post| post.id)
+            .collect::<Vec<u32>>();
+        for id in followed_ids {
+            self.queue_thumbnail(id, FOLLOWED_OWNER);
+        }
+    }
+
+    /// Followed tag updates with blacklisted posts removed.
+    fn filter_followed_posts(&self) -> FxHashMap<String, Vec<Post>> {
+        self.followed
+            .received_posts
+            .iter()
+            .map(|(tag, posts)| {
+                let visible = posts
+                    .iter()
+                    .filter(|post| !self.blacklist.is_blacklisted(post))
+                    .cloned()
+                    .collect();
+                (tag.clone(), visible)
+            })
+            .collect()
+    }
+
+    /// Followed pool updates with blacklisted posts removed.
+    fn filter_pool_updates(&self) -> Vec<PoolUpdate> {
+        self.followed
+            .pool_updates
+            .iter()
+            .map(|update| PoolUpdate {
+                pool: update.pool.clone(),
+                posts: update
+                    .posts
+                    .iter()
+                    .filter(|post| !self.blacklist.is_blacklisted(post))
+                    .cloned()
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Journals changes to the store in the background, so they survive a crash.
+    fn flush_store(&mut self) {
+        if let Some(writer) = &self.store_writer {
+            self.store.flush_to(writer);
+        }
+    }
+
+    /// Saves the config, unless the file couldn't be read at startup and would be overwritten.
+    /// Parses DText that's come into view or changed, and drops what's no longer shown.
+    fn parse_dtext(&mut self) {
+        let mut shown: Vec<(DTextSource, Cow<str>)> = Vec::new();
+        match &self.ui.view_mode {
+            ViewMode::Detail(_) => {
+                if let Some(post) = self.selected_post.and_then(|id| self.store.get_post(id)) {
+                    let description = post.description.as_str();
+                    shown.push((DTextSource::Description(post.id), description.into()));
+                    for comment in self.store.get_comments(post.id).into_iter().flatten() {
+                        shown.push((
+                            DTextSource::Comment(comment.id),
+                            comment.body.as_str().into(),
+                        ));
+                    }
+                }
+                if self.comment_draft.preview {
+                    shown.push((DTextSource::Draft, self.comment_draft.content.text().into()));
+                }
+            }
+            ViewMode::Pool(_) => {
+                if let Some(pool) = &self.pools.current {
+                    shown.push((DTextSource::Pool(pool.id), pool.description.as_str().into()));
+                }
+            }
+            ViewMode::Wiki(tag) => {
+                let wiki = self.wiki.as_ref().filter(|wiki| &wiki.tag == tag);
+                if let Some(page) = wiki.and_then(|wiki| wiki.page.as_ref()) {
+                    shown.push((DTextSource::Wiki(tag.clone()), page.body.as_str().into()));
+                }
+            }
+            _ => {}
+        }
+
+        self.dtext
+            .retain(|source| shown.iter().any(|(shown, _)| shown == source));
+        for (source, text) in shown {
+            self.dtext.insert(source, &text);
+        }
+    }
+
+    fn save_config(&self) -> Result<(), ConfigError> {
+        if !self.config_writable {
+            warn!("Not saving config over one that couldn't be read");
+            return Ok(());
+        }
+        self.config.save()
+    }
+
     fn exit(&mut self) -> Task<Message> {
         info!("exiting...");
 
         self.config.followed_tags = compose_vec(self.followed.tags.clone());
 
-        match &self.config.save() {
+        match &self.save_config() {
             Ok(()) => info!("Saved config"),
             Err(err) => {
                 error!("Couldn't save config: {err}");
             }
         }
 
-        if let Some(path) = poststore_path() {
-            match self.store.save_to(&path) {
+        // The snapshot replaces the journal, so it has to wait for the last changes to be written.
+        if let Some(mut writer) = self.store_writer.take() {
+            writer.finish();
+        }
+        if let Some(path) = &self.store_path {
+            match self.store.save_to(path) {
                 Ok(()) => info!("Saved PostStore to {path:?}"),
                 Err(err) => {
                     error!("Couldn't save PostStore: {err}")
                 }
             }
         } else {
-            warn!("Couldn't find path for PostStore");
+            warn!("Not saving PostStore, there's nowhere to save it");
         }
 
         return window::latest().and_then(window::close);
@@ -1,16 +1,21 @@
 use iced::{
-    widget::{column, Row},
-    Alignment, Element, Length, Theme,
+    widget::{
+        button, center, column, container, mouse_area, opaque, row, stack, text, Column, Row,
+    },
+    Alignment, Color, Element, Font, Length, Theme,
 };
 
-use super::{state::ViewMode, App, Message};
+use super::{message::ViewMessage, state::ViewMode, App, Message};
+use crate::core::keymap::Action;
 
 mod debug;
 mod detail;
-// mod dtext;
+pub mod dtext;
 mod followed;
 mod grid;
+mod pool;
 mod settings;
+mod wiki;
 
 impl App {
     pub fn view(&self) -> Element<'_, Message> {
@@ -19,6 +24,9 @@ impl App {
             ViewMode::Detail(_) => detail::detail_bar(self),
             ViewMode::Settings => settings::settings_bar(self),
             ViewMode::Followed => followed::followed_bar(),
+            ViewMode::Pool(_) => pool::pool_bar(self),
+            ViewMode::Pools => pool::pools_bar(self),
+            ViewMode::Wiki(tag) => wiki::wiki_bar(tag),
         }
         .spacing(8)
         .padding(8)
@@ -31,19 +39,32 @@ impl App {
             ViewMode::Detail(_) => detail::render_detail(self),
             ViewMode::Settings => settings::render_settings(self),
             ViewMode::Followed => followed::render_followed(self),
+            ViewMode::Pool(_) => pool::render_pool(self),
+            ViewMode::Pools => pool::render_pools(self),
+            ViewMode::Wiki(tag) => wiki::render_wiki(self, tag),
         };
 
+        let mut layout: Column<Message> = column![];
         if self.debug {
-            let debug_overlay = debug::render_debug_overlay(self);
-            column![debug_overlay, header, main_view]
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .into()
+            layout = layout.push(debug::render_debug_overlay(self));
+        }
+        layout = layout.push(header);
+        if matches!(self.ui.view_mode, ViewMode::Grid(..)) && !self.search.suggestions.is_empty() {
+            layout = layout.push(grid::suggestions(self));
+        }
+        if let Some(error) = &self.ui.error {
+            layout = layout.push(error_banner(error));
+        }
+
+        let layout = layout
+            .push(main_view)
+            .width(Length::Fill)
+            .height(Length::Fill);
+
+        if self.ui.show_help {
+            stack![layout, help_overlay(self)].into()
         } else {
-            column![header, main_view]
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .into()
+            layout.into()
         }
     }
 
@@ -56,6 +77,12 @@ impl App {
             ViewMode::Followed => "Followed tags".into(),
             ViewMode::Settings => "Settings".into(),
             ViewMode::Detail(id) => format!("Post #{id}"),
+            ViewMode::Pool(id) => match &self.pools.current {
+                Some(pool) if pool.id == *id => pool.display_name(),
+                _ => format!("Pool #{id}"),
+            },
+            ViewMode::Pools => "Pools".into(),
+            ViewMode::Wiki(tag) => format!("Wiki: {tag}"),
         };
 
         return format!("{window_title} | {name} v{version}");
@@ -66,3 +93,68 @@ impl App {
         self.config.view.theme.get()
     }
 }
+
+fn error_banner(error: &str) -> Element<'_, Message> {
+    container(
+        row![
+            text(error).width(Length::Fill),
+            button("dismiss")
+                .on_press(Message::View(ViewMessage::DismissError))
+                .style(button::secondary),
+        ]
+        .spacing(8)
+        .align_y(Alignment::Center),
+    )
+    .padding(8)
+    .width(Length::Fill)
+    .style(container::danger)
+    .into()
+}
+
+/// Lists the key bindings over the current view. Clicking anywhere closes it.
+fn help_overlay(app: &App) -> Element<'_, Message> {
+    let bindings = column(Action::ALL.into_iter().map(|action| {
+        let keys = app
+            .config
+            .keymap
+            .bindings(action)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        row![
+            text(action.description()).width(Length::Fill),
+            text(if keys.is_empty() {
+                "unbound".to_string()
+            } else {
+                keys
+            })
+            .font(Font::MONOSPACE),
+        ]
+        .spacing(16)
+        .into()
+    }))
+    .spacing(4);
+
+    let panel = container(
+        column![
+            text("Keyboard shortcuts").size(20),
+            bindings,
+            text("Change these under [keymap] in config.toml.").size(12),
+        ]
+        .spacing(12),
+    )
+    .padding(16)
+    .width(400)
+    .style(container::bordered_box);
+
+    opaque(
+        mouse_area(center(panel).style(|_| {
+            container::background(Color {
+                a: 0.6,
+                ..Color::BLACK
+            })
+        }))
+        .on_press(Message::View(ViewMessage::ToggleHelp)),
+    )
+}
@@ -5,6 +5,8 @@ use iced::{
 
 use crate::{
     app::{App, Message},
+    core::api::rate_limiter::{API_LIMITER, MEDIA_LIMITER},
+    core::media::MediaKind,
     core::model::PostType,
 };
 
@@ -16,11 +18,25 @@ pub fn render_debug_overlay(app: &App) -> Element<'_, Message> {
             app.search.query, app.search.page
         )),
         text(format!("Posts cached: {}", app.store.posts.len())),
-        text(format!("Thumbnails cached: {}", app.store.thumbnails.len())),
-        text(format!("Images cached: {}", app.store.images.len())),
+        text(format!(
+            "Thumbnails cached: {}",
+            app.store.media.len(MediaKind::Thumbnail)
+        )),
+        text(format!(
+            "Images cached: {}",
+            app.store.media.len(MediaKind::Image)
+        )),
+        text(format!(
+            "Media in memory: {} bytes",
+            app.store.media.memory_used()
+        )),
         text(format!("Gifs cached: {}", app.store.gifs.len())),
         text(format!("Videos cached: {}", app.store.videos.len())),
-        text(format!("Thumbnail queue: {:?}", app.search.thumbnail_queue)),
+        text(format!(
+            "Thumbnails: {} queued, {} downloading",
+            app.thumbnails.waiting(),
+            app.thumbnails.in_flight()
+        )),
         text(format!(
             "Undo: {:?}\nRedo:{:?}",
             app.ui.history.backwards, app.ui.history.forwards
@@ -29,6 +45,19 @@ pub fn render_debug_overlay(app: &App) -> Element<'_, Message> {
         text(format!("New followed posts: {:?}", app.followed_posts())),
     ];
 
+    for stats in [API_LIMITER.stats(), MEDIA_LIMITER.stats()] {
+        debug_lines.push(text(format!(
+            "{} limiter: {} queued, {} in flight, {} done, {:.2} tokens, wait avg {:?} / max {:?}",
+            stats.name,
+            stats.queued,
+            stats.in_flight,
+            stats.completed,
+            stats.tokens,
+            stats.average_wait,
+            stats.max_wait
+        )));
+    }
+
     if let Some(auth) = &app.config.auth {
         debug_lines.push(text(format!("Auth: yes, as {}", auth.username)));
     } else {
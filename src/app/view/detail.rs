@@ -14,11 +14,25 @@ pub fn detail_bar(app: &App) -> Row<'_, Message> {
     let mut bar =
         row![button("back").on_press(Message::View(ViewMessage::Back))].align_y(Alignment::Center);
 
-    if app.selected_post.is_none() {
-        return bar.into();
+    if app.browsing.is_some() {
+        let (previous, next) = app.neighbours();
+        bar = bar.push(
+            button("<")
+                .on_press_maybe(previous.map(|_| Message::Detail(DetailMessage::PreviousPost))),
+        );
+        bar = bar.push(
+            button(">").on_press_maybe(next.map(|_| Message::Detail(DetailMessage::NextPost))),
+        );
+        if next.is_none() && app.search.loading_more.is_some() {
+            bar = bar.push(text("loading more...").size(12));
+        }
     }
 
-    let post = app.store.get_post(app.selected_post.unwrap()).unwrap();
+    // Nothing selected, or a linked post that's still loading.
+    let Some(post) = app.selected_post.and_then(|id| app.store.get_post(id)) else {
+        return bar;
+    };
+
     bar = bar.push(text(format!("post #{}", post.id)).size(20));
     bar = bar.push(text(match post.rating {
         Rating::Safe => "Rating: Safe",
@@ -30,14 +44,28 @@ pub fn detail_bar(app: &App) -> Row<'_, Message> {
     bar = bar.push(button("copy URL").on_press(Message::Detail(DetailMessage::CopyURL)));
     bar = bar.push(button("open file").on_press(Message::Detail(DetailMessage::OpenFile)));
 
+    if let Some(pool) = &app.pools.current {
+        if let Some(position) = pool.position(post.id) {
+            bar = bar.push(super::pool::page_controls(app, pool, position));
+        }
+    }
+
     bar
 }
 
 pub fn render_detail(app: &App) -> Element<'_, Message> {
-    if let Some(selected_post) = app.selected_post {
-        let post = app.store.get_post(selected_post).unwrap();
-        crate::gui::detail_view::render_detail(&post, &app.store, &app.video_player)
-    } else {
-        Column::new().push(text("no post selected!")).into()
+    match app.selected_post {
+        Some(id) => match app.store.get_post(id) {
+            Some(post) => crate::gui::detail_view::render_detail(
+                post,
+                &app.store,
+                &app.video_player,
+                &app.comment_draft,
+                &app.dtext,
+                app.config.auth.as_ref().map(|auth| auth.username.as_str()),
+            ),
+            None => text(format!("loading post #{id}...")).into(),
+        },
+        None => Column::new().push(text("no post selected!")).into(),
     }
 }
@@ -1,32 +1,479 @@
-use crate::core::dtext::model::DTextSpan;
-use iced::{
-    font,
-    widget::{
-        text::{self, Rich, Span},
-        Column, Text,
-    },
-    Element, Length,
+use crate::app::message::ViewMessage;
+use crate::app::state::ViewMode;
+use crate::app::Message;
+use crate::core::dtext::cache::{DTextCache, DTextSource};
+use crate::core::dtext::model::{DTextBlock, DTextSpan, ListItem, TableRow};
+use crate::core::model::TagCategory;
+use crate::core::store::PostStore;
+use iced::font::{Style, Weight};
+use iced::widget::text::Span;
+use iced::widget::tooltip::Position;
+use iced::widget::{
+    button, column, container, hover, image, rich_text, row, span, text, tooltip, Column,
 };
+use iced::{padding, Color, Element, Font, Length};
 
-pub fn render_dtext<'a>(spans: &'a [DTextSpan]) -> Text<'a, Rich<'a>> {
-    let rich_spans: Vec<Rich<'a>> = spans.iter().flat_map(span_to_rich).collect();
-    Text::with(&rich_spans)
+use super::grid::category_color;
+
+const TEXT_SIZE: f32 = 16.0;
+const LINK_COLOR: Color = Color::from_rgb8(0xb4, 0xc7, 0xd9);
+const SPOILER_COLOR: Color = Color::BLACK;
+/// Behind spoilers while they're hovered, dark enough that they still stand out as spoilers.
+const REVEALED_SPOILER_COLOR: Color = Color::from_rgb8(0x30, 0x30, 0x30);
+const PREVIEW_HEIGHT: f32 = 150.0;
+
+/// Renders the DText parsed into `dtext` for `source`, like a post description, comment or wiki
+/// page.
+///
+/// Links are clickable, and hovering over text that references posts shows their thumbnails, if
+/// the `store` has them. Spoilers show while the paragraph or block they're in is hovered, and
+/// sections open and close when their title is clicked.
+pub fn render_dtext<'a>(
+    dtext: &DTextCache,
+    source: DTextSource,
+    store: &PostStore,
+) -> Element<'a, Message> {
+    let blocks = dtext.get(&source);
+    let mut document = Document {
+        source,
+        store,
+        sections: 0,
+    };
+    self::blocks(blocks, Format::default(), &mut document).into()
+}
+
+/// The DText being drawn.
+struct Document<'s> {
+    source: DTextSource,
+    store: &'s PostStore,
+    /// Sections drawn so far, which is how [`ViewMessage::ToggleSection`] tells them apart.
+    sections: usize,
 }
 
-fn span_to_rich<'a>(span: &'a DTextSpan) -> Vec<Rich<'a>> {
-    match span {
-        DTextSpan::Text(s) => vec![Rich::with_spans(Span::new(s.as_str()))],
+/// How text inside inline tags is drawn.
+#[derive(Debug, Clone, Copy, Default)]
+struct Format {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+    monospace: bool,
+    spoiler: bool,
+    /// Spoilers are drawn readable, for when they're hovered.
+    revealed: bool,
+    color: Option<Color>,
+    size: Option<f32>,
+}
 
-        DTextSpan::Bold(children) => children
-            .iter()
-            .flat_map(span_to_rich)
-            .map(|r| r.font(font::Weight::Bold))
-            .collect(),
+impl Format {
+    fn span(self, content: String) -> Span<'static, Message> {
+        let mut font = if self.monospace {
+            Font::MONOSPACE
+        } else {
+            Font::DEFAULT
+        };
+        if self.bold {
+            font.weight = Weight::Bold;
+        }
+        if self.italic {
+            font.style = Style::Italic;
+        }
 
-        DTextSpan::Italics(children) => children
+        let mut span = span(content)
+            .font(font)
+            .underline(self.underline)
+            .strikethrough(self.strikethrough)
+            .color_maybe(self.color);
+        if let Some(size) = self.size {
+            span = span.size(size);
+        }
+        if self.spoiler && self.revealed {
+            span = span
+                .color(self.color.unwrap_or(Color::WHITE))
+                .background(REVEALED_SPOILER_COLOR);
+        } else if self.spoiler {
+            span = span.color(SPOILER_COLOR).background(SPOILER_COLOR);
+        }
+        span
+    }
+
+    fn link(self, label: String, message: Message) -> Span<'static, Message> {
+        Self {
+            underline: true,
+            color: Some(LINK_COLOR),
+            ..self
+        }
+        .span(label)
+        .link(message)
+    }
+
+    /// For superscript and subscript.
+    fn smaller(self) -> Self {
+        Self {
+            size: Some(self.size.unwrap_or(TEXT_SIZE) * 0.75),
+            ..self
+        }
+    }
+}
+
+fn blocks<'a>(
+    blocks: &[DTextBlock],
+    format: Format,
+    document: &mut Document,
+) -> Column<'a, Message> {
+    column(
+        blocks
             .iter()
-            .flat_map(span_to_rich)
-            .map(|r| r.font(font::Style::Italic))
-            .collect(),
+            .map(|block| render_block(block, format, document)),
+    )
+    .spacing(8)
+}
+
+fn render_block<'a>(
+    block: &DTextBlock,
+    format: Format,
+    document: &mut Document,
+) -> Element<'a, Message> {
+    let store = document.store;
+    match block {
+        DTextBlock::Paragraph(spans) => paragraph(spans, format, store),
+        DTextBlock::Header { level, spans } => {
+            let size = TEXT_SIZE + 2.0 * (7 - (*level).min(6)) as f32;
+            paragraph(
+                spans,
+                Format {
+                    bold: true,
+                    size: Some(size),
+                    ..format
+                },
+                store,
+            )
+        }
+        DTextBlock::Quote(inner) => container(self::blocks(inner, format, document))
+            .style(container::bordered_box)
+            .padding(8)
+            .width(Length::Fill)
+            .into(),
+        DTextBlock::Spoiler(inner) => {
+            let hidden = Format {
+                spoiler: true,
+                ..format
+            };
+            let revealed = Format {
+                revealed: true,
+                ..hidden
+            };
+            let start = document.sections;
+            let hidden = spoiler_block(inner, hidden, document);
+            // Both are drawn from the same blocks, so their sections are numbered the same.
+            document.sections = start;
+            hover(hidden, spoiler_block(inner, revealed, document))
+        }
+        DTextBlock::Code(code) => container(text(code.clone()).font(Font::MONOSPACE))
+            .style(container::bordered_box)
+            .padding(8)
+            .width(Length::Fill)
+            .into(),
+        DTextBlock::Section {
+            title,
+            expanded,
+            blocks,
+        } => {
+            let index = document.sections;
+            document.sections += 1;
+            let marker = if *expanded { "▾" } else { "▸" };
+            let title = button(text(format!("{marker} {title}")).font(Font {
+                weight: Weight::Bold,
+                ..Font::DEFAULT
+            }))
+            .on_press(Message::View(ViewMessage::ToggleSection(
+                document.source.clone(),
+                index,
+            )))
+            .style(button::text)
+            .padding(0);
+            if !*expanded {
+                // Sections inside still count, so the ones after keep their numbers.
+                document.sections += sections(blocks);
+                return title.into();
+            }
+            column![
+                title,
+                container(self::blocks(blocks, format, document)).padding(padding::left(16.0)),
+            ]
+            .spacing(4)
+            .into()
+        }
+        DTextBlock::List(items) => list(items, format, store),
+        DTextBlock::Table(rows) => table(rows, format, store),
+    }
+}
+
+fn spoiler_block<'a>(
+    inner: &[DTextBlock],
+    format: Format,
+    document: &mut Document,
+) -> Element<'a, Message> {
+    let background = if format.revealed {
+        REVEALED_SPOILER_COLOR
+    } else {
+        SPOILER_COLOR
+    };
+    container(self::blocks(inner, format, document))
+        .style(move |_| container::background(background))
+        .padding(8)
+        .width(Length::Fill)
+        .into()
+}
+
+/// How many sections are in `blocks`, counting ones inside others.
+fn sections(blocks: &[DTextBlock]) -> usize {
+    blocks
+        .iter()
+        .map(|block| match block {
+            DTextBlock::Section { blocks, .. } => 1 + sections(blocks),
+            DTextBlock::Quote(blocks) | DTextBlock::Spoiler(blocks) => sections(blocks),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn paragraph<'a>(spans: &[DTextSpan], format: Format, store: &PostStore) -> Element<'a, Message> {
+    // Inside a spoiler block, the block takes care of revealing them.
+    let content = if !format.spoiler && has_spoiler(spans) {
+        let revealed = Format {
+            revealed: true,
+            ..format
+        };
+        hover(rich_spans(spans, format), rich_spans(spans, revealed))
+    } else {
+        rich_spans(spans, format)
+    };
+
+    let mut ids = Vec::new();
+    post_links(spans, &mut ids);
+    let previews: Vec<Element<'a, Message>> = ids
+        .into_iter()
+        .filter_map(|id| {
+            let thumbnail = store.get_thumbnail(id)?;
+            Some(
+                column![
+                    image(thumbnail).height(PREVIEW_HEIGHT),
+                    text(format!("post #{id}")).size(12),
+                ]
+                .into(),
+            )
+        })
+        .collect();
+
+    if previews.is_empty() {
+        return content;
+    }
+    tooltip(content, row(previews).spacing(8), Position::Bottom)
+        .style(container::bordered_box)
+        .padding(8)
+        .into()
+}
+
+fn rich_spans<'a>(spans: &[DTextSpan], format: Format) -> Element<'a, Message> {
+    let mut out = Vec::new();
+    render_spans(spans, format, &mut out);
+    rich_text(out).on_link_click(|message| message).into()
+}
+
+fn has_spoiler(spans: &[DTextSpan]) -> bool {
+    spans.iter().any(|span| match span {
+        DTextSpan::Spoiler(_) => true,
+        DTextSpan::Bold(children)
+        | DTextSpan::Italics(children)
+        | DTextSpan::Strikeout(children)
+        | DTextSpan::Underline(children)
+        | DTextSpan::Superscript(children)
+        | DTextSpan::Subscript(children)
+        | DTextSpan::Colored { children, .. } => has_spoiler(children),
+        _ => false,
+    })
+}
+
+/// IDs of the posts referenced in `spans`, in order and without repeats.
+fn post_links(spans: &[DTextSpan], ids: &mut Vec<u32>) {
+    for span in spans {
+        match span {
+            DTextSpan::PostLink(id) if !ids.contains(id) => ids.push(*id),
+            DTextSpan::Bold(children)
+            | DTextSpan::Italics(children)
+            | DTextSpan::Strikeout(children)
+            | DTextSpan::Underline(children)
+            | DTextSpan::Superscript(children)
+            | DTextSpan::Subscript(children)
+            | DTextSpan::Spoiler(children)
+            | DTextSpan::Colored { children, .. } => post_links(children, ids),
+            _ => {}
+        }
+    }
+}
+
+fn list<'a>(items: &[ListItem], format: Format, store: &PostStore) -> Element<'a, Message> {
+    column(items.iter().map(|item| {
+        row![text("•"), paragraph(&item.spans, format, store)]
+            .spacing(6)
+            .padding(padding::left(16.0 * (item.depth - 1) as f32))
+            .into()
+    }))
+    .spacing(2)
+    .into()
+}
+
+fn table<'a>(rows: &[TableRow], format: Format, store: &PostStore) -> Element<'a, Message> {
+    column(rows.iter().map(|table_row| {
+        row(table_row.cells.iter().map(|cell| {
+            let format = Format {
+                bold: cell.header,
+                ..format
+            };
+            container(paragraph(&cell.spans, format, store))
+                .style(container::bordered_box)
+                .padding(4)
+                .width(Length::Fill)
+                .into()
+        }))
+        .into()
+    }))
+    .into()
+}
+
+fn render_spans(spans: &[DTextSpan], format: Format, out: &mut Vec<Span<'static, Message>>) {
+    for dtext_span in spans {
+        match dtext_span {
+            DTextSpan::Text(content) => out.push(format.span(content.clone())),
+            DTextSpan::Bold(children) => render_spans(
+                children,
+                Format {
+                    bold: true,
+                    ..format
+                },
+                out,
+            ),
+            DTextSpan::Italics(children) => render_spans(
+                children,
+                Format {
+                    italic: true,
+                    ..format
+                },
+                out,
+            ),
+            DTextSpan::Strikeout(children) => render_spans(
+                children,
+                Format {
+                    strikethrough: true,
+                    ..format
+                },
+                out,
+            ),
+            DTextSpan::Underline(children) => render_spans(
+                children,
+                Format {
+                    underline: true,
+                    ..format
+                },
+                out,
+            ),
+            DTextSpan::Superscript(children) | DTextSpan::Subscript(children) => {
+                render_spans(children, format.smaller(), out)
+            }
+            DTextSpan::Spoiler(children) => render_spans(
+                children,
+                Format {
+                    spoiler: true,
+                    ..format
+                },
+                out,
+            ),
+            DTextSpan::InlineCode(code) => out.push(
+                Format {
+                    monospace: true,
+                    ..format
+                }
+                .span(code.clone()),
+            ),
+            DTextSpan::Colored { color, children } => render_spans(
+                children,
+                Format {
+                    color: parse_color(color).or(format.color),
+                    ..format
+                },
+                out,
+            ),
+            DTextSpan::Link { href, label } => out.push(format.link(
+                label.clone(),
+                Message::View(ViewMessage::OpenUrl(href.clone())),
+            )),
+            DTextSpan::WikiLink { tag, label } => {
+                let label = label.clone().unwrap_or_else(|| tag.replace('_', " "));
+                let tag = tag.to_lowercase().replace(' ', "_");
+                out.push(format.link(label, show(ViewMode::Grid(tag, Some(1)))));
+            }
+            DTextSpan::TagSearch(query) => {
+                out.push(format.link(query.clone(), show(ViewMode::Grid(query.clone(), Some(1)))))
+            }
+            DTextSpan::PostLink(id) => {
+                out.push(format.link(format!("post #{id}"), show(ViewMode::Detail(*id))))
+            }
+            DTextSpan::PoolLink(id) => {
+                out.push(format.link(format!("pool #{id}"), show(ViewMode::Pool(*id))))
+            }
+            DTextSpan::CommentLink(id) => out.push(format.link(
+                format!("comment #{id}"),
+                Message::View(ViewMessage::OpenUrl(format!("/comments/{id}"))),
+            )),
+            DTextSpan::UserLink(id) => out.push(format.link(
+                format!("user #{id}"),
+                Message::View(ViewMessage::OpenUrl(format!("/users/{id}"))),
+            )),
+            DTextSpan::LineBreak => out.push(format.span("\n".to_string())),
+        }
+    }
+}
+
+fn show(mode: ViewMode) -> Message {
+    Message::View(ViewMessage::Show(mode))
+}
+
+/// Colors can be `#hex` codes, a few names, or tag categories like `artist`.
+fn parse_color(color: &str) -> Option<Color> {
+    let color = color.trim().to_ascii_lowercase();
+    let category = match color.as_str() {
+        "general" => Some(TagCategory::General),
+        "artist" => Some(TagCategory::Artist),
+        "contributor" => Some(TagCategory::Contributor),
+        "copyright" => Some(TagCategory::Copyright),
+        "character" => Some(TagCategory::Character),
+        "species" => Some(TagCategory::Species),
+        "invalid" => Some(TagCategory::Invalid),
+        "meta" => Some(TagCategory::Meta),
+        "lore" => Some(TagCategory::Lore),
+        _ => None,
+    };
+    if let Some(category) = category {
+        return Some(category_color(category));
+    }
+
+    match color.as_str() {
+        "red" => Some(Color::from_rgb8(0xff, 0x00, 0x00)),
+        "orange" => Some(Color::from_rgb8(0xff, 0xa5, 0x00)),
+        "yellow" => Some(Color::from_rgb8(0xff, 0xff, 0x00)),
+        "green" => Some(Color::from_rgb8(0x00, 0x80, 0x00)),
+        "blue" => Some(Color::from_rgb8(0x00, 0x00, 0xff)),
+        "purple" => Some(Color::from_rgb8(0x80, 0x00, 0x80)),
+        "pink" => Some(Color::from_rgb8(0xff, 0xc0, 0xcb)),
+        "white" => Some(Color::WHITE),
+        "black" => Some(Color::BLACK),
+        "gray" | "grey" => Some(Color::from_rgb8(0x80, 0x80, 0x80)),
+        // `Color::from_str` slices by byte, so only hand it ASCII.
+        _ => color
+            .strip_prefix('#')
+            .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))?
+            .parse()
+            .ok(),
     }
 }
@@ -37,7 +37,38 @@ pub fn render_followed(app: &App) -> Element<'_, Message> {
                     app.ui.window_width as usize,
                     app.config.view.posts_per_row,
                     app.config.view.tile_width,
-                    false
+                    None,
+                ),
+            ]);
+        }
+    }
+
+    for update in &app.followed.new_pool_pages {
+        let name = row![
+            button(text(update.pool.display_name()))
+                .on_press(Message::View(ViewMessage::Show(ViewMode::Pool(
+                    update.pool.id
+                ))))
+                .style(button::text)
+                .padding(0),
+            button("unfollow")
+                .on_press(Message::Pool(PoolMessage::Unfollow(update.pool.id)))
+                .style(button::secondary)
+                .padding(4),
+        ]
+        .spacing(8);
+        if update.posts.is_empty() {
+            content = content.push(column![name, text("no new pages").size(12)]);
+        } else {
+            content = content.push(column![
+                name,
+                grid_view(
+                    &update.posts,
+                    &app.store,
+                    app.ui.window_width as usize,
+                    app.config.view.posts_per_row,
+                    app.config.view.tile_width,
+                    None,
                 ),
             ]);
         }
@@ -1,53 +1,201 @@
 use crate::app::message::{FollowedMessage, SearchMessage, ViewMessage};
-use crate::app::state::ViewMode;
+use crate::app::state::{ViewMode, GRID_SCROLLABLE, SEARCH_INPUT};
 use crate::app::App;
 use crate::app::Message;
-use crate::core::model::Post;
-use iced::Length;
+use crate::core::model::{TagCategory, TagSuggestion};
+use crate::gui::post_tile;
+use iced::widget::text::Shaping;
 use iced::{
-    widget::{button, row, scrollable, text_input, Row},
-    Element,
+    widget::{button, column, container, row, scrollable, text, text_input, Column, Row},
+    Color, Element,
 };
+use iced::{Alignment, Length};
 
 pub fn search_bar(app: &App) -> Row<'_, Message> {
-    row![
-        text_input("search tags...", &app.search.input)
-            .on_input(|input| Message::Search(SearchMessage::InputChanged(input)))
-            .on_submit(Message::Search(SearchMessage::Submitted))
-            .padding(8)
-            .size(16),
+    let mut bar = row![text_input("search tags...", &app.search.input)
+        .id(SEARCH_INPUT)
+        .on_input(|input| Message::Search(SearchMessage::InputChanged(input)))
+        .on_submit(Message::Search(SearchMessage::Submitted))
+        .padding(8)
+        .size(16)];
+
+    let hidden = app.store.hidden_count(&app.search.query);
+    if hidden > 0 {
+        let label = if app.search.show_hidden {
+            format!("{hidden} hidden (showing)")
+        } else {
+            format!("{hidden} hidden")
+        };
+        bar = bar.push(
+            button(text(label))
+                .on_press(Message::Search(SearchMessage::ToggleHidden))
+                .style(button::secondary)
+                .padding(8),
+        );
+    }
+
+    bar.push(
         button("favorites")
             .on_press(Message::Search(SearchMessage::GetFavorites))
             .padding(8),
+    )
+    .push(
         button("search")
             .on_press(Message::Search(SearchMessage::Submitted))
             .padding(8),
+    )
+    .push(
+        button("pools")
+            .on_press(Message::View(ViewMessage::Show(ViewMode::Pools)))
+            .padding(8),
+    )
+    .push(
         button("settings")
             .on_press(Message::View(ViewMessage::Show(ViewMode::Settings)))
             .padding(8),
+    )
+    .push(
         button("followed")
             .on_press(Message::Followed(FollowedMessage::CheckUpdates))
-            .padding(8)
-    ]
+            .padding(8),
+    )
+}
+
+/// Autocomplete dropdown for the tag being typed in the search bar.
+pub fn suggestions(app: &App) -> Element<'_, Message> {
+    let list: Column<Message> = column(app.search.suggestions.iter().enumerate().map(
+        |(i, suggestion)| {
+            let selected = app.search.selected_suggestion == Some(i);
+            button(suggestion_row(suggestion))
+                .on_press(Message::Search(SearchMessage::AcceptSuggestion(Some(i))))
+                .style(if selected {
+                    button::secondary
+                } else {
+                    button::text
+                })
+                .width(Length::Fill)
+                .padding([2, 8])
+                .into()
+        },
+    ));
+
+    container(list)
+        .padding(4)
+        .width(Length::Fill)
+        .style(container::bordered_box)
+        .into()
+}
+
+fn suggestion_row(suggestion: &TagSuggestion) -> Row<'_, Message> {
+    let name: Element<Message> = match &suggestion.antecedent_name {
+        Some(alias) => text(format!("{alias} → {}", suggestion.name)),
+        None => text(&suggestion.name),
+    }
+    .shaping(Shaping::Advanced)
+    .color(category_color(suggestion.category))
+    .width(Length::Fill)
+    .into();
+
+    row![name, text(post_count(suggestion.post_count)).size(12)].align_y(Alignment::Center)
+}
+
+/// Tag colors from e621's dark theme.
+pub fn category_color(category: TagCategory) -> Color {
+    match category {
+        TagCategory::General => Color::from_rgb8(0xb4, 0xc7, 0xd9),
+        TagCategory::Artist => Color::from_rgb8(0xf2, 0xac, 0x08),
+        TagCategory::Contributor => Color::from_rgb8(0xc0, 0xc0, 0xc0),
+        TagCategory::Copyright => Color::from_rgb8(0xdd, 0x00, 0xdd),
+        TagCategory::Character => Color::from_rgb8(0x00, 0xaa, 0x00),
+        TagCategory::Species => Color::from_rgb8(0xed, 0x5d, 0x1f),
+        TagCategory::Invalid => Color::from_rgb8(0xff, 0x3d, 0x3d),
+        TagCategory::Meta => Color::from_rgb8(0xff, 0xff, 0xff),
+        TagCategory::Lore => Color::from_rgb8(0x22, 0x88, 0x22),
+    }
 }
 
+/// Shortens post counts the way e621 does, like `12.3k`.
+fn post_count(count: u32) -> String {
+    match count {
+        0..1_000 => count.to_string(),
+        1_000..10_000 => format!("{:.1}k", count as f32 / 1_000.0),
+        _ => format!("{}k", count / 1_000),
+    }
+}
+
+/// Height of the footer under the results and of the heading over the hidden posts, fixed so it's
+/// known where the hidden posts' grid starts.
+const FOOTER_HEIGHT: f32 = 50.0;
+const HIDDEN_HEADING_HEIGHT: f32 = 30.0;
+
 pub fn render_grid<'a>(app: &'a App, query: &'a str) -> Element<'a, Message> {
-    let posts: Vec<Post> = app
-        .store
-        .get_results(query)
-        .unwrap_or(&Vec::new())
-        .iter()
-        .filter_map(|&id| app.store.get_post(id).cloned())
-        .collect();
-
-    let content = crate::gui::post_tile::grid_view(
-        &posts,
-        &app.store,
+    let ids = app.store.get_results(query).map_or(&[][..], Vec::as_slice);
+    let columns = post_tile::columns(
         app.ui.window_width as usize,
         app.config.view.posts_per_row,
         app.config.view.tile_width,
-        true,
     );
+    let mut content = post_tile::virtual_grid_view(
+        ids,
+        &app.store,
+        columns,
+        app.config.view.tile_width,
+        app.grid_in_view(),
+        app.ui.highlighted,
+    );
+
+    // More pages load when scrolling near the bottom, or with the button if the results don't
+    // fill the window yet.
+    let footer: Element<Message> = if app.search.loading_more.is_some() {
+        text("loading more...").into()
+    } else if app.search.exhausted {
+        text("end of results").into()
+    } else {
+        button("load more")
+            .on_press(Message::Search(SearchMessage::LoadMorePosts))
+            .padding(8)
+            .into()
+    };
+    content = content.push(
+        container(footer)
+            .center_x(Length::Fill)
+            .center_y(FOOTER_HEIGHT)
+            .padding(8),
+    );
+
+    if app.search.show_hidden {
+        if let Some(hidden) = app.store.get_hidden(query) {
+            let hidden_posts = hidden
+                .iter()
+                .filter_map(|(id, rule)| Some((app.store.get_post(*id)?, rule.as_str())))
+                .collect::<Vec<_>>();
+
+            // The hidden posts are laid out below the results, the footer and their heading.
+            let row_height = post_tile::tile_height(app.config.view.tile_width as f32);
+            let offset = ids.len().div_ceil(columns) as f32 * row_height
+                + FOOTER_HEIGHT
+                + HIDDEN_HEADING_HEIGHT;
+            let (top, bottom) = app.grid_in_view();
+
+            content = content
+                .push(
+                    text(format!("Hidden by blacklist ({})", hidden_posts.len()))
+                        .size(16)
+                        .height(HIDDEN_HEADING_HEIGHT),
+                )
+                .push(post_tile::hidden_grid_view(
+                    &hidden_posts,
+                    &app.store,
+                    columns,
+                    app.config.view.tile_width,
+                    (top - offset, bottom - offset),
+                ));
+        }
+    }
 
-    scrollable(content.padding(16)).width(Length::Fill).into()
+    scrollable(content.padding(GRID_PADDING))
+        .id(GRID_SCROLLABLE)
+        .on_scroll(|viewport| Message::View(ViewMessage::GridScrolled(viewport)))
+        .width(Length::Fill)
+        .into()
 }
@@ -0,0 +1,148 @@
+use crate::app::message::{PoolMessage, ViewMessage};
+use crate::app::state::ViewMode;
+use crate::app::App;
+use crate::app::Message;
+use crate::core::dtext::cache::DTextSource;
+use crate::core::model::{Pool, PoolCategory, Post};
+use crate::gui::post_tile::grid_view;
+use iced::widget::text::Shaping;
+use iced::{
+    widget::{button, column, row, scrollable, text, text_input, Row},
+    Alignment, Element, Length,
+};
+
+use super::dtext::render_dtext;
+
+pub fn pools_bar(app: &App) -> Row<'_, Message> {
+    row![
+        button("back").on_press(Message::View(ViewMessage::Back)),
+        text_input("search pools...", &app.pools.search_input)
+            .on_input(|input| Message::Pool(PoolMessage::SearchInputChanged(input)))
+            .on_submit(Message::Pool(PoolMessage::Search))
+            .padding(8)
+            .size(16),
+        button("search")
+            .on_press(Message::Pool(PoolMessage::Search))
+            .padding(8),
+    ]
+}
+
+pub fn render_pools(app: &App) -> Element<'_, Message> {
+    if app.loading {
+        return text("loading...").into();
+    }
+
+    let mut content = column![].spacing(4);
+    for pool in &app.pools.search_results {
+        let kind = match pool.category {
+            PoolCategory::Series => "series",
+            PoolCategory::Collection => "collection",
+        };
+        content = content.push(
+            button(
+                row![
+                    text(pool.display_name())
+                        .shaping(Shaping::Advanced)
+                        .width(Length::Fill),
+                    text(format!("{kind}, {} posts", pool.post_ids.len())).size(12),
+                ]
+                .align_y(Alignment::Center),
+            )
+            .on_press(Message::View(ViewMessage::Show(ViewMode::Pool(pool.id))))
+            .style(button::secondary)
+            .width(Length::Fill)
+            .padding(8),
+        );
+    }
+
+    scrollable(content.padding(16).width(Length::Fill)).into()
+}
+
+pub fn pool_bar(app: &App) -> Row<'_, Message> {
+    let mut bar = row![button("back").on_press(Message::View(ViewMessage::Back))];
+
+    let Some(pool) = &app.pools.current else {
+        return bar;
+    };
+    bar = bar.push(
+        text(pool.display_name())
+            .size(20)
+            .shaping(Shaping::Advanced)
+            .width(Length::Fill),
+    );
+
+    if app.config.followed_pools.iter().any(|f| f.id == pool.id) {
+        bar.push(button("unfollow").on_press(Message::Pool(PoolMessage::Unfollow(pool.id))))
+    } else {
+        bar.push(button("follow").on_press(Message::Pool(PoolMessage::Follow)))
+    }
+}
+
+pub fn render_pool(app: &App) -> Element<'_, Message> {
+    let Some(pool) = app.pools.current.as_ref().filter(|_| !app.loading) else {
+        return text("loading...").into();
+    };
+
+    let posts: Vec<Post> = pool
+        .post_ids
+        .iter()
+        .filter_map(|&id| app.store.get_post(id))
+        .filter(|post| !app.blacklist.is_blacklisted(post))
+        .cloned()
+        .collect();
+
+    let mut content = column![].spacing(8);
+    if !pool.description.is_empty() {
+        content = content.push(render_dtext(
+            &app.dtext,
+            DTextSource::Pool(pool.id),
+            &app.store,
+        ));
+    }
+    content = content.push(grid_view(
+        &posts,
+        &app.store,
+        app.ui.window_width as usize,
+        app.config.view.posts_per_row,
+        app.config.view.tile_width,
+        None,
+    ));
+
+    scrollable(content.padding(16)).width(Length::Fill).into()
+}
+
+/// Previous/next page buttons for the detail bar, when the post is at `position` in `pool`.
+pub fn page_controls<'a>(app: &App, pool: &Pool, position: usize) -> Row<'a, Message> {
+    let previous = button("<")
+        .on_press_maybe((position > 0).then_some(Message::Pool(PoolMessage::PreviousPage)));
+    let next = button(">").on_press_maybe(
+        (position + 1 < pool.post_ids.len()).then_some(Message::Pool(PoolMessage::NextPage)),
+    );
+    let read_mode = if app.pools.read_mode {
+        "reading"
+    } else {
+        "read"
+    };
+
+    row![
+        previous,
+        button(text(format!(
+            "{} {}/{}",
+            pool.display_name(),
+            position + 1,
+            pool.post_ids.len()
+        )))
+        .on_press(Message::View(ViewMessage::Show(ViewMode::Pool(pool.id))))
+        .style(button::text),
+        next,
+        button(read_mode)
+            .on_press(Message::Pool(PoolMessage::ToggleReadMode))
+            .style(if app.pools.read_mode {
+                button::primary
+            } else {
+                button::secondary
+            }),
+    ]
+    .spacing(4)
+    .align_y(Alignment::Center)
+}
@@ -1,4 +1,4 @@
-use crate::app::message::SettingsMessage;
+use crate::app::message::{SettingsMessage, ViewMessage};
 use crate::app::App;
 use crate::app::Message;
 use iced::{
@@ -9,6 +9,9 @@ use iced::{
 pub fn settings_bar(_app: &App) -> Row<'_, Message> {
     row![
         text("Settings").size(20).width(Length::Fill),
+        button("shortcuts")
+            .on_press(Message::View(ViewMessage::ToggleHelp))
+            .style(button::secondary),
         button("save").on_press(Message::Settings(SettingsMessage::Save)),
     ]
 }
@@ -18,9 +21,13 @@ pub fn render_settings(app: &App) -> Element<'_, Message> {
         &app.settings.username,
         &app.settings.api_key,
         &app.settings.blacklist_content,
+        app.settings.blacklist_sync.as_ref(),
+        app.settings.sync_status.as_deref(),
         &app.followed.tags,
         &app.store,
         &app.followed.new_followed_tag,
         &app.config.view,
+        &app.config.host,
+        &app.settings.custom_host,
     )
 }
@@ -0,0 +1,111 @@
+use crate::app::message::{FollowedMessage, ViewMessage};
+use crate::app::state::ViewMode;
+use crate::app::App;
+use crate::app::Message;
+use crate::core::dtext::cache::DTextSource;
+use crate::core::model::{RelatedTag, TagRelation};
+use iced::widget::text::Shaping;
+use iced::{
+    widget::{button, column, row, scrollable, text, Column, Row},
+    Alignment, Element, Length,
+};
+
+use super::dtext::render_dtext;
+use super::grid::category_color;
+
+pub fn wiki_bar<'a>(tag: &str) -> Row<'a, Message> {
+    row![
+        button("back").on_press(Message::View(ViewMessage::Back)),
+        text(tag.replace('_', " "))
+            .size(20)
+            .shaping(Shaping::Advanced)
+            .width(Length::Fill),
+        button("follow").on_press(Message::Followed(FollowedMessage::FollowTag(
+            tag.to_string()
+        ))),
+        button("search").on_press(Message::View(ViewMessage::Show(ViewMode::Grid(
+            tag.to_string(),
+            Some(1)
+        )))),
+    ]
+}
+
+/// The wiki for `tag`, once it's loaded. Whatever's left from another tag isn't shown.
+pub fn render_wiki<'a>(app: &'a App, tag: &str) -> Element<'a, Message> {
+    if let Some((_, err)) = app.wiki_error.as_ref().filter(|(failed, _)| failed == tag) {
+        return text(format!("Couldn't load the wiki: {err}")).into();
+    }
+    let Some(wiki) = app.wiki.as_ref().filter(|wiki| wiki.tag == tag) else {
+        return text("loading...").into();
+    };
+
+    let mut content = column![].spacing(12);
+
+    match &wiki.page {
+        Some(page) => {
+            if !page.other_names.is_empty() {
+                content = content.push(
+                    text(format!("Also known as: {}", page.other_names.join(", ")))
+                        .size(12)
+                        .shaping(Shaping::Advanced),
+                );
+            }
+            content = content.push(render_dtext(
+                &app.dtext,
+                DTextSource::Wiki(wiki.tag.clone()),
+                &app.store,
+            ));
+        }
+        None => content = content.push(text("This tag has no wiki page.")),
+    }
+
+    if !wiki.aliases.is_empty() {
+        content = content.push(relations("Aliases", &wiki.aliases));
+    }
+    if !wiki.implications.is_empty() {
+        content = content.push(relations("Implications", &wiki.implications));
+    }
+    if !wiki.related.is_empty() {
+        content = content.push(related_tags(&wiki.related));
+    }
+
+    scrollable(content.padding(16).width(Length::Fill)).into()
+}
+
+/// Aliases or implications as `from → to`, with each side linking to its wiki page.
+fn relations<'a>(heading: &'a str, relations: &'a [TagRelation]) -> Column<'a, Message> {
+    let mut list = column![text(heading).size(16)];
+    for relation in relations {
+        list = list.push(
+            row![
+                wiki_link(&relation.antecedent_name, None),
+                text("→"),
+                wiki_link(&relation.consequent_name, None),
+            ]
+            .align_y(Alignment::Center),
+        );
+    }
+    list
+}
+
+fn related_tags(related: &[RelatedTag]) -> Column<'_, Message> {
+    let mut list = column![text("Related tags").size(16)];
+    for tag in related {
+        list = list.push(wiki_link(&tag.name, Some(tag)));
+    }
+    list
+}
+
+fn wiki_link<'a>(tag: &'a str, related: Option<&RelatedTag>) -> Element<'a, Message> {
+    let mut label = text(tag).shaping(Shaping::Advanced);
+    if let Some(related) = related {
+        label = label.color(category_color(related.category));
+    }
+    button(label)
+        .on_press(Message::View(ViewMessage::Show(ViewMode::Wiki(
+            tag.to_string(),
+        ))))
+        .style(button::text)
+        .padding(2)
+        .into()
+}
@@ -1,30 +1,200 @@
-use reqwest::Method;
+use std::time::Duration;
+
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Method, Response, StatusCode};
 use serde::Deserialize;
 use thiserror::Error;
 use tracing::{debug, instrument, trace};
+use url::form_urlencoded::byte_serialize;
 
 use super::config::Auth;
-use super::http::{authed_request, CLIENT};
+use super::http::{authed_request, send_with_retry, CLIENT};
 use super::model::{Post, Vote};
 
 pub mod comments;
+pub mod pools;
 pub mod rate_limiter;
+pub mod tags;
+pub mod users;
+pub mod wiki;
 use rate_limiter::API_LIMITER;
 
-pub use comments::fetch_comments;
-
-const BASE_URL: &str = "https://e621.net";
+pub use comments::{
+    create_comment, fetch_all_comments, fetch_comments, update_comment, vote_comment,
+};
+pub use pools::{fetch_pool, fetch_pool_posts, search_pools};
+pub use tags::autocomplete_tags;
+pub use users::{fetch_blacklisted_tags, update_blacklisted_tags};
+pub use wiki::{fetch_wiki, TagWiki};
 
+/// Errors from talking to e621. The `Display` text is meant to be shown to the user.
 #[derive(Debug, Error)]
 pub enum ApiError {
-    #[error("Request failed: {0}")]
+    #[error("Couldn't reach e621: {0}")]
     Request(#[from] reqwest::Error),
 
-    #[error("Deserialization failed: {0}")]
+    #[error("e621 sent a response msg couldn't read: {0}")]
     Deserialize(#[from] serde_json::Error),
 
-    #[error("Voting error: {0}")]
-    VoteError(String),
+    /// 401, usually a wrong username or API key.
+    #[error("Not logged in: check your username and API key")]
+    Unauthorized,
+
+    /// 403, e.g. acting on a locked post or without the required user level.
+    #[error("Not allowed: {0}")]
+    Forbidden(String),
+
+    #[error("Not found")]
+    NotFound,
+
+    /// 429, or a 503 that says to slow down.
+    #[error("Rate limited by e621, try again shortly")]
+    RateLimited { retry_after: Option<Duration> },
+
+    /// 503 while e621 is down for maintenance or overloaded.
+    #[error("e621 is down for maintenance")]
+    Maintenance { retry_after: Option<Duration> },
+
+    /// 422, or any other request e621 rejected with a reason.
+    #[error("{message}")]
+    Validation { message: String },
+
+    /// Any other unsuccessful status.
+    #[error("e621 returned {status}: {message}")]
+    Status { status: StatusCode, message: String },
+
+    /// A successful response that didn't contain what was expected.
+    #[error("Unexpected response: {0}")]
+    Unexpected(String),
+}
+
+/// e621's JSON error body. Depending on the endpoint, the reason is in `message`, `reason`
+/// or a map of field `errors`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct ErrorResponse {
+    message: Option<String>,
+    reason: Option<String>,
+    errors: Option<serde_json::Value>,
+}
+
+impl ErrorResponse {
+    /// Gets the most specific message from an error body, if it has one.
+    fn parse(body: &str) -> Option<String> {
+        let error: ErrorResponse = serde_json::from_str(body).ok()?;
+
+        let field_errors = error.errors.map(|errors| match errors {
+            serde_json::Value::Object(fields) => fields
+                .into_iter()
+                .flat_map(|(field, messages)| {
+                    json_strings(messages)
+                        .into_iter()
+                        .map(move |message| format!("{field} {message}"))
+                })
+                .collect::<Vec<_>>()
+                .join("; "),
+            other => json_strings(other).join("; "),
+        });
+
+        field_errors
+            .filter(|errors| !errors.is_empty())
+            .or(error.message)
+            .or(error.reason)
+            .filter(|message| !message.trim().is_empty())
+    }
+}
+
+/// Percent-encodes `value` to go in a URL's query, so `&`, `#` and the like in a search stay part of
+/// it.
+fn encode(value: &str) -> String {
+    byte_serialize(value.as_bytes()).collect()
+}
+
+fn json_strings(value: serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::String(s) => vec![s],
+        serde_json::Value::Array(values) => values.into_iter().flat_map(json_strings).collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl ApiError {
+    /// Builds the error for an unsuccessful response from its status, `Retry-After` header and
+    /// body.
+    pub fn from_response(status: StatusCode, retry_after: Option<Duration>, body: &str) -> Self {
+        let message = ErrorResponse::parse(body);
+
+        match status {
+            StatusCode::UNAUTHORIZED => ApiError::Unauthorized,
+            StatusCode::FORBIDDEN => {
+                ApiError::Forbidden(message.unwrap_or_else(|| "Access denied".to_string()))
+            }
+            StatusCode::NOT_FOUND => ApiError::NotFound,
+            StatusCode::TOO_MANY_REQUESTS => ApiError::RateLimited { retry_after },
+            StatusCode::SERVICE_UNAVAILABLE => match message {
+                Some(message) if message.to_lowercase().contains("rate limit") => {
+                    ApiError::RateLimited { retry_after }
+                }
+                _ => ApiError::Maintenance { retry_after },
+            },
+            StatusCode::UNPROCESSABLE_ENTITY => ApiError::Validation {
+                message: message.unwrap_or_else(|| "Invalid request".to_string()),
+            },
+            _ => match message {
+                Some(message) if status.is_client_error() => ApiError::Validation { message },
+                message => ApiError::Status {
+                    status,
+                    message: message.unwrap_or_else(|| {
+                        status.canonical_reason().unwrap_or("unknown").to_string()
+                    }),
+                },
+            },
+        }
+    }
+}
+
+impl ApiError {
+    /// Whether every other request would fail the same way, so there's no point continuing a
+    /// batch of them.
+    pub fn affects_all_requests(&self) -> bool {
+        matches!(
+            self,
+            ApiError::Request(_)
+                | ApiError::Unauthorized
+                | ApiError::RateLimited { .. }
+                | ApiError::Maintenance { .. }
+        )
+    }
+}
+
+/// Passes successful responses through, and turns anything else into an [`ApiError`].
+pub(crate) async fn check_status(res: Response) -> Result<Response, ApiError> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+
+    let retry_after = res
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs);
+    let body = res.text().await.unwrap_or_default();
+    debug!("{status} response: {body}");
+
+    Err(ApiError::from_response(status, retry_after, &body))
+}
+
+/// Rate-limited GET of a JSON endpoint, returning the body of a successful response.
+pub(crate) async fn get_json(auth: Option<&Auth>, url: &str) -> Result<String, ApiError> {
+    trace!("GET {url}");
+    let request = match auth {
+        Some(auth) => authed_request(&CLIENT, Method::GET, url, auth),
+        None => CLIENT.get(url),
+    };
+    let res = send_with_retry(&API_LIMITER, request).await?;
+    Ok(check_status(res).await?.text().await?)
 }
 
 #[derive(Deserialize)]
@@ -32,9 +202,14 @@ struct PostsResponse {
     posts: Vec<Post>,
 }
 
+#[derive(Deserialize)]
+struct PostResponse {
+    post: Post,
+}
+
 /// Index point for [`fetch_posts`]. Take the following snippet from the [e6 API wiki page](https://e621.net/wiki_pages/2425#posts_list):
 /// > `page` The page that will be returned. Can also be used with `a` or `b` + post_id to get the posts after or before the specified post ID. For example `a13` gets every post after post_id 13 up to the limit. This overrides any ordering meta-tag, `order:id_desc` is always used instead.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FetchPoint {
     Page(usize),
     Before(u32),
@@ -42,6 +217,30 @@ pub enum FetchPoint {
 }
 
 impl FetchPoint {
+    /// Where to fetch the page after `page` of `query`'s results, the last of which was
+    /// `last_id`.
+    ///
+    /// Results newest first are paged by post ID, so posts uploaded in the meantime don't shift
+    /// the pages and repeat results. Any other order can only go by page number, as can
+    /// favorites, pools and sets, which e621 orders its own way.
+    pub fn following(query: &str, page: usize, last_id: Option<u32>) -> FetchPoint {
+        let tags = query
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        let order = tags.iter().rev().find_map(|tag| tag.strip_prefix("order:"));
+        let own_order = tags.iter().any(|tag| {
+            let tag = tag.trim_start_matches('~');
+            ["fav:", "pool:", "set:"]
+                .iter()
+                .any(|metatag| tag.starts_with(metatag))
+        });
+        match (order, own_order, last_id) {
+            (None | Some("id_desc"), false, Some(id)) => FetchPoint::Before(id),
+            _ => FetchPoint::Page(page + 1),
+        }
+    }
+
     pub fn page_query(&self) -> String {
         match self {
             FetchPoint::Page(page_num) => format!("&page={page_num}"),
@@ -53,35 +252,24 @@ impl FetchPoint {
 
 #[instrument]
 pub async fn fetch_posts(
+    base_url: &str,
     auth: Option<&Auth>,
     tag: String,
     fetch_point: Option<FetchPoint>,
 ) -> Result<Vec<Post>, ApiError> {
-    let mut url = format!("{BASE_URL}/posts.json?tags={}", tag);
+    let mut url = format!("{base_url}/posts.json?tags={}", tag);
 
     if let Some(point) = fetch_point {
         url.push_str(&point.page_query());
     }
 
     trace!("GET {url}");
-    let text: String = match auth {
-        Some(auth) => {
-            API_LIMITER
-                .run(async {
-                    authed_request(&CLIENT, Method::GET, &url, auth)
-                        .send()
-                        .await?
-                        .text()
-                        .await
-                })
-                .await?
-        }
-        None => {
-            API_LIMITER
-                .run(async { CLIENT.get(&url).send().await?.text().await })
-                .await?
-        }
+    let request = match auth {
+        Some(auth) => authed_request(&CLIENT, Method::GET, &url, auth),
+        None => CLIENT.get(&url),
     };
+    let res = send_with_retry(&API_LIMITER, request).await?;
+    let text = check_status(res).await?.text().await?;
     //trace!("Raw response: {text}");
     let res: PostsResponse = serde_json::from_str(&text)?;
     let posts = res.posts;
@@ -91,122 +279,230 @@ pub async fn fetch_posts(
     Ok(posts)
 }
 
+/// Fetches a single post, like one linked from a description or comment.
+#[instrument(skip(auth))]
+pub async fn fetch_post(base_url: &str, auth: Option<&Auth>, id: u32) -> Result<Post, ApiError> {
+    let url = format!("{base_url}/posts/{id}.json");
+
+    let text = get_json(auth, &url).await?;
+    let res: PostResponse = serde_json::from_str(&text)?;
+    Ok(res.post)
+}
+
 #[derive(Deserialize)]
 struct VoteResponse {
     our_score: i8,
 }
 
+impl VoteResponse {
+    /// The vote e621 recorded, which can differ from the one sent.
+    fn confirmed(&self) -> Result<Option<Vote>, ApiError> {
+        match self.our_score {
+            1 => Ok(Some(Vote::Upvote)),
+            -1 => Ok(Some(Vote::Downvote)),
+            0 => Ok(None),
+            score => Err(ApiError::Unexpected(format!("vote returned score {score}"))),
+        }
+    }
+}
+
 #[instrument(skip(auth))]
-pub async fn vote_post(auth: &Auth, id: u32, vote: Option<Vote>) -> Result<Option<Vote>, ApiError> {
-    let url = format!("{BASE_URL}/posts/{id}/votes.json");
+pub async fn vote_post(
+    base_url: &str,
+    auth: &Auth,
+    id: u32,
+    vote: Option<Vote>,
+) -> Result<Option<Vote>, ApiError> {
+    let url = format!("{base_url}/posts/{id}/votes.json");
 
     match vote {
         None => {
             trace!("DELETE {url}");
-            let res = API_LIMITER
-                .run(async {
-                    authed_request(&CLIENT, Method::DELETE, &url, auth)
-                        .json(&serde_json::json!({ "id": id }))
-                        .send()
-                        .await
-                })
-                .await?;
+            let res = send_with_retry(
+                &API_LIMITER,
+                authed_request(&CLIENT, Method::DELETE, &url, auth)
+                    .json(&serde_json::json!({ "id": id })),
+            )
+            .await?;
 
-            if res.status().is_success() {
-                return Ok(None);
-            } else {
-                let error_text = res.text().await.unwrap_or_default();
-                return Err(ApiError::VoteError(error_text));
-            }
+            check_status(res).await?;
+            Ok(None)
         }
         Some(vote) => {
             trace!("POST {url}");
-            let res = API_LIMITER
-                .run(async {
-                    authed_request(&CLIENT, Method::POST, &url, auth)
-                        .json(&serde_json::json!({ "id": id, "score": vote as i8 }))
-                        .send()
-                        .await
-                })
-                .await?;
-
-            if res.status().is_success() {
-                let parsed: VoteResponse = res
-                    .json()
-                    .await
-                    .map_err(|e| ApiError::VoteError(format!("Invalid vote response: {}", e)))?;
-
-                let confirmed: Option<Vote> = match parsed.our_score {
-                    1 => Some(Vote::Upvote),
-                    -1 => Some(Vote::Downvote),
-                    0 => None,
-                    _ => {
-                        return Err(ApiError::VoteError(format!(
-                            "Vote failed: unexpected value {}",
-                            parsed.our_score
-                        )))
-                    }
-                };
-
-                return Ok(confirmed);
-            } else {
-                let error_text = res.text().await.unwrap_or_default();
-                return Err(ApiError::VoteError(error_text));
-            }
+            let res = send_with_retry(
+                &API_LIMITER,
+                authed_request(&CLIENT, Method::POST, &url, auth)
+                    // Otherwise voting the same way twice takes the vote back.
+                    .json(&serde_json::json!({
+                        "id": id,
+                        "score": vote as i8,
+                        "no_unvote": true,
+                    })),
+            )
+            .await?;
+
+            let text = check_status(res).await?.text().await?;
+            let parsed: VoteResponse = serde_json::from_str(&text)?;
+            parsed.confirmed()
         }
     }
 }
 
-#[derive(Deserialize, Debug)]
-struct FavoriteErrorResponse {
-    message: String,
-}
-
 #[instrument(skip(auth))]
-pub async fn favorite_post(auth: &Auth, id: u32) -> Result<(), ApiError> {
-    let url = format!("{BASE_URL}/favorites.json");
+pub async fn favorite_post(base_url: &str, auth: &Auth, id: u32) -> Result<(), ApiError> {
+    let url = format!("{base_url}/favorites.json");
 
     trace!("POST {url}");
-    let res = API_LIMITER
-        .run(async {
-            authed_request(&CLIENT, Method::POST, &url, auth)
-                .json(&serde_json::json!({ "post_id": id }))
-                .send()
-                .await
-        })
-        .await?;
-
-    if res.status().is_success() {
-        return Ok(());
-    } else {
-        let error_text = res.text().await.unwrap_or_default();
-        if let Ok(error) = serde_json::from_str::<FavoriteErrorResponse>(&error_text) {
-            if error.message == "You have already favorited this post" {
-                return Ok(());
-            }
+    let res = send_with_retry(
+        &API_LIMITER,
+        authed_request(&CLIENT, Method::POST, &url, auth)
+            .json(&serde_json::json!({ "post_id": id })),
+    )
+    .await?;
+
+    match check_status(res).await {
+        Ok(_) => Ok(()),
+        Err(ApiError::Validation { message })
+            if message == "You have already favorited this post" =>
+        {
+            Ok(())
         }
-        return Err(ApiError::VoteError(error_text));
+        Err(err) => Err(err),
     }
 }
 
 #[instrument(skip(auth))]
-pub async fn unfavorite_post(auth: &Auth, id: u32) -> Result<(), ApiError> {
-    let url = format!("{BASE_URL}/favorites/{id}.json");
+pub async fn unfavorite_post(base_url: &str, auth: &Auth, id: u32) -> Result<(), ApiError> {
+    let url = format!("{base_url}/favorites/{id}.json");
 
     trace!("DELETE {url}");
-    let res = API_LIMITER
-        .run(async {
-            authed_request(&CLIENT, Method::DELETE, &url, auth)
-                .json(&serde_json::json!({ "post_id": id }))
-                .send()
-                .await
-        })
-        .await?;
-
-    if res.status().is_success() {
-        return Ok(());
-    } else {
-        let error_text = res.text().await.unwrap_or_default();
-        return Err(ApiError::VoteError(error_text));
+    let res = send_with_retry(
+        &API_LIMITER,
+        authed_request(&CLIENT, Method::DELETE, &url, auth)
+            .json(&serde_json::json!({ "post_id": id })),
+    )
+    .await?;
+
+    check_status(res).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses_map_to_variants() {
+        let err = |status: u16, body: &str| {
+            ApiError::from_response(StatusCode::from_u16(status).unwrap(), None, body)
+        };
+
+        assert!(matches!(err(401, ""), ApiError::Unauthorized));
+        assert!(matches!(
+            err(404, "<html>Not found</html>"),
+            ApiError::NotFound
+        ));
+        assert!(matches!(
+            err(429, ""),
+            ApiError::RateLimited { retry_after: None }
+        ));
+        assert!(matches!(
+            err(503, "<html>Down for maintenance</html>"),
+            ApiError::Maintenance { .. }
+        ));
+        assert!(matches!(
+            err(503, r#"{"success":false,"reason":"Rate limit exceeded"}"#),
+            ApiError::RateLimited { .. }
+        ));
+        assert!(matches!(
+            err(500, "<html>oops</html>"),
+            ApiError::Status { status, .. } if status == StatusCode::INTERNAL_SERVER_ERROR
+        ));
+    }
+
+    #[test]
+    fn newest_first_results_are_paged_by_id() {
+        assert_eq!(
+            FetchPoint::following("wolf", 1, Some(500)),
+            FetchPoint::Before(500)
+        );
+        assert_eq!(
+            FetchPoint::following("wolf Order:ID_DESC", 3, Some(500)),
+            FetchPoint::Before(500)
+        );
+        assert_eq!(
+            FetchPoint::following("wolf order:score", 3, Some(500)),
+            FetchPoint::Page(4)
+        );
+        assert_eq!(FetchPoint::following("wolf", 1, None), FetchPoint::Page(2));
+    }
+
+    #[test]
+    fn favorites_pools_and_sets_are_paged_by_number() {
+        assert_eq!(
+            FetchPoint::following("fav:someone", 1, Some(500)),
+            FetchPoint::Page(2)
+        );
+        assert_eq!(
+            FetchPoint::following("pool:1", 2, Some(500)),
+            FetchPoint::Page(3)
+        );
+        assert_eq!(
+            FetchPoint::following("wolf SET:favs", 1, Some(500)),
+            FetchPoint::Page(2)
+        );
+        assert_eq!(
+            FetchPoint::following("fav:someone order:id_desc", 1, Some(500)),
+            FetchPoint::Page(2)
+        );
+    }
+
+    #[test]
+    fn error_bodies_are_parsed() {
+        let forbidden = ApiError::from_response(
+            StatusCode::FORBIDDEN,
+            None,
+            r#"{"success":false,"reason":"Access Denied: Post is locked"}"#,
+        );
+        assert_eq!(
+            forbidden.to_string(),
+            "Not allowed: Access Denied: Post is locked"
+        );
+
+        let favorited = ApiError::from_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            None,
+            r#"{"success":false,"message":"You have already favorited this post","code":null}"#,
+        );
+        assert!(matches!(
+            favorited,
+            ApiError::Validation { ref message } if message == "You have already favorited this post"
+        ));
+
+        let fields = ApiError::from_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            None,
+            r#"{"errors":{"body":["is too short"]}}"#,
+        );
+        assert_eq!(fields.to_string(), "body is too short");
+
+        let bad_request =
+            ApiError::from_response(StatusCode::BAD_REQUEST, None, r#"{"message":"bad tag"}"#);
+        assert!(matches!(bad_request, ApiError::Validation { .. }));
+    }
+
+    #[test]
+    fn retry_after_is_kept() {
+        let err = ApiError::from_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            Some(Duration::from_secs(3)),
+            "",
+        );
+        assert!(matches!(
+            err,
+            ApiError::RateLimited { retry_after: Some(d) } if d == Duration::from_secs(3)
+        ));
+        assert!(err.affects_all_requests());
     }
 }
@@ -1,53 +1,156 @@
 use reqwest::Method;
-use std::cmp::{max, min};
+use rustc_hash::FxHashSet;
+use serde::Deserialize;
+use serde_json::{json, Value};
 use tracing::{debug, instrument, trace, Level};
 
 use super::super::config::Auth;
-use super::super::http::{authed_request, CLIENT};
-use super::super::model::Comment;
+use super::super::http::{authed_request, send_with_retry, CLIENT};
+use super::super::model::{Comment, Vote};
 use super::rate_limiter::API_LIMITER;
-use super::{ApiError, BASE_URL};
+use super::{check_status, get_json, ApiError, VoteResponse};
 
+/// Comments asked for per page.
+pub const COMMENT_PAGE_SIZE: usize = 75;
+/// e621 won't go past this page.
+const MAX_COMMENT_PAGE: u32 = 750;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CommentsResponse {
+    Comments(Vec<Comment>),
+    /// Posts without comments get `{"comments": []}` instead of an empty list.
+    Empty {
+        comments: [Comment; 0],
+    },
+}
+
+/// Fetches one page of comments on a post, oldest first.
 #[instrument(level = Level::TRACE)]
 pub async fn fetch_comments(
+    base_url: &str,
     auth: Option<&Auth>,
     post_id: u32,
     page: Option<u32>,
 ) -> Result<Vec<Comment>, ApiError> {
-    let mut page = match page {
-        Some(p) => p,
-        None => 1,
-    };
+    let page = page.unwrap_or(1).clamp(1, MAX_COMMENT_PAGE);
+    let url = format!(
+        "{base_url}/comments.json?group_by=comment&search[post_id]={post_id}&search[order]=id_asc&limit={COMMENT_PAGE_SIZE}&page={page}"
+    );
 
-    page = max(1, min(page, 750));
+    let text = get_json(auth, &url).await?;
+    let comments = match serde_json::from_str(&text)? {
+        CommentsResponse::Comments(comments) => comments,
+        CommentsResponse::Empty { comments: [] } => Vec::new(),
+    };
 
-    let url = format!(
-        "{BASE_URL}/comments.json?group_by=comment&search[post_id]={post_id}&search[order]=id_asc&page={page}"
+    debug!(
+        "Got {} comments for #{post_id} on page {page}",
+        comments.len()
     );
+    Ok(comments)
+}
 
-    trace!("GET {url}");
-    let text: String = match auth {
-        Some(auth) => {
-            API_LIMITER
-                .run(async {
-                    authed_request(&CLIENT, Method::GET, &url, auth)
-                        .send()
-                        .await?
-                        .text()
-                        .await
-                })
-                .await?
-        }
-        None => {
-            API_LIMITER
-                .run(async { CLIENT.get(&url).send().await?.text().await })
-                .await?
+/// Fetches every comment on a post, oldest first, following pages until one comes back short.
+#[instrument(level = Level::TRACE)]
+pub async fn fetch_all_comments(
+    base_url: &str,
+    auth: Option<&Auth>,
+    post_id: u32,
+) -> Result<Vec<Comment>, ApiError> {
+    let mut comments = Vec::new();
+    // Comments posted while paging shift the pages, so some can come back twice.
+    let mut seen = FxHashSet::default();
+
+    for page in 1..=MAX_COMMENT_PAGE {
+        let batch = fetch_comments(base_url, auth, post_id, Some(page)).await?;
+        let last_page = batch.len() < COMMENT_PAGE_SIZE;
+        comments.extend(batch.into_iter().filter(|comment| seen.insert(comment.id)));
+        if last_page {
+            break;
         }
-    };
+    }
+
+    comments.sort_by_key(|comment| comment.id);
+    Ok(comments)
+}
+
+/// Sends `body` as JSON with an authenticated request, returning the response text. These are
+/// never retried, since e621 may have saved a comment before the connection dropped.
+async fn send_json(
+    auth: &Auth,
+    method: Method,
+    url: &str,
+    body: Value,
+) -> Result<String, ApiError> {
+    trace!("{method} {url}");
+    let res = send_with_retry(
+        &API_LIMITER,
+        authed_request(&CLIENT, method.clone(), url, auth).json(&body),
+    )
+    .await?;
+    Ok(check_status(res).await?.text().await?)
+}
+
+/// Posts a new comment, returning it as e621 saved it.
+#[instrument(skip(auth, body))]
+pub async fn create_comment(
+    base_url: &str,
+    auth: &Auth,
+    post_id: u32,
+    body: &str,
+) -> Result<Comment, ApiError> {
+    let url = format!("{base_url}/comments.json");
+    let text = send_json(
+        auth,
+        Method::POST,
+        &url,
+        json!({ "comment": { "post_id": post_id, "body": body } }),
+    )
+    .await?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+/// Replaces the body of one of the user's comments.
+#[instrument(skip(auth, body))]
+pub async fn update_comment(
+    base_url: &str,
+    auth: &Auth,
+    id: u32,
+    body: &str,
+) -> Result<(), ApiError> {
+    let url = format!("{base_url}/comments/{id}.json");
+    send_json(
+        auth,
+        Method::PATCH,
+        &url,
+        json!({ "comment": { "body": body } }),
+    )
+    .await?;
+    Ok(())
+}
 
-    let res: Vec<Comment> = serde_json::from_str(&text).unwrap_or_default();
-    let length = &res.len();
+/// Votes on a comment, or removes the vote with `None`. Returns the vote e621 recorded.
+#[instrument(skip(auth))]
+pub async fn vote_comment(
+    base_url: &str,
+    auth: &Auth,
+    id: u32,
+    vote: Option<Vote>,
+) -> Result<Option<Vote>, ApiError> {
+    let url = format!("{base_url}/comments/{id}/votes.json");
 
-    debug!("Got {length} comments for #{post_id}");
-    Ok(res)
+    match vote {
+        None => {
+            send_json(auth, Method::DELETE, &url, json!({})).await?;
+            Ok(None)
+        }
+        Some(vote) => {
+            // Otherwise voting the same way twice takes the vote back.
+            let body = json!({ "score": vote as i8, "no_unvote": true });
+            let text = send_json(auth, Method::POST, &url, body).await?;
+            let parsed: VoteResponse = serde_json::from_str(&text)?;
+            parsed.confirmed()
+        }
+    }
 }
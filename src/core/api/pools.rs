@@ -0,0 +1,79 @@
+use rustc_hash::FxHashMap;
+use tracing::{debug, instrument};
+
+use super::super::config::Auth;
+use super::super::model::{Pool, Post};
+use super::{encode, fetch_posts, get_json, ApiError, FetchPoint};
+
+/// Posts per page of a `pool:` search, e621's default.
+const POOL_PAGE_SIZE: usize = 75;
+
+/// Searches pools by name. `*` works as a wildcard, and spaces match underscores.
+#[instrument(skip(auth))]
+pub async fn search_pools(
+    base_url: &str,
+    auth: Option<&Auth>,
+    name: &str,
+    page: Option<usize>,
+) -> Result<Vec<Pool>, ApiError> {
+    let name = name.trim().replace(' ', "_");
+    let pattern = if name.contains('*') {
+        name
+    } else {
+        format!("*{name}*")
+    };
+    let url = format!(
+        "{base_url}/pools.json?search[name_matches]={}&search[order]=updated_at&page={}",
+        encode(&pattern),
+        page.unwrap_or(1)
+    );
+
+    let text = get_json(auth, &url).await?;
+    let pools: Vec<Pool> = serde_json::from_str(&text)?;
+
+    debug!("Got {} pools matching {pattern}", pools.len());
+    Ok(pools)
+}
+
+#[instrument(skip(auth))]
+pub async fn fetch_pool(base_url: &str, auth: Option<&Auth>, id: u32) -> Result<Pool, ApiError> {
+    let url = format!("{base_url}/pools/{id}.json");
+
+    let text = get_json(auth, &url).await?;
+    let pool: Pool = serde_json::from_str(&text)?;
+
+    debug!("Got pool #{id} with {} posts", pool.post_ids.len());
+    Ok(pool)
+}
+
+/// Fetches every post in `pool`, in reading order. Deleted or hidden posts are left out.
+#[instrument(skip(auth, pool), fields(pool = pool.id))]
+pub async fn fetch_pool_posts(
+    base_url: &str,
+    auth: Option<&Auth>,
+    pool: &Pool,
+) -> Result<Vec<Post>, ApiError> {
+    let mut found: FxHashMap<u32, Post> = FxHashMap::default();
+    let max_pages = pool.post_ids.len() / POOL_PAGE_SIZE + 1;
+
+    for page in 1..=max_pages {
+        let posts = fetch_posts(
+            base_url,
+            auth,
+            format!("pool:{}", pool.id),
+            Some(FetchPoint::Page(page)),
+        )
+        .await?;
+        let done = posts.len() < POOL_PAGE_SIZE;
+        found.extend(posts.into_iter().map(|post| (post.id, post)));
+        if done || found.len() >= pool.post_ids.len() {
+            break;
+        }
+    }
+
+    Ok(pool
+        .post_ids
+        .iter()
+        .filter_map(|id| found.remove(id))
+        .collect())
+}
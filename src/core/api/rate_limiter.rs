@@ -1,37 +1,318 @@
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::time::Duration;
-use tokio::sync::Semaphore;
-use tokio::time::sleep;
 
-/// Centralized API rate limiter to enforce e621's 2 requests/sec cap.
-#[derive(Clone)]
-pub struct ApiLimiter {
-    semaphore: Arc<Semaphore>,
-    delay: Duration,
+use once_cell::sync::Lazy;
+use tokio::time::{sleep_until, Instant};
+use tracing::trace;
+
+/// Token-bucket rate limiter: allows bursts of up to `burst` calls, refilling at `per_second`.
+///
+/// Callers reserve a token as soon as they arrive, so waiting callers are served in order and
+/// a bucket in debt makes later callers wait longer.
+pub struct RateLimiter {
+    name: &'static str,
+    bucket: Mutex<Bucket>,
+    queued: AtomicUsize,
+    in_flight: AtomicUsize,
+    completed: AtomicU64,
+    total_wait_us: AtomicU64,
+    max_wait_us: AtomicU64,
 }
 
-impl ApiLimiter {
-    pub fn new(max_concurrent: usize, delay: Duration) -> Self {
+struct Bucket {
+    burst: f64,
+    per_second: f64,
+    /// Can go negative while callers are waiting on reserved tokens.
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Snapshot of a [`RateLimiter`], for the debug view.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimiterStats {
+    pub name: &'static str,
+    /// Calls waiting for a token.
+    pub queued: usize,
+    /// Calls that got a token and haven't finished.
+    pub in_flight: usize,
+    pub completed: u64,
+    /// Tokens available right now; negative when callers are queued.
+    pub tokens: f64,
+    pub average_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(name: &'static str, burst: u32, per_second: f64) -> Self {
         Self {
-            semaphore: Arc::new(Semaphore::new(max_concurrent)),
-            delay,
+            name,
+            bucket: Mutex::new(Bucket {
+                burst: burst as f64,
+                per_second,
+                tokens: burst as f64,
+                updated: Instant::now(),
+            }),
+            queued: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            total_wait_us: AtomicU64::new(0),
+            max_wait_us: AtomicU64::new(0),
         }
     }
 
-    /// Run a future under limit control.
+    /// Run a future once a token is available.
     pub async fn run<T, F>(&self, task: F) -> T
     where
         F: std::future::Future<Output = T>,
     {
-        let _permit = self.semaphore.acquire().await.expect("semaphore poisoned!");
+        let waited = {
+            let _queued = Counter::enter(&self.queued);
+            self.acquire().await
+        };
+        self.record_wait(waited);
+
+        let _in_flight = Counter::enter(&self.in_flight);
         let result = task.await;
-        sleep(self.delay).await;
+        self.completed.fetch_add(1, Ordering::Relaxed);
         result
     }
+
+    /// Reserves a token and sleeps until it's due. Returns how long that took. A caller dropped
+    /// while waiting gives its token back.
+    async fn acquire(&self) -> Duration {
+        let now = Instant::now();
+        let ready_at = {
+            let mut bucket = self.bucket.lock().expect("rate limiter poisoned!");
+            bucket.refill(now);
+            bucket.tokens -= 1.0;
+            if bucket.tokens >= 0.0 {
+                now
+            } else {
+                now + Duration::from_secs_f64(-bucket.tokens / bucket.per_second)
+            }
+        };
+        let reservation = Reservation {
+            bucket: &self.bucket,
+            used: false,
+        };
+
+        if ready_at > now {
+            trace!("{} limiter: waiting {:?}", self.name, ready_at - now);
+            sleep_until(ready_at).await;
+        }
+        reservation.use_up();
+        ready_at - now
+    }
+
+    fn record_wait(&self, waited: Duration) {
+        let micros = waited.as_micros() as u64;
+        self.total_wait_us.fetch_add(micros, Ordering::Relaxed);
+        self.max_wait_us.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> LimiterStats {
+        let tokens = {
+            let mut bucket = self.bucket.lock().expect("rate limiter poisoned!");
+            bucket.refill(Instant::now());
+            bucket.tokens
+        };
+        let completed = self.completed.load(Ordering::Relaxed);
+        let in_flight = self.in_flight.load(Ordering::Relaxed);
+        let started = completed + in_flight as u64;
+        let total_wait = Duration::from_micros(self.total_wait_us.load(Ordering::Relaxed));
+
+        LimiterStats {
+            name: self.name,
+            queued: self.queued.load(Ordering::Relaxed),
+            in_flight,
+            completed,
+            tokens,
+            average_wait: if started == 0 {
+                Duration::ZERO
+            } else {
+                total_wait / started as u32
+            },
+            max_wait: Duration::from_micros(self.max_wait_us.load(Ordering::Relaxed)),
+        }
+    }
 }
 
-use once_cell::sync::Lazy;
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.burst);
+        self.updated = now;
+    }
+}
 
-pub static API_LIMITER: Lazy<ApiLimiter> = Lazy::new(|| {
-    ApiLimiter::new(1, Duration::from_millis(750)) // Technically the limit is 2/sec, might as well give some wiggle room
-});
+/// A token taken from a bucket, put back unless it's used.
+struct Reservation<'a> {
+    bucket: &'a Mutex<Bucket>,
+    used: bool,
+}
+
+impl Reservation<'_> {
+    fn use_up(mut self) {
+        self.used = true;
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if !self.used {
+            let mut bucket = self.bucket.lock().expect("rate limiter poisoned!");
+            bucket.refill(Instant::now());
+            bucket.tokens = (bucket.tokens + 1.0).min(bucket.burst);
+        }
+    }
+}
+
+/// Increments a counter for as long as it's alive, so cancelled calls don't leave it stuck.
+struct Counter<'a>(&'a AtomicUsize);
+
+impl<'a> Counter<'a> {
+    fn enter(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Counter(counter)
+    }
+}
+
+impl Drop for Counter<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// JSON API calls to e621. Technically the limit is 2/sec, might as well give some wiggle room.
+pub static API_LIMITER: Lazy<RateLimiter> = Lazy::new(|| RateLimiter::new("API", 2, 1.5));
+
+/// Downloads from the static media hosts, which aren't held to the API limit.
+pub static MEDIA_LIMITER: Lazy<RateLimiter> = Lazy::new(|| RateLimiter::new("media", 8, 8.0));
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn bursts_then_waits() {
+        let limiter = RateLimiter::new("test", 3, 2.0);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limiter.run(async {}).await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        limiter.run(async {}).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+        limiter.run(async {}).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(1000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sustained_rate_is_kept() {
+        let limiter = Arc::new(RateLimiter::new("test", 2, 4.0));
+        let start = Instant::now();
+
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.run(async { Instant::now() }).await })
+            })
+            .collect();
+        let mut finished = Vec::new();
+        for task in tasks {
+            finished.push(task.await.unwrap() - start);
+        }
+        finished.sort();
+
+        // Two go straight away, then one every 250ms.
+        assert_eq!(finished[1], Duration::ZERO);
+        assert_eq!(finished[2], Duration::from_millis(250));
+        assert_eq!(finished[9], Duration::from_millis(2000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_refill_is_capped_at_burst() {
+        let limiter = RateLimiter::new("test", 2, 1.0);
+        limiter.run(async {}).await;
+        limiter.run(async {}).await;
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(limiter.stats().tokens, 2.0);
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.run(async {}).await;
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stats_track_queue_and_in_flight() {
+        let limiter = Arc::new(RateLimiter::new("test", 1, 1.0));
+        let (release, hold) = tokio::sync::oneshot::channel::<()>();
+
+        let first = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.run(hold).await })
+        };
+        let second = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.run(async {}).await })
+        };
+        tokio::task::yield_now().await;
+
+        let stats = limiter.stats();
+        assert_eq!((stats.queued, stats.in_flight), (1, 1));
+        assert_eq!(stats.tokens, -1.0);
+
+        release.send(()).unwrap();
+        first.await.unwrap().unwrap();
+        second.await.unwrap();
+
+        let stats = limiter.stats();
+        assert_eq!((stats.queued, stats.in_flight, stats.completed), (0, 0, 2));
+        assert_eq!(stats.max_wait, Duration::from_secs(1));
+        assert_eq!(stats.average_wait, Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_callers_give_their_token_back() {
+        let limiter = Arc::new(RateLimiter::new("test", 1, 1.0));
+        limiter.run(async {}).await;
+
+        let waiting = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.run(async {}).await })
+        };
+        tokio::task::yield_now().await;
+        assert_eq!(limiter.stats().tokens, -1.0);
+        waiting.abort();
+        assert!(waiting.await.unwrap_err().is_cancelled());
+        assert_eq!(limiter.stats().tokens, 0.0);
+
+        // Only the one token in debt to wait for, not the abandoned one too.
+        let start = Instant::now();
+        limiter.run(async {}).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert_eq!(limiter.stats().completed, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pools_are_independent() {
+        let api = RateLimiter::new("api", 1, 1.0);
+        let media = RateLimiter::new("media", 4, 4.0);
+        let start = Instant::now();
+
+        api.run(async {}).await;
+        for _ in 0..4 {
+            media.run(async {}).await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(api.stats().tokens, 0.0);
+    }
+}
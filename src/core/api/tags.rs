@@ -0,0 +1,31 @@
+use tracing::{debug, instrument};
+
+use super::super::config::Auth;
+use super::super::model::TagSuggestion;
+use super::{encode, get_json, ApiError};
+
+/// Shortest prefix e621 will autocomplete.
+pub const MIN_AUTOCOMPLETE_LEN: usize = 3;
+
+/// Tags starting with `prefix`, most used first, including tags reached through an alias.
+#[instrument(skip(auth))]
+pub async fn autocomplete_tags(
+    base_url: &str,
+    auth: Option<&Auth>,
+    prefix: &str,
+) -> Result<Vec<TagSuggestion>, ApiError> {
+    if prefix.chars().count() < MIN_AUTOCOMPLETE_LEN {
+        return Ok(Vec::new());
+    }
+
+    let url = format!(
+        "{base_url}/tags/autocomplete.json?search[name_matches]={}&expiry=7",
+        encode(prefix)
+    );
+
+    let text = get_json(auth, &url).await?;
+    let suggestions: Vec<TagSuggestion> = serde_json::from_str(&text)?;
+
+    debug!("Got {} suggestions for {prefix}", suggestions.len());
+    Ok(suggestions)
+}
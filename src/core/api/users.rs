@@ -0,0 +1,89 @@
+use reqwest::Method;
+use serde::Deserialize;
+use tracing::{debug, instrument, trace};
+
+use super::super::config::Auth;
+use super::super::http::{authed_request, send_with_retry, CLIENT};
+use super::rate_limiter::API_LIMITER;
+use super::{check_status, ApiError};
+
+/// The parts of the logged-in user's account that msg cares about.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Account {
+    pub id: u32,
+    pub name: String,
+    /// Only present when fetching your own account.
+    #[serde(default)]
+    pub blacklisted_tags: Option<String>,
+}
+
+impl Account {
+    /// The account's blacklist, one rule per line.
+    pub fn blacklist_rules(&self) -> Vec<String> {
+        self.blacklisted_tags
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[instrument(skip(auth))]
+pub async fn fetch_account(base_url: &str, auth: &Auth) -> Result<Account, ApiError> {
+    let url = format!("{base_url}/users/{}.json", auth.username);
+
+    trace!("GET {url}");
+    let res = send_with_retry(
+        &API_LIMITER,
+        authed_request(&CLIENT, Method::GET, &url, auth),
+    )
+    .await?;
+
+    let text = check_status(res).await?.text().await?;
+    let account: Account = serde_json::from_str(&text)?;
+    if account.blacklisted_tags.is_none() {
+        return Err(ApiError::Unexpected(format!(
+            "No blacklist returned for {}; is the API key correct?",
+            account.name
+        )));
+    }
+
+    debug!(
+        "Got {} blacklist rules for {}",
+        account.blacklist_rules().len(),
+        account.name
+    );
+    Ok(account)
+}
+
+/// Fetches the blacklist saved on the logged-in user's account.
+pub async fn fetch_blacklisted_tags(base_url: &str, auth: &Auth) -> Result<Vec<String>, ApiError> {
+    Ok(fetch_account(base_url, auth).await?.blacklist_rules())
+}
+
+/// Replaces the blacklist saved on the logged-in user's account with `rules`.
+#[instrument(skip(auth, rules))]
+pub async fn update_blacklisted_tags(
+    base_url: &str,
+    auth: &Auth,
+    rules: &[String],
+) -> Result<(), ApiError> {
+    let account = fetch_account(base_url, auth).await?;
+    let url = format!("{base_url}/users/{}.json", account.id);
+    let blacklisted_tags = rules.join("\n");
+
+    trace!("PATCH {url}");
+    let res = send_with_retry(
+        &API_LIMITER,
+        authed_request(&CLIENT, Method::PATCH, &url, auth)
+            .form(&[("user[blacklisted_tags]", &blacklisted_tags)]),
+    )
+    .await?;
+
+    check_status(res).await?;
+    debug!("Pushed {} blacklist rules to {}", rules.len(), account.name);
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use serde::Deserialize;
+use tracing::{debug, instrument};
+
+use super::super::config::Auth;
+use super::super::model::{RelatedTag, TagRelation, WikiPage};
+use super::{encode, get_json, ApiError};
+
+/// Everything shown on a tag's wiki view.
+#[derive(Debug, Clone)]
+pub struct TagWiki {
+    pub tag: String,
+    /// Not every tag has a wiki page.
+    pub page: Option<WikiPage>,
+    /// Active aliases to or from the tag.
+    pub aliases: Vec<TagRelation>,
+    /// Active implications to or from the tag.
+    pub implications: Vec<TagRelation>,
+    pub related: Vec<RelatedTag>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RelationsResponse {
+    Relations(Vec<TagRelation>),
+    /// No matches get `{"tag_aliases": []}` or `{"tag_implications": []}` instead of an empty
+    /// list.
+    Empty {
+        #[serde(alias = "tag_implications")]
+        tag_aliases: [TagRelation; 0],
+    },
+}
+
+#[derive(Deserialize)]
+struct RelatedTagsResponse {
+    /// Pairs of tag name and category ID, most related first.
+    #[serde(default)]
+    tags: Vec<(String, u8)>,
+}
+
+/// Fetches the wiki page for `tag`, along with its aliases, implications and related tags.
+#[instrument(skip(auth))]
+pub async fn fetch_wiki(
+    base_url: &str,
+    auth: Option<&Auth>,
+    tag: &str,
+) -> Result<TagWiki, ApiError> {
+    Ok(TagWiki {
+        tag: tag.to_string(),
+        page: fetch_wiki_page(base_url, auth, tag).await?,
+        aliases: fetch_tag_relations(base_url, auth, "tag_aliases", tag).await?,
+        implications: fetch_tag_relations(base_url, auth, "tag_implications", tag).await?,
+        related: fetch_related_tags(base_url, auth, tag).await?,
+    })
+}
+
+/// The wiki page titled `tag`, if there is one.
+#[instrument(skip(auth))]
+pub async fn fetch_wiki_page(
+    base_url: &str,
+    auth: Option<&Auth>,
+    tag: &str,
+) -> Result<Option<WikiPage>, ApiError> {
+    let url = format!("{base_url}/wiki_pages.json?search[title]={}", encode(tag));
+
+    let text = get_json(auth, &url).await?;
+    let pages: Vec<WikiPage> = serde_json::from_str(&text)?;
+
+    debug!("Got {} wiki pages titled {tag}", pages.len());
+    Ok(pages.into_iter().find(|page| page.title == tag))
+}
+
+/// Active `tag_aliases` or `tag_implications` involving `tag` on either side.
+async fn fetch_tag_relations(
+    base_url: &str,
+    auth: Option<&Auth>,
+    kind: &str,
+    tag: &str,
+) -> Result<Vec<TagRelation>, ApiError> {
+    let url = format!(
+        "{base_url}/{kind}.json?search[name_matches]={}&search[status]=active",
+        encode(tag)
+    );
+
+    let text = get_json(auth, &url).await?;
+    let relations = match serde_json::from_str(&text)? {
+        RelationsResponse::Relations(relations) => relations,
+        RelationsResponse::Empty { tag_aliases: [] } => Vec::new(),
+    };
+
+    debug!("Got {} {kind} for {tag}", relations.len());
+    Ok(relations)
+}
+
+/// Tags that often appear with `tag`, most related first.
+async fn fetch_related_tags(
+    base_url: &str,
+    auth: Option<&Auth>,
+    tag: &str,
+) -> Result<Vec<RelatedTag>, ApiError> {
+    let url = format!("{base_url}/related_tag.json?search[query]={}", encode(tag));
+
+    let text = get_json(auth, &url).await?;
+    let res: RelatedTagsResponse = serde_json::from_str(&text)?;
+
+    Ok(res
+        .tags
+        .into_iter()
+        .filter(|(name, _)| name != tag)
+        .map(|(name, category)| RelatedTag {
+            name,
+            category: category.into(),
+        })
+        .collect())
+}
@@ -0,0 +1,86 @@
+//! Finding the tag being typed in a search query, and completing it.
+
+use super::api::tags::MIN_AUTOCOMPLETE_LEN;
+
+/// The last word of a search query, split into its prefix and tag name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialTag<'a> {
+    /// `-` to exclude the tag, `~` for "any of", or empty.
+    pub prefix: &'a str,
+    pub name: &'a str,
+    /// Byte offset of the word in the query.
+    pub start: usize,
+}
+
+/// The tag being typed at the end of `query`, if it's long enough to autocomplete.
+///
+/// Metatags like `order:score` and wildcard searches aren't autocompleted.
+pub fn partial_tag(query: &str) -> Option<PartialTag<'_>> {
+    if query.ends_with(char::is_whitespace) {
+        return None;
+    }
+
+    let start = query
+        .rfind(char::is_whitespace)
+        .map(|i| i + query[i..].chars().next().map_or(1, char::len_utf8))
+        .unwrap_or(0);
+    let word = &query[start..];
+    let (prefix, name) = match word.chars().next() {
+        Some(c @ ('-' | '~')) => word.split_at(c.len_utf8()),
+        _ => ("", word),
+    };
+
+    if name.chars().count() < MIN_AUTOCOMPLETE_LEN || name.contains([':', '*']) {
+        return None;
+    }
+    Some(PartialTag {
+        prefix,
+        name,
+        start,
+    })
+}
+
+/// Replaces the tag being typed in `query` with `tag`, keeping its prefix, ready for the next one.
+pub fn complete(query: &str, tag: &str) -> String {
+    match partial_tag(query) {
+        Some(partial) => format!("{}{}{tag} ", &query[..partial.start], partial.prefix),
+        None => query.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_word_is_the_partial_tag() {
+        assert_eq!(
+            partial_tag("order:score wol"),
+            Some(PartialTag {
+                prefix: "",
+                name: "wol",
+                start: 12
+            })
+        );
+        assert_eq!(partial_tag("fox -canin").unwrap().prefix, "-");
+        assert_eq!(partial_tag("~domestic_ca").unwrap().name, "domestic_ca");
+    }
+
+    #[test]
+    fn short_words_and_metatags_are_skipped() {
+        assert_eq!(partial_tag("fo"), None);
+        assert_eq!(partial_tag("-ab"), None);
+        assert_eq!(partial_tag("wolf "), None);
+        assert_eq!(partial_tag("order:sc"), None);
+        assert_eq!(partial_tag("wol*"), None);
+        assert_eq!(partial_tag(""), None);
+    }
+
+    #[test]
+    fn completion_keeps_prefix_and_earlier_tags() {
+        assert_eq!(complete("fox -canin", "canine"), "fox -canine ");
+        assert_eq!(complete("~wol", "wolf"), "~wolf ");
+        assert_eq!(complete("rating:s  dra", "dragon"), "rating:s  dragon ");
+        assert_eq!(complete("fo", "fox"), "fo");
+    }
+}
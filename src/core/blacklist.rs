@@ -1,31 +1,769 @@
-use super::model::Post;
+//! Blacklist rules, following the syntax e621 documents on its [blacklist help page].
+//!
+//! Each line of a [`Blacklist`] is one rule. A rule matches a post when every plain token
+//! matches, no `-negated` token matches, and (if the rule has any) at least one `~optional`
+//! token matches. Tags may contain `*` wildcards, and the following metatags are understood:
+//!
+//! - `rating:s`, `rating:q`, `rating:e` (or `safe`/`questionable`/`explicit`)
+//! - `score:`, `favcount:` and `id:` with `5`, `<5`, `<=5`, `>5`, `>=5`, `1..5`, `..5` or `5..`
+//! - `type:` matched against the file extension (`type:webm`, `type:png`, ...)
+//! - `user:name` or `user:!123` matched against the uploader
+//!
+//! Unknown metatags are treated as ordinary tags, same as on the site.
+//!
+//! [blacklist help page]: https://e621.net/help/blacklist
+
+use super::model::{Post, Rating};
+use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
 
 #[derive(Debug, Deserialize, Default, Serialize, Clone, PartialEq)]
 pub struct Blacklist {
     pub rules: Vec<String>,
 }
 
-pub fn is_blacklisted(post: &Post, blacklist: &Blacklist) -> bool {
-    let tags = post.tags.iter().flat_map(|(_, t)| t).collect::<Vec<_>>();
-
-    'rule: for rule in &blacklist.rules {
-        let tokens = rule.split_whitespace();
-        for token in tokens {
-            if token.starts_with("-") {
-                let neg = &token[1..];
-                if tags.iter().any(|t| *t == neg) {
-                    continue 'rule;
+impl Blacklist {
+    /// Parses every non-empty line into a [`Rule`].
+    pub fn parse(&self) -> Vec<Rule> {
+        self.rules
+            .iter()
+            .map(|rule| Rule::parse(rule))
+            .filter(|rule| !rule.is_empty())
+            .collect()
+    }
+
+    /// Compares this blacklist with `other`, rule by rule.
+    pub fn diff(&self, other: &Blacklist) -> BlacklistDiff {
+        let ours = self.normalized();
+        let theirs = other.normalized();
+
+        let added = theirs
+            .iter()
+            .filter(|(key, _)| !ours.iter().any(|(k, _)| k == key))
+            .map(|(_, rule)| rule.to_string())
+            .collect();
+        let removed = ours
+            .iter()
+            .filter(|(key, _)| !theirs.iter().any(|(k, _)| k == key))
+            .map(|(_, rule)| rule.to_string())
+            .collect::<Vec<String>>();
+
+        BlacklistDiff {
+            added,
+            unchanged: ours.len() - removed.len(),
+            removed,
+        }
+    }
+
+    /// Every rule from this blacklist, followed by any rules from `other` it doesn't have yet.
+    pub fn merged(&self, other: &Blacklist) -> Blacklist {
+        let mut rules = self.normalized();
+        for (key, rule) in other.normalized() {
+            if !rules.iter().any(|(k, _)| *k == key) {
+                rules.push((key, rule));
+            }
+        }
+
+        Blacklist {
+            rules: rules
+                .into_iter()
+                .map(|(_, rule)| rule.to_string())
+                .collect(),
+        }
+    }
+
+    /// Non-empty rules paired with a whitespace- and case-normalized key for comparison.
+    fn normalized(&self) -> Vec<(String, &str)> {
+        let mut seen: Vec<(String, &str)> = Vec::new();
+        for rule in &self.rules {
+            let rule = rule.trim();
+            let key = rule
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase();
+            if !key.is_empty() && !seen.iter().any(|(k, _)| *k == key) {
+                seen.push((key, rule));
+            }
+        }
+        seen
+    }
+}
+
+/// Result of [`Blacklist::diff`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlacklistDiff {
+    /// Rules only in the other blacklist.
+    pub added: Vec<String>,
+    /// Rules only in this blacklist.
+    pub removed: Vec<String>,
+    /// Number of rules in both.
+    pub unchanged: usize,
+}
+
+impl BlacklistDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A single parsed blacklist line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// The line as the user wrote it.
+    pub source: String,
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    condition: Condition,
+    negated: bool,
+    optional: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Tag(String),
+    Wildcard(String),
+    Rating(Rating),
+    Score(NumRange),
+    FavCount(NumRange),
+    Id(NumRange),
+    Type(String),
+    UserName(String),
+    UserId(u32),
+}
+
+/// Comparison used by numeric metatags like `score:<0` or `id:100..200`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumRange {
+    Eq(i64),
+    Lt(i64),
+    Le(i64),
+    Gt(i64),
+    Ge(i64),
+    Between(i64, i64),
+}
+
+impl NumRange {
+    pub fn parse(value: &str) -> Option<NumRange> {
+        let num = |s: &str| s.parse::<i64>().ok();
+
+        if let Some(rest) = value.strip_prefix("<=") {
+            num(rest).map(NumRange::Le)
+        } else if let Some(rest) = value.strip_prefix(">=") {
+            num(rest).map(NumRange::Ge)
+        } else if let Some(rest) = value.strip_prefix('<') {
+            num(rest).map(NumRange::Lt)
+        } else if let Some(rest) = value.strip_prefix('>') {
+            num(rest).map(NumRange::Gt)
+        } else if let Some((low, high)) = value.split_once("..") {
+            match (low.is_empty(), high.is_empty()) {
+                (true, true) => None,
+                (true, false) => num(high).map(NumRange::Le),
+                (false, true) => num(low).map(NumRange::Ge),
+                (false, false) => Some(NumRange::Between(num(low)?, num(high)?)),
+            }
+        } else {
+            num(value).map(NumRange::Eq)
+        }
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        match *self {
+            NumRange::Eq(n) => value == n,
+            NumRange::Lt(n) => value < n,
+            NumRange::Le(n) => value <= n,
+            NumRange::Gt(n) => value > n,
+            NumRange::Ge(n) => value >= n,
+            NumRange::Between(low, high) => low <= value && value <= high,
+        }
+    }
+}
+
+impl Rule {
+    pub fn parse(source: &str) -> Rule {
+        let tokens = source
+            .split_whitespace()
+            .filter_map(Token::parse)
+            .collect::<Vec<_>>();
+
+        Rule {
+            source: source.trim().to_string(),
+            tokens,
+        }
+    }
+
+    /// A rule with no tokens never matches anything.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn matches(&self, post: &Post) -> bool {
+        if self.tokens.is_empty() {
+            return false;
+        }
+
+        let mut has_optional = false;
+        let mut any_optional = false;
+
+        for token in &self.tokens {
+            let hit = token.condition.matches(post);
+            if token.optional {
+                has_optional = true;
+                any_optional |= hit;
+            } else if hit == token.negated {
+                return false;
+            }
+        }
+
+        !has_optional || any_optional
+    }
+}
+
+impl Token {
+    fn parse(raw: &str) -> Option<Token> {
+        let raw = raw.to_lowercase();
+        let (negated, optional, body) = if let Some(rest) = raw.strip_prefix('-') {
+            (true, false, rest)
+        } else if let Some(rest) = raw.strip_prefix('~') {
+            (false, true, rest)
+        } else {
+            (false, false, raw.as_str())
+        };
+
+        if body.is_empty() {
+            return None;
+        }
+
+        Some(Token {
+            condition: Condition::parse(body),
+            negated,
+            optional,
+        })
+    }
+}
+
+impl Condition {
+    fn parse(body: &str) -> Condition {
+        if let Some((name, value)) = body.split_once(':') {
+            let metatag = match name {
+                "rating" => parse_rating(value).map(Condition::Rating),
+                "score" => NumRange::parse(value).map(Condition::Score),
+                "favcount" => NumRange::parse(value).map(Condition::FavCount),
+                "id" => NumRange::parse(value).map(Condition::Id),
+                "type" if !value.is_empty() => Some(Condition::Type(value.to_string())),
+                "user" => match value.strip_prefix('!') {
+                    Some(id) => id.parse().ok().map(Condition::UserId),
+                    None if !value.is_empty() => Some(Condition::UserName(value.to_string())),
+                    None => None,
+                },
+                _ => None,
+            };
+            if let Some(condition) = metatag {
+                return condition;
+            }
+        }
+
+        if body.contains('*') {
+            Condition::Wildcard(body.to_string())
+        } else {
+            Condition::Tag(body.to_string())
+        }
+    }
+
+    fn matches(&self, post: &Post) -> bool {
+        match self {
+            Condition::Tag(tag) => post_tags(post).any(|t| t == tag),
+            Condition::Wildcard(pattern) => post_tags(post).any(|t| wildcard_match(pattern, t)),
+            Condition::Rating(rating) => post.rating == *rating,
+            Condition::Score(range) => range.contains(post.score.total as i64),
+            Condition::FavCount(range) => range.contains(post.fav_count as i64),
+            Condition::Id(range) => range.contains(post.id as i64),
+            Condition::Type(ext) => post
+                .file
+                .ext
+                .as_deref()
+                .is_some_and(|e| e.eq_ignore_ascii_case(ext)),
+            Condition::UserName(name) => post
+                .uploader_name
+                .as_deref()
+                .is_some_and(|n| n.eq_ignore_ascii_case(name)),
+            Condition::UserId(id) => post.uploader_id == Some(*id),
+        }
+    }
+}
+
+fn post_tags(post: &Post) -> impl Iterator<Item = &str> {
+    post.tags
+        .iter()
+        .flat_map(|(_, tags)| tags.iter().map(String::as_str))
+}
+
+fn parse_rating(value: &str) -> Option<Rating> {
+    match value {
+        "s" | "safe" => Some(Rating::Safe),
+        "q" | "questionable" => Some(Rating::Questionable),
+        "e" | "explicit" => Some(Rating::Explicit),
+        _ => None,
+    }
+}
+
+/// Glob match where `*` matches any (possibly empty) run of characters.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one item
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        // no `*` at all
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Returns the first of `rules` that matches `post`, if any.
+pub fn matching_rule<'a>(post: &Post, rules: &'a [Rule]) -> Option<&'a Rule> {
+    rules.iter().find(|rule| rule.matches(post))
+}
+
+/// A [`Blacklist`] compiled for repeated matching.
+///
+/// Plain tags from every rule are interned into one table, and each tag points at the rules it
+/// could set off. Checking a post costs one hash lookup per post tag, and only the rules its tags
+/// touch are evaluated, along with the few no tag can rule out, like ones made only of metatags,
+/// wildcards or negations. Metatags and wildcards are kept as-is and checked directly against the
+/// post.
+#[derive(Debug, Default, Clone)]
+pub struct CompiledBlacklist {
+    tags: FxHashMap<String, usize>,
+    rules: Vec<CompiledRule>,
+    /// For each interned tag, the rules it counts towards. See [`CompiledRule::needed`].
+    by_tag: Vec<Vec<usize>>,
+    /// Rules that have to be checked against every post.
+    unindexed: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    source: String,
+    required: Vec<usize>,
+    excluded: Vec<usize>,
+    optional: Vec<usize>,
+    /// Tokens that aren't plain tags.
+    other: Vec<Token>,
+    /// How many of a post's tags have to count towards this rule before it's worth checking:
+    /// all of its required tags, or else any one of its optional ones.
+    needed: usize,
+}
+
+/// Interned tag IDs present on a post, sorted.
+struct TagSet(Vec<usize>);
+
+impl TagSet {
+    fn contains(&self, id: usize) -> bool {
+        self.0.binary_search(&id).is_ok()
+    }
+}
+
+impl CompiledBlacklist {
+    pub fn compile(blacklist: &Blacklist) -> Self {
+        let mut tags: FxHashMap<String, usize> = FxHashMap::default();
+        let mut rules = Vec::new();
+
+        for rule in blacklist.parse() {
+            let mut compiled = CompiledRule {
+                source: rule.source,
+                required: Vec::new(),
+                excluded: Vec::new(),
+                optional: Vec::new(),
+                other: Vec::new(),
+                needed: 0,
+            };
+
+            for token in rule.tokens {
+                let Condition::Tag(tag) = token.condition else {
+                    compiled.other.push(token);
+                    continue;
+                };
+
+                let next_id = tags.len();
+                let id = *tags.entry(tag).or_insert(next_id);
+                if token.optional {
+                    compiled.optional.push(id);
+                } else if token.negated {
+                    compiled.excluded.push(id);
+                } else {
+                    compiled.required.push(id);
+                }
+            }
+            compiled.required.sort_unstable();
+            compiled.required.dedup();
+            compiled.optional.sort_unstable();
+            compiled.optional.dedup();
+
+            rules.push(compiled);
+        }
+
+        let mut by_tag = vec![Vec::new(); tags.len()];
+        let mut unindexed = Vec::new();
+        for (index, rule) in rules.iter_mut().enumerate() {
+            // A rule with required tags can't match a post missing any of them. One without can
+            // only be ruled out by its optional tags, unless a metatag or wildcard is optional too.
+            let (keys, needed) = if !rule.required.is_empty() {
+                (&rule.required, rule.required.len())
+            } else if !rule.optional.is_empty() && !rule.other.iter().any(|token| token.optional) {
+                (&rule.optional, 1)
+            } else {
+                unindexed.push(index);
+                continue;
+            };
+            rule.needed = needed;
+            for &id in keys {
+                by_tag[id].push(index);
+            }
+        }
+
+        Self {
+            tags,
+            rules,
+            by_tag,
+            unindexed,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn is_blacklisted(&self, post: &Post) -> bool {
+        self.matching_rule(post).is_some()
+    }
+
+    /// Returns the source of the first rule that matches `post`, if any.
+    pub fn matching_rule(&self, post: &Post) -> Option<&str> {
+        if self.rules.is_empty() {
+            return None;
+        }
+
+        let mut present = post_tags(post)
+            .filter_map(|tag| self.tags.get(tag).copied())
+            .collect::<Vec<_>>();
+        present.sort_unstable();
+        present.dedup();
+
+        let mut counts: FxHashMap<usize, usize> = FxHashMap::default();
+        let mut candidates = self.unindexed.clone();
+        for &id in &present {
+            for &index in &self.by_tag[id] {
+                let count = counts.entry(index).or_default();
+                *count += 1;
+                if *count == self.rules[index].needed {
+                    candidates.push(index);
                 }
-            } else if !tags.iter().any(|t| *t == token) {
-                continue 'rule;
             }
         }
-        let post_id = post.id;
-        //debug!("Filtered post {post_id} for rule {rule}");
-        return true;
+        // Checked in the blacklist's order, so the first matching rule is the one reported.
+        candidates.sort_unstable();
+
+        let present = TagSet(present);
+        candidates
+            .into_iter()
+            .map(|index| &self.rules[index])
+            .find(|rule| rule.matches(post, &present))
+            .map(|rule| rule.source.as_str())
+    }
+}
+
+impl CompiledRule {
+    fn matches(&self, post: &Post, present: &TagSet) -> bool {
+        if !self.required.iter().all(|&id| present.contains(id))
+            || self.excluded.iter().any(|&id| present.contains(id))
+        {
+            return false;
+        }
+
+        let mut has_optional = !self.optional.is_empty();
+        let mut any_optional = self.optional.iter().any(|&id| present.contains(id));
+
+        for token in &self.other {
+            let hit = token.condition.matches(post);
+            if token.optional {
+                has_optional = true;
+                any_optional |= hit;
+            } else if hit == token.negated {
+                return false;
+            }
+        }
+
+        !has_optional || any_optional
     }
+}
+
+/// Checks `post` against `blacklist` without compiling it first.
+/// Prefer [`CompiledBlacklist`] when checking more than a handful of posts.
+pub fn is_blacklisted(post: &Post, blacklist: &Blacklist) -> bool {
+    blacklist
+        .rules
+        .iter()
+        .any(|rule| Rule::parse(rule).matches(post))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixture::post;
+    use super::*;
+    use serde_json::json;
 
-    return false;
+    /// Checks `rule` both directly and through [`CompiledBlacklist`], which must agree.
+    fn blacklisted(rule: &str, post: &Post) -> bool {
+        let blacklist = Blacklist {
+            rules: vec![rule.to_string()],
+        };
+        let direct = is_blacklisted(post, &blacklist);
+        let compiled = CompiledBlacklist::compile(&blacklist).is_blacklisted(post);
+        assert_eq!(direct, compiled, "compiled matcher disagrees on {rule:?}");
+        direct
+    }
+
+    #[test]
+    fn plain_tags_must_all_match() {
+        let p = post(json!({}));
+        assert!(blacklisted("solo", &p));
+        assert!(blacklisted("solo canine", &p));
+        assert!(!blacklisted("solo feline", &p));
+    }
+
+    #[test]
+    fn negated_tags() {
+        let p = post(json!({}));
+        assert!(blacklisted("canine -feline", &p));
+        assert!(!blacklisted("canine -smile", &p));
+        assert!(blacklisted("-feline", &p));
+    }
+
+    #[test]
+    fn optional_tags_need_one_match() {
+        let p = post(json!({}));
+        assert!(blacklisted("~feline ~canine", &p));
+        assert!(!blacklisted("~feline ~equine", &p));
+        assert!(blacklisted("solo ~feline ~canine", &p));
+        assert!(!blacklisted("duo ~feline ~canine", &p));
+    }
+
+    #[test]
+    fn wildcards() {
+        let p = post(json!({}));
+        assert!(blacklisted("domestic_*", &p));
+        assert!(blacklisted("*_dog", &p));
+        assert!(blacklisted("d*c_d*g", &p));
+        assert!(!blacklisted("*_cat", &p));
+        assert!(!blacklisted("-*_res", &p));
+    }
+
+    #[test]
+    fn wildcard_matcher() {
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("a*", "a"));
+        assert!(wildcard_match("*a*", "banana"));
+        assert!(wildcard_match("a*a", "aa"));
+        assert!(!wildcard_match("a*a", "a"));
+        assert!(!wildcard_match("abc", "abcd"));
+    }
+
+    #[test]
+    fn rating_metatag() {
+        let safe = post(json!({}));
+        let explicit = post(json!({ "rating": "e" }));
+        assert!(blacklisted("rating:e", &explicit));
+        assert!(blacklisted("rating:explicit", &explicit));
+        assert!(!blacklisted("rating:e", &safe));
+        assert!(blacklisted("canine -rating:s", &explicit));
+        assert!(!blacklisted("canine -rating:s", &safe));
+    }
+
+    #[test]
+    fn score_metatag() {
+        let negative = post(json!({ "score": { "total": -3 } }));
+        let positive = post(json!({}));
+        assert!(blacklisted("score:<0", &negative));
+        assert!(!blacklisted("score:<0", &positive));
+        assert!(blacklisted("score:>=10", &positive));
+        assert!(blacklisted("score:5..15", &positive));
+        assert!(blacklisted("score:..-1", &negative));
+        assert!(blacklisted("score:10", &positive));
+        assert!(!blacklisted("score:11..", &positive));
+    }
+
+    #[test]
+    fn id_and_favcount_metatags() {
+        let p = post(json!({}));
+        assert!(blacklisted("id:1000", &p));
+        assert!(blacklisted("id:<=1000", &p));
+        assert!(!blacklisted("id:>1000", &p));
+        assert!(blacklisted("favcount:>20", &p));
+        assert!(!blacklisted("favcount:<20", &p));
+    }
+
+    #[test]
+    fn type_metatag() {
+        let webm = post(json!({ "file": { "ext": "webm", "url": null } }));
+        let png = post(json!({}));
+        assert!(blacklisted("type:webm", &webm));
+        assert!(!blacklisted("type:webm", &png));
+        assert!(blacklisted("~type:webm ~type:png", &png));
+    }
+
+    #[test]
+    fn user_metatag() {
+        let p = post(json!({}));
+        assert!(blacklisted("user:uploader", &p));
+        assert!(blacklisted("user:!42", &p));
+        assert!(!blacklisted("user:!43", &p));
+        assert!(!blacklisted("user:someone_else", &p));
+
+        let anonymous = post(json!({ "uploader_id": null, "uploader_name": null }));
+        assert!(!blacklisted("user:uploader", &anonymous));
+    }
+
+    #[test]
+    fn unknown_or_invalid_metatags_are_tags() {
+        let p = post(json!({ "tags": {
+            "general": ["score:high"], "artist": [], "copyright": [], "character": [],
+            "species": [], "invalid": [], "meta": [], "lore": []
+        } }));
+        assert!(blacklisted("score:high", &p));
+        assert!(!blacklisted("pool:123", &p));
+    }
+
+    #[test]
+    fn rules_are_case_insensitive() {
+        let p = post(json!({}));
+        assert!(blacklisted("Domestic_Dog", &p));
+        assert!(blacklisted("RATING:S", &p));
+    }
+
+    #[test]
+    fn empty_rules_never_match() {
+        let p = post(json!({}));
+        assert!(!blacklisted("", &p));
+        assert!(!blacklisted("   ", &p));
+        assert!(!blacklisted("- ~", &p));
+    }
+
+    #[test]
+    fn first_matching_rule_is_reported() {
+        let p = post(json!({ "rating": "e" }));
+        let blacklist = Blacklist {
+            rules: vec![
+                "feline".to_string(),
+                "rating:e solo".to_string(),
+                "canine".to_string(),
+            ],
+        };
+        let rules = blacklist.parse();
+        let rule = matching_rule(&p, &rules).expect("should match");
+        assert_eq!(rule.source, "rating:e solo");
+    }
+
+    #[test]
+    fn compiled_reports_first_matching_rule() {
+        let p = post(json!({}));
+        let compiled = CompiledBlacklist::compile(&Blacklist {
+            rules: vec![
+                "feline".to_string(),
+                "".to_string(),
+                "canine ~solo ~duo -rating:e".to_string(),
+                "smile".to_string(),
+            ],
+        });
+        assert_eq!(
+            compiled.matching_rule(&p),
+            Some("canine ~solo ~duo -rating:e")
+        );
+    }
+
+    #[test]
+    fn compiled_handles_many_interned_tags() {
+        // only one of which the post's tags touch
+        let rules = (0..200).map(|i| format!("tag_{i}")).collect::<Vec<_>>();
+        let compiled = CompiledBlacklist::compile(&Blacklist { rules });
+        let p = post(json!({ "tags": {
+            "general": ["tag_150"], "artist": [], "copyright": [], "character": [],
+            "species": [], "invalid": [], "meta": [], "lore": []
+        } }));
+        assert_eq!(compiled.matching_rule(&p), Some("tag_150"));
+        assert!(!compiled.is_blacklisted(&post(json!({}))));
+    }
+
+    #[test]
+    fn compiled_checks_unindexed_rules_in_order() {
+        let p = post(json!({}));
+        let compile = |rules: &[&str]| {
+            CompiledBlacklist::compile(&Blacklist {
+                rules: rules.iter().map(|r| r.to_string()).collect(),
+            })
+        };
+
+        // Neither negations nor metatags are tied to a tag the post has.
+        let compiled = compile(&["solo smile", "-feline", "rating:s"]);
+        assert_eq!(compiled.matching_rule(&p), Some("solo smile"));
+        let compiled = compile(&["-feline", "solo smile"]);
+        assert_eq!(compiled.matching_rule(&p), Some("-feline"));
+
+        // Optional tags alone set a rule off, unless a metatag can stand in for them.
+        let compiled = compile(&["~feline ~equine", "~feline ~smile"]);
+        assert_eq!(compiled.matching_rule(&p), Some("~feline ~smile"));
+        let compiled = compile(&["~feline ~rating:s", "solo"]);
+        assert_eq!(compiled.matching_rule(&p), Some("~feline ~rating:s"));
+
+        // Repeated tags still count once.
+        let compiled = compile(&["solo solo canine"]);
+        assert_eq!(compiled.matching_rule(&p), Some("solo solo canine"));
+    }
+
+    #[test]
+    fn diff_and_merge_with_account_blacklist() {
+        let local = Blacklist {
+            rules: vec![
+                "gore".to_string(),
+                "rating:e  score:<0".to_string(),
+                "".to_string(),
+                "feline".to_string(),
+            ],
+        };
+        let remote = Blacklist {
+            rules: vec![
+                "Rating:E score:<0".to_string(),
+                "scat".to_string(),
+                "gore".to_string(),
+            ],
+        };
+
+        let diff = local.diff(&remote);
+        assert_eq!(diff.added, vec!["scat".to_string()]);
+        assert_eq!(diff.removed, vec!["feline".to_string()]);
+        assert_eq!(diff.unchanged, 2);
+        assert!(!diff.is_empty());
+        assert!(local.diff(&local).is_empty());
+
+        let merged = local.merged(&remote);
+        assert_eq!(
+            merged.rules,
+            vec!["gore", "rating:e  score:<0", "feline", "scat"]
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use chrono::Utc;
 use core::fmt;
 use directories::ProjectDirs;
 use iced::Theme;
@@ -10,7 +11,9 @@ use thiserror::Error;
 use tracing::{info, trace};
 
 use super::blacklist::Blacklist;
-use super::followed::FollowedTag;
+use super::followed::{FollowedPool, FollowedTag};
+use super::keymap::Keymap;
+use super::media::DEFAULT_MEMORY_BUDGET_MB;
 
 const fn _default_true() -> bool {
     true
@@ -25,6 +28,16 @@ const fn default_tile_width() -> usize {
     180
 }
 
+/// Schema of the config files this version of msg writes.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Upgrades a config from each version to the next, starting at 0.
+const MIGRATIONS: [fn(&mut toml::Table); CONFIG_VERSION as usize] = [
+    // Configs from before versioning. Every field still means the same, so only the version is
+    // new.
+    |_| {},
+];
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("couldn't get config path")]
@@ -38,6 +51,17 @@ pub enum ConfigError {
 
     #[error("TOML serialize error")]
     TomlSerError(#[from] toml::ser::Error),
+
+    #[error("unknown config version {0}, maybe from a newer msg")]
+    UnknownVersion(String),
+}
+
+impl ConfigError {
+    /// Whether the config was read but couldn't be made sense of, which is when it's set aside.
+    /// One from a newer msg is left for that version.
+    pub fn is_undecodable(&self) -> bool {
+        matches!(self, ConfigError::TomlDeError(_))
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug, Eq, PartialEq)]
@@ -66,13 +90,70 @@ impl ToString for MsgTheme {
     }
 }
 
-#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+/// Which e621-compatible site to talk to.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiHost {
+    #[default]
+    E621,
+    /// e621's safe-for-work mirror.
+    E926,
+    /// Any other e621ng instance, by base URL.
+    Custom(String),
+}
+
+impl ApiHost {
+    /// Base URL with no trailing slash, e.g. `https://e621.net`.
+    pub fn base_url(&self) -> &str {
+        match self {
+            ApiHost::E621 => "https://e621.net",
+            ApiHost::E926 => "https://e926.net",
+            ApiHost::Custom(url) => url.trim().trim_end_matches('/'),
+        }
+    }
+}
+
+impl fmt::Display for ApiHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiHost::E621 => write!(f, "e621"),
+            ApiHost::E926 => write!(f, "e926"),
+            ApiHost::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(default)]
 pub struct Config {
+    /// Schema the config was written with. See [`CONFIG_VERSION`].
+    pub version: u32,
+    pub host: ApiHost,
     pub auth: Option<Auth>,
     pub blacklist: Blacklist,
     pub followed_tags: Vec<FollowedTag>,
+    pub followed_pools: Vec<FollowedPool>,
     pub view: ViewConfig,
+    pub retry: RetryConfig,
+    pub cache: CacheConfig,
+    pub keymap: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: CONFIG_VERSION,
+            host: ApiHost::default(),
+            auth: None,
+            blacklist: Blacklist::default(),
+            followed_tags: Vec::new(),
+            followed_pools: Vec::new(),
+            view: ViewConfig::default(),
+            retry: RetryConfig::default(),
+            cache: CacheConfig::default(),
+            keymap: Keymap::default(),
+        }
+    }
 }
 
 #[derive(Deserialize, Default, Serialize, Clone, PartialEq)]
@@ -107,6 +188,51 @@ impl Default for ViewConfig {
     }
 }
 
+/// How failed requests are retried. See [`send_with_retry`](super::http::send_with_retry).
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Retries after the first attempt; 0 disables retrying.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each one after.
+    pub base_delay_ms: u64,
+    /// Upper bound on any single delay. A `Retry-After` longer than this isn't waited out.
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 10_000,
+        }
+    }
+}
+
+/// How much downloaded media is kept in memory. See [`MediaCache`](super::media::MediaCache).
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Thumbnails, samples and images held in memory, in MiB. Past this, the least recently
+    /// shown are read from disk again when needed.
+    pub memory_budget_mb: usize,
+}
+
+impl CacheConfig {
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget_mb * 1024 * 1024
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            memory_budget_mb: DEFAULT_MEMORY_BUDGET_MB,
+        }
+    }
+}
+
 impl fmt::Debug for Auth {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Auth")
@@ -122,6 +248,15 @@ pub fn config_path() -> Result<PathBuf, ConfigError> {
         .ok_or(ConfigError::LoadPathError)
 }
 
+/// Moves an unreadable config at `path` out of the way, so saving a fresh one doesn't overwrite
+/// it. Returns where it went.
+pub fn set_aside(path: &Path) -> Result<PathBuf, ConfigError> {
+    let stamp = Utc::now().format("%Y%m%d%H%M%S");
+    let aside = path.with_extension(format!("unreadable-{stamp}.toml"));
+    fs::rename(path, &aside)?;
+    Ok(aside)
+}
+
 impl Config {
     pub fn new() -> Config {
         Config::load().unwrap_or_default()
@@ -144,10 +279,39 @@ impl Config {
         Ok(())
     }
 
+    /// Reads the config at `path`. One from an older version of msg is upgraded and saved again,
+    /// after copying the original next to it as e.g. `config.v0.toml`.
     pub fn load_from(path: &Path) -> Result<Config, ConfigError> {
         let raw = fs::read_to_string(path)?;
-        let config = toml::from_str::<Config>(&raw)?;
+        let mut table = toml::from_str::<toml::Table>(&raw)?;
+
+        let version = match table.get("version") {
+            None => 0,
+            Some(version) => version
+                .as_integer()
+                .and_then(|version| u32::try_from(version).ok())
+                .filter(|&version| version <= CONFIG_VERSION)
+                .ok_or_else(|| ConfigError::UnknownVersion(version.to_string()))?,
+        };
+        if version == CONFIG_VERSION {
+            return Ok(toml::Value::Table(table).try_into()?);
+        }
 
+        let backup = path.with_extension(format!("v{version}.toml"));
+        info!(
+            "Upgrading config from version {version} to {CONFIG_VERSION}, backed up to {backup:?}"
+        );
+        // An earlier backup is older still, so it's the one to keep.
+        if !backup.exists() {
+            fs::copy(path, &backup)?;
+        }
+        for migrate in &MIGRATIONS[version as usize..] {
+            migrate(&mut table);
+        }
+        table.insert("version".to_owned(), CONFIG_VERSION.into());
+
+        let config: Config = toml::Value::Table(table).try_into()?;
+        config.save_to(path)?;
         Ok(config)
     }
 
@@ -167,6 +331,8 @@ mod tests {
     #[test]
     fn toml_sanity_check() {
         let config = Config {
+            version: CONFIG_VERSION,
+            host: ApiHost::Custom("http://localhost:3000".to_owned()),
             auth: Some(Auth {
                 username: "dingus".to_owned(),
                 api_key: "bingus".to_owned(),
@@ -184,9 +350,25 @@ mod tests {
                     last_seen: Some(1),
                 },
             ],
+            followed_pools: vec![FollowedPool {
+                id: 7,
+                name: "a_comic".to_owned(),
+                last_seen: Some(20),
+            }],
             view: ViewConfig {
                 ..Default::default()
             },
+            retry: RetryConfig {
+                max_retries: 5,
+                ..Default::default()
+            },
+            cache: CacheConfig {
+                memory_budget_mb: 64,
+            },
+            keymap: Keymap {
+                favorite: vec!["ctrl+d".parse().unwrap()],
+                ..Default::default()
+            },
         };
 
         let temp_dir = TempDir::new().expect("Couldn't make TempDir");
@@ -197,4 +379,14 @@ mod tests {
 
         assert_eq!(config, new_config);
     }
+
+    #[test]
+    fn custom_hosts_are_normalized() {
+        assert_eq!(ApiHost::default().base_url(), "https://e621.net");
+        assert_eq!(ApiHost::E926.base_url(), "https://e926.net");
+        assert_eq!(
+            ApiHost::Custom(" http://localhost:3000/ ".to_owned()).base_url(),
+            "http://localhost:3000"
+        );
+    }
 }
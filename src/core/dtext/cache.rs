@@ -0,0 +1,152 @@
+//! Parsed DText for what's on screen, so it isn't parsed again every time the view is drawn.
+
+use rustc_hash::FxHashMap;
+
+use super::model::DTextBlock;
+use super::parse_dtext;
+
+/// Where a piece of DText comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DTextSource {
+    /// A post's description, by post ID.
+    Description(u32),
+    /// A comment's body, by comment ID.
+    Comment(u32),
+    /// A pool's description, by pool ID.
+    Pool(u32),
+    /// The wiki page for a tag.
+    Wiki(String),
+    /// The comment being written, while it's previewed.
+    Draft,
+}
+
+#[derive(Debug)]
+struct Document {
+    /// What `blocks` were parsed from, to tell when it changes.
+    text: String,
+    blocks: Vec<DTextBlock>,
+}
+
+#[derive(Debug, Default)]
+pub struct DTextCache {
+    documents: FxHashMap<DTextSource, Document>,
+}
+
+impl DTextCache {
+    /// Parses `text` as the DText for `source`, unless that's what it already holds. Sections
+    /// opened or closed in it stay that way until the text changes.
+    pub fn insert(&mut self, source: DTextSource, text: &str) {
+        if self
+            .documents
+            .get(&source)
+            .is_some_and(|document| document.text == text)
+        {
+            return;
+        }
+        let document = Document {
+            text: text.to_string(),
+            blocks: parse_dtext(text),
+        };
+        self.documents.insert(source, document);
+    }
+
+    /// The parsed DText for `source`, or nothing if it wasn't inserted.
+    pub fn get(&self, source: &DTextSource) -> &[DTextBlock] {
+        self.documents
+            .get(source)
+            .map_or(&[], |document| &document.blocks)
+    }
+
+    /// Drops everything `keep` returns `false` for.
+    pub fn retain(&mut self, mut keep: impl FnMut(&DTextSource) -> bool) {
+        self.documents.retain(|source, _| keep(source));
+    }
+
+    /// Opens or closes the `index`th `[section]` in `source`, counting nested ones right after
+    /// the section they're in.
+    pub fn toggle_section(&mut self, source: &DTextSource, mut index: usize) {
+        if let Some(document) = self.documents.get_mut(source) {
+            toggle_section(&mut document.blocks, &mut index);
+        }
+    }
+}
+
+/// Counts `index` down through the sections in `blocks`, toggling the one it reaches zero on.
+fn toggle_section(blocks: &mut [DTextBlock], index: &mut usize) -> bool {
+    blocks.iter_mut().any(|block| match block {
+        DTextBlock::Section {
+            expanded, blocks, ..
+        } => {
+            if *index == 0 {
+                *expanded = !*expanded;
+                return true;
+            }
+            *index -= 1;
+            toggle_section(blocks, index)
+        }
+        DTextBlock::Quote(blocks) | DTextBlock::Spoiler(blocks) => toggle_section(blocks, index),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section_states(blocks: &[DTextBlock]) -> Vec<bool> {
+        let mut out = Vec::new();
+        for block in blocks {
+            match block {
+                DTextBlock::Section {
+                    expanded, blocks, ..
+                } => {
+                    out.push(*expanded);
+                    out.extend(section_states(blocks));
+                }
+                DTextBlock::Quote(blocks) | DTextBlock::Spoiler(blocks) => {
+                    out.extend(section_states(blocks))
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn text_is_only_parsed_again_when_it_changes() {
+        let mut cache = DTextCache::default();
+        let source = DTextSource::Comment(1);
+        let text = "[section=a]x[/section]";
+
+        cache.insert(source.clone(), text);
+        cache.toggle_section(&source, 0);
+        cache.insert(source.clone(), text);
+        assert_eq!(section_states(cache.get(&source)), [true]);
+
+        cache.insert(source.clone(), "[section=b]x[/section]");
+        assert_eq!(section_states(cache.get(&source)), [false]);
+
+        cache.retain(|kept| *kept != source);
+        assert!(cache.get(&source).is_empty());
+    }
+
+    #[test]
+    fn sections_are_counted_in_document_order() {
+        let mut cache = DTextCache::default();
+        let source = DTextSource::Wiki("tag".into());
+        cache.insert(
+            source.clone(),
+            "[section=a][section=b]x[/section][/section]\n[quote][section,expanded=c]y[/section][/quote]",
+        );
+        assert_eq!(section_states(cache.get(&source)), [false, false, true]);
+
+        cache.toggle_section(&source, 1);
+        cache.toggle_section(&source, 2);
+        assert_eq!(section_states(cache.get(&source)), [false, true, false]);
+
+        // Out of range, or for something that isn't cached.
+        cache.toggle_section(&source, 3);
+        cache.toggle_section(&DTextSource::Draft, 0);
+        assert_eq!(section_states(cache.get(&source)), [false, true, false]);
+    }
+}
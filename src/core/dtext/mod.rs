@@ -1,2 +1,54 @@
+//! DText, e621's markup language for descriptions, comments and wiki pages.
+//! See the [DText help page].
+//!
+//! [DText help page]: https://e621.net/help/dtext
+
+use url::Url;
+
+pub mod cache;
 pub mod model;
 pub mod parser;
+
+pub use parser::parse_dtext;
+
+/// Where a DText link goes, as an `http` or `https` URL. Paths on the site, like `/posts/1`, are
+/// resolved against `base_url`. Anything else, like `file://` URLs or local paths, isn't
+/// followed.
+pub fn link_target(base_url: &str, href: &str) -> Option<Url> {
+    let href = href.trim();
+    let url = match href.starts_with('/') {
+        true => Url::parse(&format!("{base_url}{href}")),
+        false => Url::parse(href),
+    }
+    .ok()?;
+    matches!(url.scheme(), "http" | "https").then_some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "https://e621.net";
+
+    #[test]
+    fn only_web_links_are_followed() {
+        assert_eq!(
+            link_target(BASE, "/wiki_pages/help:dtext").map(String::from),
+            Some("https://e621.net/wiki_pages/help:dtext".to_string())
+        );
+        assert_eq!(
+            link_target(BASE, "http://example.com/a b").map(String::from),
+            Some("http://example.com/a%20b".to_string())
+        );
+
+        for href in [
+            "file:///etc/passwd",
+            "C:\\Windows\\System32\\calc.exe",
+            "javascript:alert(1)",
+            "smb://host/share",
+            "not a url",
+        ] {
+            assert_eq!(link_target(BASE, href), None, "{href}");
+        }
+    }
+}
@@ -1,19 +1,81 @@
-#[derive(Debug)]
+/// A block of DText, laid out top to bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DTextBlock {
+    /// Lines of text, separated from other blocks by a blank line.
+    Paragraph(Vec<DTextSpan>),
+    /// `h1.` to `h6.`
+    Header { level: u8, spans: Vec<DTextSpan> },
+    /// `[quote]`
+    Quote(Vec<DTextBlock>),
+    /// `[spoiler]` spanning several lines.
+    Spoiler(Vec<DTextBlock>),
+    /// `[code]`, kept exactly as written.
+    Code(String),
+    /// `[section=title]`, or `[section,expanded=title]`.
+    Section {
+        title: String,
+        expanded: bool,
+        blocks: Vec<DTextBlock>,
+    },
+    /// Consecutive `*` lines.
+    List(Vec<ListItem>),
+    /// `[table]`
+    Table(Vec<TableRow>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    /// Number of `*`s, starting from 1.
+    pub depth: usize,
+    pub spans: Vec<DTextSpan>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRow {
+    pub cells: Vec<TableCell>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableCell {
+    /// `[th]` rather than `[td]`.
+    pub header: bool,
+    pub spans: Vec<DTextSpan>,
+}
+
+/// Inline DText.
+#[derive(Debug, Clone, PartialEq)]
 pub enum DTextSpan {
     Text(String),
     Bold(Vec<DTextSpan>),
     Italics(Vec<DTextSpan>),
-    //Strikeout(Vec<DTextSpan>),
-    //Underline(Vec<DTextSpan>),
-    //Superscript(Vec<DTextSpan>),
-    //Subscript(Vec<DTextSpan>),
-    //Spoiler(Vec<DTextSpan>),
-    //InlineCode(String),
-    //ColoredText { color: String, text: Vec<DTextSpan> },
+    Strikeout(Vec<DTextSpan>),
+    Underline(Vec<DTextSpan>),
+    Superscript(Vec<DTextSpan>),
+    Subscript(Vec<DTextSpan>),
+    Spoiler(Vec<DTextSpan>),
+    /// `` `code` ``
+    InlineCode(String),
+    /// `[color=...]`, with a color name, hex code or tag category.
+    Colored {
+        color: String,
+        children: Vec<DTextSpan>,
+    },
 
-    //Link { href: String, label: Vec<DTextSpan> },
+    /// `"label":url`, or a bare URL where `label` is the URL.
+    Link {
+        href: String,
+        label: String,
+    },
+    /// `[[tag]]` or `[[tag|label]]`
+    WikiLink {
+        tag: String,
+        label: Option<String>,
+    },
+    /// `{{tags}}`
+    TagSearch(String),
     PostLink(u32),
-    //LineBreak,
-
-    //Quote(Vec<DTextSpan>),
+    PoolLink(u32),
+    CommentLink(u32),
+    UserLink(u32),
+    LineBreak,
 }
@@ -1,50 +1,1032 @@
+//! Parses DText in two passes: blocks first, line by line, then the inline markup inside them.
+//!
+//! Parsing never fails. Anything that isn't valid markup, like an unclosed `[b]` or a table with
+//! stray text in it, is kept as text.
+
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while1},
-    character::complete::{digit1, not_line_ending},
-    combinator::{map, recognize},
-    multi::many0,
+    bytes::complete::{tag, tag_no_case, take_till1, take_while1},
+    character::complete::{alpha1, char, digit1, one_of, space0},
+    combinator::{map, map_res, opt, verify},
+    error::{Error, ErrorKind},
     sequence::{delimited, preceded},
     IResult, Parser,
 };
 
-use crate::core::dtext::model::DTextSpan;
+use super::model::{DTextBlock, DTextSpan, ListItem, TableCell, TableRow};
+
+/// Tags nested deeper than this are kept as text, so a post can't nest them deep enough to
+/// overflow the stack while parsing or drawing it.
+const MAX_DEPTH: usize = 16;
+
+/// Parses DText into blocks. This never fails.
+pub fn parse_dtext(input: &str) -> Vec<DTextBlock> {
+    let text = input.replace("\r\n", "\n");
+    let doc = Document {
+        text: &text,
+        lower: text.to_ascii_lowercase(),
+    };
+    parse_blocks(&doc, &text, 0)
+}
+
+// --- Blocks ---
+
+/// The whole text being parsed, which every block's input is a slice of.
+struct Document<'a> {
+    text: &'a str,
+    /// `text` lowercased once up front, for finding closing tags in any case. ASCII lowercasing
+    /// keeps byte offsets the same.
+    lower: String,
+}
+
+impl Document<'_> {
+    /// The lowercased copy of `part`, a slice of the document.
+    fn lower(&self, part: &str) -> &str {
+        let start = part.as_ptr() as usize - self.text.as_ptr() as usize;
+        &self.lower[start..start + part.len()]
+    }
+}
+
+/// Parses blocks nested `depth` block tags deep.
+fn parse_blocks(doc: &Document, mut input: &str, depth: usize) -> Vec<DTextBlock> {
+    let mut blocks = Vec::new();
+    while !input.is_empty() {
+        let (line, rest) = split_line(input);
+        if line.trim().is_empty() {
+            input = rest;
+            continue;
+        }
+
+        let (block, rest) = (depth < MAX_DEPTH)
+            .then(|| block_tag(doc, input, depth))
+            .flatten()
+            .or_else(|| header(input))
+            .or_else(|| list(input))
+            .unwrap_or_else(|| paragraph(input));
+        blocks.push(block);
+        input = rest;
+    }
+    blocks
+}
+
+/// Splits off the first line, without its newline.
+fn split_line(input: &str) -> (&str, &str) {
+    input.split_once('\n').unwrap_or((input, ""))
+}
+
+/// Lines up to the next blank line or block, with single newlines kept as line breaks.
+fn paragraph(input: &str) -> (DTextBlock, &str) {
+    let mut len;
+    let mut rest = input;
+    loop {
+        let (line, after) = split_line(rest);
+        len = input.len() - rest.len() + line.len();
+        rest = after;
+
+        let (next, _) = split_line(rest);
+        if rest.is_empty() || next.trim().is_empty() || starts_block(next) {
+            break;
+        }
+    }
+    (DTextBlock::Paragraph(parse_inline(&input[..len])), rest)
+}
+
+/// Whether `line` looks like the start of a block, which ends the paragraph before it.
+fn starts_block(line: &str) -> bool {
+    if header_start(line).is_some() || list_item(line).is_some() {
+        return true;
+    }
+    match opening_tag(line) {
+        Ok((after, (name, _))) => match name.to_ascii_lowercase().as_str() {
+            "quote" | "code" | "section" | "section,expanded" | "table" => true,
+            "spoiler" => after.trim().is_empty(),
+            _ => false,
+        },
+        Err(_) => false,
+    }
+}
+
+/// Parses an opening tag like `[quote]`, `[color=red]` or `[section,expanded=Title]`.
+fn opening_tag(input: &str) -> IResult<&str, (&str, Option<&str>)> {
+    delimited(
+        char('['),
+        (
+            take_while1(|c: char| c.is_ascii_alphanumeric() || c == ','),
+            opt(preceded(char('='), take_till1(|c| c == ']' || c == '\n'))),
+        ),
+        char(']'),
+    )
+    .parse(input)
+}
+
+/// Parses `[quote]`, `[code]`, `[section]`, `[table]` and multi-line `[spoiler]` blocks, inside
+/// `depth` others.
+fn block_tag<'a>(doc: &Document, input: &'a str, depth: usize) -> Option<(DTextBlock, &'a str)> {
+    let (after, (name, arg)) = opening_tag(input).ok()?;
+    let name = name.to_ascii_lowercase();
+
+    match (name.as_str(), arg) {
+        ("quote", None) => {
+            let (inner, rest) = find_close(doc, after, "quote", true)?;
+            Some((DTextBlock::Quote(parse_blocks(doc, inner, depth + 1)), rest))
+        }
+        ("code", None) => {
+            let (inner, rest) = find_close(doc, after, "code", false)?;
+            Some((DTextBlock::Code(inner.trim_matches('\n').to_string()), rest))
+        }
+        ("spoiler", None) => {
+            let (inner, rest) = find_close(doc, after, "spoiler", true)?;
+            inner.contains('\n').then(|| {
+                (
+                    DTextBlock::Spoiler(parse_blocks(doc, inner, depth + 1)),
+                    rest,
+                )
+            })
+        }
+        ("section" | "section,expanded", title) => {
+            let (inner, rest) = find_close(doc, after, "section", true)?;
+            let section = DTextBlock::Section {
+                title: title.unwrap_or_default().trim().to_string(),
+                expanded: name.ends_with("expanded"),
+                blocks: parse_blocks(doc, inner, depth + 1),
+            };
+            Some((section, rest))
+        }
+        ("table", None) => {
+            let (inner, rest) = find_close(doc, after, "table", false)?;
+            Some((DTextBlock::Table(parse_table(doc, inner)?), rest))
+        }
+        _ => None,
+    }
+}
+
+/// Finds the `[/name]` closing a tag that was just opened, skipping over nested tags with the
+/// same name if it `nests`. Returns what's between them and what comes after.
+fn find_close<'a>(
+    doc: &Document,
+    input: &'a str,
+    name: &str,
+    nests: bool,
+) -> Option<(&'a str, &'a str)> {
+    let lower = doc.lower(input);
+    let open = format!("[{name}");
+    let close = format!("[/{name}]");
+
+    let mut depth = 0;
+    let mut pos = 0;
+    while let Some(found) = lower[pos..].find('[') {
+        let at = pos + found;
+        let here = &lower[at..];
+        if here.starts_with(&close) {
+            if depth == 0 {
+                return Some((&input[..at], &input[at + close.len()..]));
+            }
+            depth -= 1;
+            pos = at + close.len();
+        } else if nests
+            && here.starts_with(&open)
+            && here[open.len()..].starts_with([']', '=', ','])
+        {
+            depth += 1;
+            pos = at + open.len();
+        } else {
+            pos = at + 1;
+        }
+    }
+    None
+}
+
+/// Parses the start of `h1. Title` through `h6.`, returning the level and title.
+fn header_start(line: &str) -> Option<(u8, &str)> {
+    let parsed: IResult<&str, _> =
+        (tag_no_case("h"), one_of("123456"), char('.'), space0).parse(line);
+    let (title, (_, level, _, _)) = parsed.ok()?;
+    Some((level as u8 - b'0', title))
+}
+
+fn header(input: &str) -> Option<(DTextBlock, &str)> {
+    let (line, rest) = split_line(input);
+    let (level, title) = header_start(line)?;
+    let spans = parse_inline(title);
+    Some((DTextBlock::Header { level, spans }, rest))
+}
+
+/// Parses a `* item` line, returning its depth, text and the lines after it.
+fn list_item(input: &str) -> Option<(usize, &str, &str)> {
+    let (line, rest) = split_line(input);
+    let depth = line.len() - line.trim_start_matches('*').len();
+    let text = line[depth..].strip_prefix(' ')?;
+    (depth > 0).then_some((depth, text, rest))
+}
+
+fn list(input: &str) -> Option<(DTextBlock, &str)> {
+    let mut items = Vec::new();
+    let mut rest = input;
+    while let Some((depth, text, after)) = list_item(rest) {
+        items.push(ListItem {
+            depth,
+            spans: parse_inline(text),
+        });
+        rest = after;
+    }
+    (!items.is_empty()).then_some((DTextBlock::List(items), rest))
+}
+
+/// Strips `tag` from the start of `input`, ignoring case.
+fn strip_tag<'a>(input: &'a str, tag: &str) -> Option<&'a str> {
+    input
+        .get(..tag.len())
+        .filter(|start| start.eq_ignore_ascii_case(tag))
+        .map(|_| &input[tag.len()..])
+}
+
+/// Parses the rows inside `[table]`. Anything but whitespace outside of cells makes it invalid.
+fn parse_table(doc: &Document, input: &str) -> Option<Vec<TableRow>> {
+    let mut rows = Vec::new();
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let wrapper = ["[thead]", "[/thead]", "[tbody]", "[/tbody]"]
+            .iter()
+            .find_map(|wrapper| strip_tag(rest, wrapper));
+        if let Some(after) = wrapper {
+            rest = after.trim_start();
+            continue;
+        }
+
+        let (inner, after) = find_close(doc, strip_tag(rest, "[tr]")?, "tr", false)?;
+        rows.push(TableRow {
+            cells: parse_cells(doc, inner)?,
+        });
+        rest = after.trim_start();
+    }
+    Some(rows)
+}
+
+fn parse_cells(doc: &Document, input: &str) -> Option<Vec<TableCell>> {
+    let mut cells = Vec::new();
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let (header, name, after) = match strip_tag(rest, "[th]") {
+            Some(after) => (true, "th", after),
+            None => (false, "td", strip_tag(rest, "[td]")?),
+        };
+        let (inner, after) = find_close(doc, after, name, false)?;
+        cells.push(TableCell {
+            header,
+            spans: parse_inline(inner.trim()),
+        });
+        rest = after.trim_start();
+    }
+    Some(cells)
+}
+
+// --- Inline ---
+
+/// Inline tags that wrap other spans.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Bold,
+    Italics,
+    Strikeout,
+    Underline,
+    Superscript,
+    Subscript,
+    Spoiler,
+    Color,
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Span(DTextSpan),
+    Open {
+        format: Format,
+        color: Option<&'a str>,
+        raw: &'a str,
+    },
+    Close {
+        format: Format,
+        raw: &'a str,
+    },
+}
+
+/// An opened inline tag, waiting for its closing tag.
+struct Frame<'a> {
+    format: Format,
+    color: Option<&'a str>,
+    raw: &'a str,
+    children: Vec<DTextSpan>,
+}
+
+fn parse_inline(input: &str) -> Vec<DTextSpan> {
+    build_spans(tokenize(input))
+}
+
+fn tokenize(mut input: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    while let Some(first) = input.chars().next() {
+        if let Ok((rest, token)) = inline_token(input) {
+            tokens.push(token);
+            input = rest;
+            continue;
+        }
 
-/// Entry point for DText parser
-pub fn parse_dtext(input: &str) -> IResult<&str, Vec<DTextSpan>> {
-    many0(parse_span).parse(input)
+        // Plain text runs up to the next place markup could start, taking at least one char.
+        let end = markup_start(input, first.len_utf8());
+        tokens.push(Token::Text(&input[..end]));
+        input = &input[end..];
+    }
+    tokens
 }
 
-/// Parses one DText span at a time.
-fn parse_span(input: &str) -> IResult<&str, DTextSpan> {
+/// The first place at or after `from` that could start some markup.
+fn markup_start(input: &str, from: usize) -> usize {
+    let mut prev = input[..from].chars().next_back();
+    for (i, c) in input[from..].char_indices() {
+        let word_start = !prev.is_some_and(char::is_alphanumeric);
+        if matches!(c, '[' | '{' | '"' | '`' | '\n')
+            || (word_start && matches!(c.to_ascii_lowercase(), 'p' | 'c' | 'u' | 'h'))
+        {
+            return from + i;
+        }
+        prev = Some(c);
+    }
+    input.len()
+}
+
+fn inline_token(input: &str) -> IResult<&str, Token<'_>> {
     alt((
-        parse_bold,
-        parse_italics,
-        parse_post_reference,
-        parse_plain_text,
+        map(
+            alt((
+                line_break,
+                inline_code,
+                wiki_link,
+                tag_search,
+                quoted_link,
+                bare_url,
+                reference,
+            )),
+            Token::Span,
+        ),
+        format_tag,
     ))
     .parse(input)
 }
 
-/// Parses `[b]bold[/b]`
-fn parse_bold(input: &str) -> IResult<&str, DTextSpan> {
-    let (rest, content) = delimited(tag("[b]"), parse_dtext, tag("[/b]")).parse(input)?;
-    Ok((rest, DTextSpan::Bold(content)))
+fn line_break(input: &str) -> IResult<&str, DTextSpan> {
+    map(char('\n'), |_| DTextSpan::LineBreak).parse(input)
+}
+
+fn not_blank(text: &str) -> bool {
+    !text.trim().is_empty()
+}
+
+/// Parses `` `code` ``
+fn inline_code(input: &str) -> IResult<&str, DTextSpan> {
+    let (rest, code) =
+        delimited(char('`'), take_till1(|c| c == '`' || c == '\n'), char('`')).parse(input)?;
+    Ok((rest, DTextSpan::InlineCode(code.to_string())))
+}
+
+/// Parses `[[tag]]` and `[[tag|label]]`
+fn wiki_link(input: &str) -> IResult<&str, DTextSpan> {
+    let (rest, (name, label)) = delimited(
+        tag("[["),
+        (
+            verify(take_till1(|c| matches!(c, '|' | ']' | '\n')), not_blank),
+            opt(preceded(char('|'), take_till1(|c| c == ']' || c == '\n'))),
+        ),
+        tag("]]"),
+    )
+    .parse(input)?;
+
+    Ok((
+        rest,
+        DTextSpan::WikiLink {
+            tag: name.trim().to_string(),
+            label: label.map(|label| label.trim().to_string()),
+        },
+    ))
+}
+
+/// Parses `{{tag search}}`
+fn tag_search(input: &str) -> IResult<&str, DTextSpan> {
+    let (rest, query) = delimited(
+        tag("{{"),
+        verify(take_till1(|c| c == '}' || c == '\n'), not_blank),
+        tag("}}"),
+    )
+    .parse(input)?;
+    Ok((rest, DTextSpan::TagSearch(query.trim().to_string())))
+}
+
+/// Parses an absolute URL, or a path on the site, up to the next whitespace.
+fn url(input: &str) -> IResult<&str, &str> {
+    let (_, scheme) =
+        alt((tag_no_case("https://"), tag_no_case("http://"), tag("/"))).parse(input)?;
+    let end = input.find(char::is_whitespace).unwrap_or(input.len());
+    // Punctuation at the end is more likely part of the sentence than the URL.
+    let url = input[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\'']);
+    if url.len() <= scheme.len() {
+        return Err(nom::Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+    Ok((&input[url.len()..], url))
+}
+
+/// Parses `"label":url` and `"label":[url]`
+fn quoted_link(input: &str) -> IResult<&str, DTextSpan> {
+    let (rest, label) =
+        delimited(char('"'), take_till1(|c| c == '"' || c == '\n'), tag("\":")).parse(input)?;
+    let (rest, href) = alt((
+        delimited(char('['), take_till1(|c| c == ']' || c == '\n'), char(']')),
+        url,
+    ))
+    .parse(rest)?;
+
+    Ok((
+        rest,
+        DTextSpan::Link {
+            href: href.to_string(),
+            label: label.to_string(),
+        },
+    ))
+}
+
+fn bare_url(input: &str) -> IResult<&str, DTextSpan> {
+    let (rest, href) = verify(url, |url: &str| !url.starts_with('/')).parse(input)?;
+    Ok((
+        rest,
+        DTextSpan::Link {
+            href: href.to_string(),
+            label: href.to_string(),
+        },
+    ))
+}
+
+/// Parses `post #123`, `pool #123`, `comment #123` and `user #123`
+fn reference(input: &str) -> IResult<&str, DTextSpan> {
+    let (rest, (kind, id)) = (
+        alt((
+            tag_no_case("post #"),
+            tag_no_case("pool #"),
+            tag_no_case("comment #"),
+            tag_no_case("user #"),
+        )),
+        map_res(digit1, str::parse::<u32>),
+    )
+        .parse(input)?;
+
+    let span = match kind.to_ascii_lowercase().as_str() {
+        "post #" => DTextSpan::PostLink(id),
+        "pool #" => DTextSpan::PoolLink(id),
+        "comment #" => DTextSpan::CommentLink(id),
+        _ => DTextSpan::UserLink(id),
+    };
+    Ok((rest, span))
+}
+
+/// Parses opening and closing inline tags like `[b]`, `[/b]` and `[color=red]`.
+fn format_tag(input: &str) -> IResult<&str, Token<'_>> {
+    let (rest, (close, name, arg)) = delimited(
+        char('['),
+        (
+            opt(char('/')),
+            alpha1,
+            opt(preceded(char('='), take_till1(|c| c == ']' || c == '\n'))),
+        ),
+        char(']'),
+    )
+    .parse(input)?;
+    let raw = &input[..input.len() - rest.len()];
+
+    let format = match name.to_ascii_lowercase().as_str() {
+        "b" => Format::Bold,
+        "i" => Format::Italics,
+        "s" => Format::Strikeout,
+        "u" => Format::Underline,
+        "sup" => Format::Superscript,
+        "sub" => Format::Subscript,
+        "spoiler" => Format::Spoiler,
+        "color" => Format::Color,
+        _ => return Err(nom::Err::Error(Error::new(input, ErrorKind::Tag))),
+    };
+
+    // Only `[color=...]` takes an argument, and it needs one.
+    match (close.is_some(), arg) {
+        (true, None) => Ok((rest, Token::Close { format, raw })),
+        (false, None) if format != Format::Color => Ok((
+            rest,
+            Token::Open {
+                format,
+                color: None,
+                raw,
+            },
+        )),
+        (false, Some(color)) if format == Format::Color => Ok((
+            rest,
+            Token::Open {
+                format,
+                color: Some(color.trim()),
+                raw,
+            },
+        )),
+        _ => Err(nom::Err::Error(Error::new(input, ErrorKind::Verify))),
+    }
+}
+
+/// Nests spans between matching opening and closing tags. Tags without a match are kept as text.
+fn build_spans(tokens: Vec<Token<'_>>) -> Vec<DTextSpan> {
+    let mut root = Vec::new();
+    let mut open: Vec<Frame> = Vec::new();
+
+    for token in tokens {
+        match token {
+            Token::Text(text) => push_text(current(&mut root, &mut open), text),
+            Token::Span(span) => current(&mut root, &mut open).push(span),
+            Token::Open { raw, .. } if open.len() >= MAX_DEPTH => {
+                push_text(current(&mut root, &mut open), raw)
+            }
+            Token::Open { format, color, raw } => open.push(Frame {
+                format,
+                color,
+                raw,
+                children: Vec::new(),
+            }),
+            Token::Close { format, raw } => {
+                match open.iter().rposition(|frame| frame.format == format) {
+                    Some(index) => {
+                        // Tags opened inside this one and never closed are just text.
+                        while open.len() > index + 1 {
+                            let frame = open.pop().unwrap();
+                            unclosed(frame, current(&mut root, &mut open));
+                        }
+                        let span = open.pop().unwrap().into_span();
+                        current(&mut root, &mut open).push(span);
+                    }
+                    None => push_text(current(&mut root, &mut open), raw),
+                }
+            }
+        }
+    }
+
+    while let Some(frame) = open.pop() {
+        unclosed(frame, current(&mut root, &mut open));
+    }
+    root
+}
+
+fn current<'a>(root: &'a mut Vec<DTextSpan>, open: &'a mut [Frame]) -> &'a mut Vec<DTextSpan> {
+    match open.last_mut() {
+        Some(frame) => &mut frame.children,
+        None => root,
+    }
 }
 
-/// Parses `[i]italics[/i]`
-fn parse_italics(input: &str) -> IResult<&str, DTextSpan> {
-    let (rest, content) = delimited(tag("[i]"), parse_dtext, tag("[/i]")).parse(input)?;
-    Ok((rest, DTextSpan::Italics(content)))
+/// Puts back a tag that was never closed, as text followed by what was inside it.
+fn unclosed(frame: Frame, parent: &mut Vec<DTextSpan>) {
+    push_text(parent, frame.raw);
+    for child in frame.children {
+        match child {
+            DTextSpan::Text(text) => push_text(parent, &text),
+            other => parent.push(other),
+        }
+    }
 }
 
-fn parse_post_reference(input: &str) -> IResult<&str, DTextSpan> {
-    let (rest, (_, digits)) = (tag("post #"), digit1).parse(input)?;
-    let id = digits.parse::<u32>().unwrap_or(0);
-    Ok((rest, DTextSpan::PostLink(id)))
+/// Adds text, joining it onto the last span if that's text too.
+fn push_text(spans: &mut Vec<DTextSpan>, text: &str) {
+    match spans.last_mut() {
+        Some(DTextSpan::Text(last)) => last.push_str(text),
+        _ => spans.push(DTextSpan::Text(text.to_string())),
+    }
 }
 
-fn parse_plain_text(input: &str) -> IResult<&str, DTextSpan> {
-    let (rest, text) = take_while1(|c| !matches!(c, '*' | 'p')).parse(input)?;
-    Ok((rest, DTextSpan::Text(text.to_string())))
+impl Frame<'_> {
+    fn into_span(self) -> DTextSpan {
+        let children = self.children;
+        match self.format {
+            Format::Bold => DTextSpan::Bold(children),
+            Format::Italics => DTextSpan::Italics(children),
+            Format::Strikeout => DTextSpan::Strikeout(children),
+            Format::Underline => DTextSpan::Underline(children),
+            Format::Superscript => DTextSpan::Superscript(children),
+            Format::Subscript => DTextSpan::Subscript(children),
+            Format::Spoiler => DTextSpan::Spoiler(children),
+            Format::Color => DTextSpan::Colored {
+                color: self.color.unwrap_or_default().to_string(),
+                children,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn text(text: &str) -> DTextSpan {
+        DTextSpan::Text(text.to_string())
+    }
+
+    fn paragraph(spans: Vec<DTextSpan>) -> Vec<DTextBlock> {
+        vec![DTextBlock::Paragraph(spans)]
+    }
+
+    /// Every string in the parsed tree: text, labels, targets, titles and code.
+    fn strings(blocks: &[DTextBlock]) -> Vec<String> {
+        let mut out = Vec::new();
+        block_strings(blocks, &mut out);
+        out
+    }
+
+    fn block_strings(blocks: &[DTextBlock], out: &mut Vec<String>) {
+        for block in blocks {
+            match block {
+                DTextBlock::Paragraph(spans) | DTextBlock::Header { spans, .. } => {
+                    span_strings(spans, out)
+                }
+                DTextBlock::Quote(blocks) | DTextBlock::Spoiler(blocks) => {
+                    block_strings(blocks, out)
+                }
+                DTextBlock::Code(code) => out.push(code.clone()),
+                DTextBlock::Section { title, blocks, .. } => {
+                    out.push(title.clone());
+                    block_strings(blocks, out);
+                }
+                DTextBlock::List(items) => {
+                    items.iter().for_each(|item| span_strings(&item.spans, out))
+                }
+                DTextBlock::Table(rows) => rows
+                    .iter()
+                    .flat_map(|row| &row.cells)
+                    .for_each(|cell| span_strings(&cell.spans, out)),
+            }
+        }
+    }
+
+    fn span_strings(spans: &[DTextSpan], out: &mut Vec<String>) {
+        for span in spans {
+            match span {
+                DTextSpan::Text(text)
+                | DTextSpan::InlineCode(text)
+                | DTextSpan::TagSearch(text) => out.push(text.clone()),
+                DTextSpan::Bold(children)
+                | DTextSpan::Italics(children)
+                | DTextSpan::Strikeout(children)
+                | DTextSpan::Underline(children)
+                | DTextSpan::Superscript(children)
+                | DTextSpan::Subscript(children)
+                | DTextSpan::Spoiler(children) => span_strings(children, out),
+                DTextSpan::Colored { color, children } => {
+                    out.push(color.clone());
+                    span_strings(children, out);
+                }
+                DTextSpan::Link { href, label } => out.extend([href.clone(), label.clone()]),
+                DTextSpan::WikiLink { tag, label } => {
+                    out.push(tag.clone());
+                    out.extend(label.clone());
+                }
+                DTextSpan::PostLink(id)
+                | DTextSpan::PoolLink(id)
+                | DTextSpan::CommentLink(id)
+                | DTextSpan::UserLink(id) => out.push(id.to_string()),
+                DTextSpan::LineBreak => {}
+            }
+        }
+    }
+
+    #[test]
+    fn paragraphs_are_split_on_blank_lines() {
+        assert_eq!(
+            parse_dtext("one\r\ntwo\n\n\nthree"),
+            vec![
+                DTextBlock::Paragraph(vec![text("one"), DTextSpan::LineBreak, text("two")]),
+                DTextBlock::Paragraph(vec![text("three")]),
+            ]
+        );
+        assert_eq!(parse_dtext(""), vec![]);
+        assert_eq!(parse_dtext(" \n\n"), vec![]);
+    }
+
+    #[test]
+    fn inline_formatting_nests() {
+        assert_eq!(
+            parse_dtext("[b]bold [I]both[/i][/B] [color=#ff0000]red[/color]"),
+            paragraph(vec![
+                DTextSpan::Bold(vec![text("bold "), DTextSpan::Italics(vec![text("both")])]),
+                text(" "),
+                DTextSpan::Colored {
+                    color: "#ff0000".to_string(),
+                    children: vec![text("red")]
+                },
+            ])
+        );
+        assert_eq!(
+            parse_dtext("x[sup]2[/sup] [spoiler]twist[/spoiler] `[b]`"),
+            paragraph(vec![
+                text("x"),
+                DTextSpan::Superscript(vec![text("2")]),
+                text(" "),
+                DTextSpan::Spoiler(vec![text("twist")]),
+                text(" "),
+                DTextSpan::InlineCode("[b]".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn unmatched_tags_stay_text() {
+        assert_eq!(
+            parse_dtext("[b]never closed [i]italic[/i]"),
+            paragraph(vec![
+                text("[b]never closed "),
+                DTextSpan::Italics(vec![text("italic")])
+            ])
+        );
+        assert_eq!(
+            parse_dtext("[b]a[i]b[/b] [/s] [color]x[/color] [foo]"),
+            paragraph(vec![
+                DTextSpan::Bold(vec![text("a[i]b")]),
+                text(" [/s] [color]x[/color] [foo]"),
+            ])
+        );
+    }
+
+    #[test]
+    fn plain_text_with_markup_characters() {
+        let input = "apples * pears cost $5 (each) - a \"deal\" [maybe]";
+        assert_eq!(parse_dtext(input), paragraph(vec![text(input)]));
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            parse_dtext("see \"the site\":https://e621.net/posts, or \"here\":[/wiki_pages/help]."),
+            paragraph(vec![
+                text("see "),
+                DTextSpan::Link {
+                    href: "https://e621.net/posts".to_string(),
+                    label: "the site".to_string()
+                },
+                text(", or "),
+                DTextSpan::Link {
+                    href: "/wiki_pages/help".to_string(),
+                    label: "here".to_string()
+                },
+                text("."),
+            ])
+        );
+        assert_eq!(
+            parse_dtext("(source: https://example.com/a?b=c)"),
+            paragraph(vec![
+                text("(source: "),
+                DTextSpan::Link {
+                    href: "https://example.com/a?b=c".to_string(),
+                    label: "https://example.com/a?b=c".to_string()
+                },
+                text(")"),
+            ])
+        );
+        assert_eq!(
+            parse_dtext("[[canine]] [[red_fox|foxes]] {{fox -canine}} [[ ]]"),
+            paragraph(vec![
+                DTextSpan::WikiLink {
+                    tag: "canine".to_string(),
+                    label: None
+                },
+                text(" "),
+                DTextSpan::WikiLink {
+                    tag: "red_fox".to_string(),
+                    label: Some("foxes".to_string())
+                },
+                text(" "),
+                DTextSpan::TagSearch("fox -canine".to_string()),
+                text(" [[ ]]"),
+            ])
+        );
+    }
+
+    #[test]
+    fn references() {
+        assert_eq!(
+            parse_dtext("Post #12 is in pool #3, see comment #4 by user #5"),
+            paragraph(vec![
+                DTextSpan::PostLink(12),
+                text(" is in "),
+                DTextSpan::PoolLink(3),
+                text(", see "),
+                DTextSpan::CommentLink(4),
+                text(" by "),
+                DTextSpan::UserLink(5),
+            ])
+        );
+        // Only at the start of a word, and only if the ID fits.
+        let input = "repost #3 post #99999999999 post #";
+        assert_eq!(parse_dtext(input), paragraph(vec![text(input)]));
+    }
+
+    #[test]
+    fn headers_and_lists() {
+        assert_eq!(
+            parse_dtext("h2. [b]Title[/b]\n* one\n** two\ntext\nh7. not a header"),
+            vec![
+                DTextBlock::Header {
+                    level: 2,
+                    spans: vec![DTextSpan::Bold(vec![text("Title")])]
+                },
+                DTextBlock::List(vec![
+                    ListItem {
+                        depth: 1,
+                        spans: vec![text("one")]
+                    },
+                    ListItem {
+                        depth: 2,
+                        spans: vec![text("two")]
+                    },
+                ]),
+                DTextBlock::Paragraph(vec![
+                    text("text"),
+                    DTextSpan::LineBreak,
+                    text("h7. not a header")
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_nest_and_code_is_verbatim() {
+        assert_eq!(
+            parse_dtext("[quote]outer\n[QUOTE]inner[/quote][/quote]\n[code]\n[b]x[/b]\n[/code]"),
+            vec![
+                DTextBlock::Quote(vec![
+                    DTextBlock::Paragraph(vec![text("outer")]),
+                    DTextBlock::Quote(vec![DTextBlock::Paragraph(vec![text("inner")])]),
+                ]),
+                DTextBlock::Code("[b]x[/b]".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_dtext("[quote]unclosed"),
+            paragraph(vec![text("[quote]unclosed")])
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_kept_as_text() {
+        let quotes = "[quote]".repeat(MAX_DEPTH + 1) + "deep" + &"[/quote]".repeat(MAX_DEPTH + 1);
+        let mut blocks = parse_dtext(&quotes);
+        for _ in 0..MAX_DEPTH {
+            blocks = match blocks.as_slice() {
+                [DTextBlock::Quote(inner)] => inner.clone(),
+                other => panic!("expected a quote, got {other:?}"),
+            };
+        }
+        assert_eq!(blocks, paragraph(vec![text("[quote]deep[/quote]")]));
+
+        let bold = "[b]".repeat(MAX_DEPTH + 1) + "deep" + &"[/b]".repeat(MAX_DEPTH + 1);
+        let mut spans = match parse_dtext(&bold).as_slice() {
+            [DTextBlock::Paragraph(spans)] => spans.clone(),
+            other => panic!("expected a paragraph, got {other:?}"),
+        };
+        for _ in 0..MAX_DEPTH {
+            spans = match spans.as_slice() {
+                [DTextSpan::Bold(inner)] | [DTextSpan::Bold(inner), DTextSpan::Text(_)] => {
+                    inner.clone()
+                }
+                other => panic!("expected bold, got {other:?}"),
+            };
+        }
+        assert_eq!(spans, vec![text("[b]deep")]);
+    }
+
+    #[test]
+    fn sections_and_spoilers() {
+        assert_eq!(
+            parse_dtext("[section,expanded=Notes]text[/section]\n[spoiler]\nhidden\n[/spoiler]"),
+            vec![
+                DTextBlock::Section {
+                    title: "Notes".to_string(),
+                    expanded: true,
+                    blocks: vec![DTextBlock::Paragraph(vec![text("text")])]
+                },
+                DTextBlock::Spoiler(vec![DTextBlock::Paragraph(vec![text("hidden")])]),
+            ]
+        );
+        assert_eq!(
+            parse_dtext("[section]x[/section]"),
+            vec![DTextBlock::Section {
+                title: String::new(),
+                expanded: false,
+                blocks: vec![DTextBlock::Paragraph(vec![text("x")])]
+            }]
+        );
+    }
+
+    #[test]
+    fn tables() {
+        let input = "[table]\n[thead][tr][th]Name[/th][/tr][/thead]\n[tbody][tr][td] [[fox]] [/td][/tr][/tbody]\n[/table]";
+        assert_eq!(
+            parse_dtext(input),
+            vec![DTextBlock::Table(vec![
+                TableRow {
+                    cells: vec![TableCell {
+                        header: true,
+                        spans: vec![text("Name")]
+                    }]
+                },
+                TableRow {
+                    cells: vec![TableCell {
+                        header: false,
+                        spans: vec![DTextSpan::WikiLink {
+                            tag: "fox".to_string(),
+                            label: None
+                        }]
+                    }]
+                },
+            ])]
+        );
+
+        // Stray text makes the whole table plain text.
+        let blocks = parse_dtext("[table]stray[tr][td]x[/td][/tr][/table]");
+        assert!(matches!(blocks.as_slice(), [DTextBlock::Paragraph(_)]));
+        assert!(strings(&blocks).concat().contains("stray"));
+    }
+
+    fn markup() -> impl Strategy<Value = String> {
+        let token = prop::sample::select(vec![
+            "alpha",
+            "beta",
+            "gamma",
+            "delta",
+            " ",
+            "\n",
+            "\n\n",
+            "[b]",
+            "[/b]",
+            "[i]",
+            "[/i]",
+            "[spoiler]",
+            "[/spoiler]",
+            "[color=red]",
+            "[/color]",
+            "[quote]",
+            "[/quote]",
+            "[code]",
+            "[/code]",
+            "[section=",
+            "[section]",
+            "[/section]",
+            "[table]",
+            "[/table]",
+            "[tr]",
+            "[/tr]",
+            "[td]",
+            "[/td]",
+            "]",
+            "[[",
+            "]]",
+            "|",
+            "{{",
+            "}}",
+            "\"",
+            "\":",
+            "https://",
+            "/",
+            "post #",
+            "pool #1",
+            "1",
+            "h1. ",
+            "* ",
+            "** ",
+            "`",
+            ".",
+            ")",
+        ]);
+        prop::collection::vec(token, 0..40).prop_map(|tokens| tokens.concat())
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(input in "\\PC*") {
+            parse_dtext(&input);
+        }
+
+        #[test]
+        fn words_are_never_lost(input in markup()) {
+            let parsed = strings(&parse_dtext(&input)).join(" ");
+            for word in ["alpha", "beta", "gamma", "delta"] {
+                prop_assert!(
+                    parsed.matches(word).count() >= input.matches(word).count(),
+                    "{word} lost from {input:?}: {parsed:?}"
+                );
+            }
+        }
+
+        #[test]
+        fn plain_text_is_unchanged(input in "[a-zA-Z0-9 ,.!?]*[a-zA-Z0-9]") {
+            // Like `h1.Title`, which is a header.
+            prop_assume!(header_start(&input).is_none());
+            prop_assert_eq!(parse_dtext(&input), paragraph(vec![text(&input)]));
+        }
+    }
 }
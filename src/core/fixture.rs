@@ -0,0 +1,52 @@
+//! The post every test and bench builds its posts from. Only compiled for the crate's own tests,
+//! or with the `test-util` feature, which the integration tests and benches turn on.
+
+use serde_json::{json, Value};
+
+use super::model::Post;
+
+/// A post in the API's JSON shape, with `overrides` merged on top. Objects such as `"tags"` are
+/// merged a level down, so `{"tags": {"general": ["solo"]}}` leaves the other categories alone.
+pub fn post_json(overrides: Value) -> Value {
+    let mut base = json!({
+        "id": 1000,
+        "created_at": "2024-01-01T00:00:00.000-05:00",
+        "updated_at": "2024-01-01T00:00:00.000-05:00",
+        "file": { "ext": "png", "url": "https://static1.e621.net/data/ab/cd/abcd.png" },
+        "preview": { "url": null },
+        "sample": { "has": false, "url": null },
+        "score": { "total": 10 },
+        "tags": {
+            "general": ["solo", "smile"],
+            "artist": ["some_artist"],
+            "copyright": [],
+            "character": [],
+            "species": ["canine", "domestic_dog"],
+            "invalid": [],
+            "meta": ["hi_res"],
+            "lore": []
+        },
+        "rating": "s",
+        "is_favorited": false,
+        "fav_count": 25,
+        "description": "",
+        "uploader_id": 42,
+        "uploader_name": "Uploader"
+    });
+    if let (Some(base), Value::Object(overrides)) = (base.as_object_mut(), overrides) {
+        for (key, value) in overrides {
+            match (base.get_mut(&key), value) {
+                (Some(Value::Object(field)), Value::Object(value)) => field.extend(value),
+                (_, value) => {
+                    base.insert(key, value);
+                }
+            }
+        }
+    }
+    base
+}
+
+/// [`post_json`], deserialized.
+pub fn post(overrides: Value) -> Post {
+    serde_json::from_value(post_json(overrides)).expect("fixture should deserialize")
+}
@@ -1,6 +1,6 @@
 use super::api::{self, FetchPoint};
 use super::config::Auth;
-use super::model::Post;
+use super::model::{Pool, Post};
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use tracing::{instrument, warn};
@@ -13,6 +13,7 @@ pub struct FollowedTag {
 
 #[instrument(skip(auth))]
 pub async fn check_for_updates(
+    base_url: &str,
     followed_tags: &Vec<FollowedTag>,
     auth: Option<&Auth>,
 ) -> Result<FxHashMap<String, Vec<Post>>, api::ApiError> {
@@ -23,10 +24,11 @@ pub async fn check_for_updates(
             None => None,
             Some(id) => Some(FetchPoint::After(id)),
         };
-        match api::fetch_posts(auth, tag.tag.clone(), fetch_point).await {
+        match api::fetch_posts(base_url, auth, tag.tag.clone(), fetch_point).await {
             Ok(posts) => {
                 updates.insert(tag.tag.clone(), posts);
             }
+            Err(err) if err.affects_all_requests() => return Err(err),
             Err(err) => {
                 warn!("Failed to fetch for tag '{}': {err}", tag.tag);
             }
@@ -36,6 +38,95 @@ pub async fn check_for_updates(
     Ok(updates)
 }
 
+/// A pool followed for new pages.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct FollowedPool {
+    pub id: u32,
+    pub name: String,
+    /// Last page seen, so anything after it is new.
+    pub last_seen: Option<u32>,
+}
+
+/// New pages of a followed pool.
+#[derive(Debug, Clone)]
+pub struct PoolUpdate {
+    pub pool: Pool,
+    pub posts: Vec<Post>,
+}
+
+impl FollowedPool {
+    /// Follows `pool` from its current last page.
+    pub fn new(pool: &Pool) -> Self {
+        Self {
+            id: pool.id,
+            name: pool.name.clone(),
+            last_seen: pool.post_ids.last().copied(),
+        }
+    }
+
+    /// Pages added to `pool` after the last seen one. If that page was removed from the pool,
+    /// falls back to pages with a higher post ID.
+    pub fn new_pages(&self, pool: &Pool) -> Vec<u32> {
+        match self.last_seen {
+            None => pool.post_ids.clone(),
+            Some(last_seen) => match pool.position(last_seen) {
+                Some(pos) => pool.post_ids[pos + 1..].to_vec(),
+                None => pool
+                    .post_ids
+                    .iter()
+                    .copied()
+                    .filter(|&id| id > last_seen)
+                    .collect(),
+            },
+        }
+    }
+}
+
+#[instrument(skip(auth))]
+pub async fn check_pool_updates(
+    base_url: &str,
+    followed_pools: &[FollowedPool],
+    auth: Option<&Auth>,
+) -> Result<Vec<PoolUpdate>, api::ApiError> {
+    let mut updates = Vec::new();
+
+    for followed in followed_pools {
+        let pool = match api::fetch_pool(base_url, auth, followed.id).await {
+            Ok(pool) => pool,
+            Err(err) if err.affects_all_requests() => return Err(err),
+            Err(err) => {
+                warn!("Failed to fetch pool #{}: {err}", followed.id);
+                continue;
+            }
+        };
+
+        let new_pages = followed.new_pages(&pool);
+        if new_pages.is_empty() {
+            updates.push(PoolUpdate {
+                pool,
+                posts: Vec::new(),
+            });
+            continue;
+        }
+
+        let posts = match api::fetch_pool_posts(base_url, auth, &pool).await {
+            Ok(posts) => posts,
+            Err(err) if err.affects_all_requests() => return Err(err),
+            Err(err) => {
+                warn!("Failed to fetch posts of pool #{}: {err}", followed.id);
+                continue;
+            }
+        };
+        let posts = posts
+            .into_iter()
+            .filter(|post| new_pages.contains(&post.id))
+            .collect();
+        updates.push(PoolUpdate { pool, posts });
+    }
+
+    Ok(updates)
+}
+
 impl FollowedTag {
     pub fn decompose(self) -> (String, Option<u32>) {
         (self.tag, self.last_seen)
@@ -60,3 +151,40 @@ pub fn compose_hashmap(vec: Vec<FollowedTag>) -> FxHashMap<String, Option<u32>>
     }
     tag_map
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(post_ids: Vec<u32>) -> Pool {
+        serde_json::from_value(serde_json::json!({
+            "id": 7,
+            "name": "a_comic",
+            "created_at": "2024-01-01T00:00:00.000-05:00",
+            "updated_at": "2024-01-01T00:00:00.000-05:00",
+            "post_ids": post_ids
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn new_pool_pages_follow_the_last_seen_one() {
+        let followed = FollowedPool::new(&pool(vec![10, 30, 20]));
+        assert_eq!(followed.last_seen, Some(20));
+
+        assert!(followed.new_pages(&pool(vec![10, 30, 20])).is_empty());
+        assert_eq!(
+            followed.new_pages(&pool(vec![10, 30, 20, 15, 40])),
+            vec![15, 40]
+        );
+
+        // The last seen page was removed, so go by post ID instead.
+        assert_eq!(followed.new_pages(&pool(vec![10, 30, 40])), vec![30, 40]);
+
+        let unseen = FollowedPool {
+            last_seen: None,
+            ..followed
+        };
+        assert_eq!(unseen.new_pages(&pool(vec![1, 2])), vec![1, 2]);
+    }
+}
@@ -1,7 +1,18 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
-use reqwest::{Client, Method, RequestBuilder};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use tokio::time::sleep;
+use tracing::{debug, warn};
 
-use super::config::Auth;
+use super::api::rate_limiter::RateLimiter;
+use super::config::{Auth, RetryConfig};
 
 pub static CLIENT: Lazy<Client> = Lazy::new(|| {
     let user_agent = format!(
@@ -15,7 +26,332 @@ pub static CLIENT: Lazy<Client> = Lazy::new(|| {
         .expect("failed to build reqwest Client")
 });
 
+/// Retry policy used by [`send_with_retry`], set from the config.
+static RETRY_CONFIG: Lazy<RwLock<RetryConfig>> = Lazy::new(|| RwLock::new(RetryConfig::default()));
+
 pub fn authed_request(client: &Client, method: Method, url: &str, auth: &Auth) -> RequestBuilder {
     let request = client.request(method, url);
     request.basic_auth(&auth.username, Some(&auth.api_key))
 }
+
+/// Replaces the retry policy for all following requests.
+pub fn set_retry_config(config: RetryConfig) {
+    *RETRY_CONFIG.write().expect("retry config poisoned!") = config;
+}
+
+fn retry_config() -> RetryConfig {
+    *RETRY_CONFIG.read().expect("retry config poisoned!")
+}
+
+/// Sends `request`, retrying dropped connections and 429/5xx responses with exponential
+/// backoff. Gives back the last response once retries run out, so callers still see the error.
+///
+/// Only GET and HEAD requests are retried. Anything else may have reached the server before the
+/// connection dropped, and sending it again could post a comment twice or undo a vote.
+///
+/// Every attempt waits its turn with `limiter`, so retries count against the rate limit too.
+pub async fn send_with_retry(
+    limiter: &RateLimiter,
+    request: RequestBuilder,
+) -> reqwest::Result<Response> {
+    send_with_config(limiter, request, &retry_config()).await
+}
+
+/// GETs `url` and reads the whole body, retrying if the connection drops partway through.
+pub async fn get_bytes(limiter: &RateLimiter, url: &str) -> reqwest::Result<Bytes> {
+    get_bytes_with_config(limiter, url, &retry_config()).await
+}
+
+async fn get_bytes_with_config(
+    limiter: &RateLimiter,
+    url: &str,
+    config: &RetryConfig,
+) -> reqwest::Result<Bytes> {
+    let mut attempt = 0;
+
+    loop {
+        let res = send_with_config(limiter, CLIENT.get(url), config).await?;
+        match res.error_for_status()?.bytes().await {
+            Ok(bytes) => return Ok(bytes),
+            Err(err) if attempt < config.max_retries => {
+                let delay = backoff(config, attempt);
+                warn!("Reading {url} failed ({err}), retrying in {delay:?}");
+                sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// GETs `url` and reads the whole body, without retrying anything. For callers that retry on a
+/// schedule of their own.
+pub async fn get_bytes_once(limiter: &RateLimiter, url: &str) -> reqwest::Result<Bytes> {
+    let config = RetryConfig {
+        max_retries: 0,
+        ..retry_config()
+    };
+    let res = send_with_config(limiter, CLIENT.get(url), &config).await?;
+    res.error_for_status()?.bytes().await
+}
+
+async fn send_with_config(
+    limiter: &RateLimiter,
+    request: RequestBuilder,
+    config: &RetryConfig,
+) -> reqwest::Result<Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let retries = match *request.method() {
+        Method::GET | Method::HEAD => config.max_retries,
+        _ => 0,
+    };
+    let max_delay = Duration::from_millis(config.max_delay_ms);
+    let mut attempt = 0;
+
+    loop {
+        // Streaming bodies can't be cloned, so those only get the one attempt.
+        let Some(this_try) = request.try_clone().filter(|_| attempt < retries) else {
+            return limiter.run(client.execute(request)).await;
+        };
+
+        let delay = match limiter.run(client.execute(this_try)).await {
+            Ok(res) if is_transient(res.status()) => match retry_after(&res) {
+                Some(wait) if wait > max_delay => {
+                    debug!("Server asked to wait {wait:?}, not retrying");
+                    return Ok(res);
+                }
+                Some(wait) => wait,
+                None => backoff(config, attempt),
+            },
+            Ok(res) => return Ok(res),
+            Err(err) if err.is_builder() || err.is_redirect() => return Err(err),
+            Err(err) => {
+                debug!("Request failed: {err}");
+                backoff(config, attempt)
+            }
+        };
+
+        attempt += 1;
+        warn!(
+            "Retrying request ({attempt}/{}) in {delay:?}",
+            config.max_retries
+        );
+        sleep(delay).await;
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Reads `Retry-After` as either a number of seconds or an HTTP date.
+fn retry_after(res: &Response) -> Option<Duration> {
+    let value = res.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Exponential backoff with jitter: somewhere between half and all of `base * 2^attempt`,
+/// capped at the max delay.
+fn backoff(config: &RetryConfig, attempt: u32) -> Duration {
+    let ceiling = config
+        .base_delay_ms
+        .saturating_mul(1u64 << attempt.min(16))
+        .min(config.max_delay_ms);
+    let jitter = RandomState::new().build_hasher().finish() % (ceiling / 2 + 1);
+
+    Duration::from_millis(ceiling - jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Serves `responses` in order, one per connection, then keeps repeating the last one.
+    /// An empty response closes the connection without answering. Returns the base URL and a
+    /// counter of requests received.
+    async fn mock_server(responses: Vec<&'static str>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                let response = responses[n.min(responses.len() - 1)];
+
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        (url, hits)
+    }
+
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+    const UNAVAILABLE: &str =
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const NOT_FOUND: &str =
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const RATE_LIMITED: &str = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const SLOW_DOWN: &str = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 3600\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const TRUNCATED: &str = "HTTP/1.1 200 OK\r\nContent-Length: 100\r\nConnection: close\r\n\r\nok";
+
+    /// Enough tokens that no test waits on them.
+    fn limiter() -> RateLimiter {
+        RateLimiter::new("test", 100, 100.0)
+    }
+
+    fn fast() -> RetryConfig {
+        RetryConfig {
+            max_retries: 3,
+            base_delay_ms: 1,
+            max_delay_ms: 2000,
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_until_success() {
+        let (url, hits) = mock_server(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let limiter = limiter();
+
+        let res = send_with_config(&limiter, CLIENT.get(&url), &fast())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+        // Each attempt took a token of its own.
+        assert_eq!(limiter.stats().completed, 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_with_last_response() {
+        let (url, hits) = mock_server(vec![UNAVAILABLE]).await;
+
+        let res = send_with_config(&limiter(), CLIENT.get(&url), &fast())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let (url, hits) = mock_server(vec![NOT_FOUND, OK]).await;
+
+        let res = send_with_config(&limiter(), CLIENT.get(&url), &fast())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn only_gets_are_retried() {
+        let (url, hits) = mock_server(vec![UNAVAILABLE, OK]).await;
+
+        let res = send_with_config(&limiter(), CLIENT.post(&url).body("{}"), &fast())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let (url, hits) = mock_server(vec!["", OK]).await;
+        assert!(send_with_config(&limiter(), CLIENT.delete(&url), &fast())
+            .await
+            .is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn dropped_connections_are_retried() {
+        let (url, hits) = mock_server(vec!["", OK]).await;
+
+        let res = send_with_config(&limiter(), CLIENT.get(&url), &fast())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn honors_retry_after() {
+        let (url, hits) = mock_server(vec![RATE_LIMITED, OK]).await;
+
+        let start = std::time::Instant::now();
+        let res = send_with_config(&limiter(), CLIENT.get(&url), &fast())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn long_retry_after_is_not_waited_out() {
+        let (url, hits) = mock_server(vec![SLOW_DOWN, OK]).await;
+
+        let res = send_with_config(&limiter(), CLIENT.get(&url), &fast())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn truncated_bodies_are_refetched() {
+        let (url, hits) = mock_server(vec![TRUNCATED, OK]).await;
+
+        let bytes = get_bytes_with_config(&limiter(), &url, &fast())
+            .await
+            .unwrap();
+        assert_eq!(&bytes[..], b"ok");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let config = RetryConfig {
+            max_retries: 10,
+            base_delay_ms: 100,
+            max_delay_ms: 1000,
+        };
+
+        for _ in 0..50 {
+            let first = backoff(&config, 0);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+
+            let third = backoff(&config, 2);
+            assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+
+            assert!(backoff(&config, 9) <= Duration::from_millis(1000));
+            assert!(backoff(&config, 40) >= Duration::from_millis(500));
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! Keyboard shortcuts, and the keymap in the config that binds keys to them.
+
+use core::fmt;
+use std::str::FromStr;
+
+use iced::keyboard::{Key, Modifiers};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Something a keyboard shortcut can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Next post in the grid or detail view.
+    Next,
+    Previous,
+    /// The post one row up in the grid.
+    Up,
+    Down,
+    Open,
+    Back,
+    Forward,
+    Favorite,
+    Upvote,
+    Downvote,
+    FocusSearch,
+    /// Pause or play the video being viewed.
+    TogglePlayback,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 13] = [
+        Action::Next,
+        Action::Previous,
+        Action::Up,
+        Action::Down,
+        Action::Open,
+        Action::Back,
+        Action::Forward,
+        Action::Favorite,
+        Action::Upvote,
+        Action::Downvote,
+        Action::FocusSearch,
+        Action::TogglePlayback,
+        Action::Help,
+    ];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Next => "Next post",
+            Action::Previous => "Previous post",
+            Action::Up => "Post above",
+            Action::Down => "Post below",
+            Action::Open => "Open post",
+            Action::Back => "Go back",
+            Action::Forward => "Go forward",
+            Action::Favorite => "Favorite",
+            Action::Upvote => "Upvote",
+            Action::Downvote => "Downvote",
+            Action::FocusSearch => "Search",
+            Action::TogglePlayback => "Pause or play video",
+            Action::Help => "Show shortcuts",
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("invalid key binding {0:?}")]
+pub struct KeyBindingError(String);
+
+/// A key and the modifiers held with it, written like `ctrl+f` or `alt+ArrowLeft`.
+///
+/// Keys are either a character, as typed, or one of iced's named keys like `Enter`, `Escape`,
+/// `Space` or `ArrowDown`. Shift is implied by the character, so `?` and `F` don't need it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyBinding {
+    key: String,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl KeyBinding {
+    /// Whether `key`, as modified by shift, pressed with `modifiers` triggers this binding.
+    pub fn matches(&self, key: &Key, modifiers: Modifiers) -> bool {
+        if self.ctrl != modifiers.control() || self.alt != modifiers.alt() {
+            return false;
+        }
+        match key {
+            Key::Character(c) => self.key == c.as_str(),
+            Key::Named(named) => {
+                self.shift == modifiers.shift()
+                    && self.key.eq_ignore_ascii_case(&format!("{named:?}"))
+            }
+            Key::Unidentified => false,
+        }
+    }
+
+    fn is_character(key: &str) -> bool {
+        key.chars().count() == 1
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = KeyBindingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || KeyBindingError(s.to_string());
+        // `+` is a key too, so split off modifiers from the front rather than splitting on `+`.
+        let mut binding = KeyBinding {
+            key: String::new(),
+            ctrl: false,
+            alt: false,
+            shift: false,
+        };
+        let mut rest = s.trim();
+        while let Some((modifier, key)) = rest.split_once('+').filter(|(m, _)| !m.is_empty()) {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => binding.ctrl = true,
+                "alt" => binding.alt = true,
+                "shift" => binding.shift = true,
+                _ => return Err(invalid()),
+            }
+            rest = key;
+        }
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+
+        if Self::is_character(rest) {
+            binding.key = match binding.shift {
+                true => rest.to_uppercase(),
+                false => rest.to_string(),
+            };
+            binding.shift = false;
+        } else {
+            binding.key = rest.to_string();
+        }
+        Ok(binding)
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = KeyBindingError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<KeyBinding> for String {
+    fn from(binding: KeyBinding) -> Self {
+        binding.to_string()
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "alt+")?;
+        }
+        if self.shift {
+            write!(f, "shift+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// Keys bound to each [`Action`]. Actions missing from the config keep their default keys, and
+/// an empty list unbinds one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keymap {
+    pub next: Vec<KeyBinding>,
+    pub previous: Vec<KeyBinding>,
+    pub up: Vec<KeyBinding>,
+    pub down: Vec<KeyBinding>,
+    pub open: Vec<KeyBinding>,
+    pub back: Vec<KeyBinding>,
+    pub forward: Vec<KeyBinding>,
+    pub favorite: Vec<KeyBinding>,
+    pub upvote: Vec<KeyBinding>,
+    pub downvote: Vec<KeyBinding>,
+    pub focus_search: Vec<KeyBinding>,
+    pub toggle_playback: Vec<KeyBinding>,
+    pub help: Vec<KeyBinding>,
+}
+
+impl Keymap {
+    pub fn bindings(&self, action: Action) -> &[KeyBinding] {
+        match action {
+            Action::Next => &self.next,
+            Action::Previous => &self.previous,
+            Action::Up => &self.up,
+            Action::Down => &self.down,
+            Action::Open => &self.open,
+            Action::Back => &self.back,
+            Action::Forward => &self.forward,
+            Action::Favorite => &self.favorite,
+            Action::Upvote => &self.upvote,
+            Action::Downvote => &self.downvote,
+            Action::FocusSearch => &self.focus_search,
+            Action::TogglePlayback => &self.toggle_playback,
+            Action::Help => &self.help,
+        }
+    }
+
+    /// The action bound to a key press, if any. If a key is bound twice, the first action in
+    /// [`Action::ALL`] wins.
+    pub fn action(&self, key: &Key, modifiers: Modifiers) -> Option<Action> {
+        Action::ALL.into_iter().find(|&action| {
+            self.bindings(action)
+                .iter()
+                .any(|binding| binding.matches(key, modifiers))
+        })
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        fn keys(keys: &[&str]) -> Vec<KeyBinding> {
+            keys.iter()
+                .map(|key| key.parse().expect("default key bindings are valid"))
+                .collect()
+        }
+
+        Keymap {
+            next: keys(&["j", "ArrowRight"]),
+            previous: keys(&["k", "ArrowLeft"]),
+            up: keys(&["ArrowUp"]),
+            down: keys(&["ArrowDown"]),
+            open: keys(&["Enter"]),
+            back: keys(&["Escape", "alt+ArrowLeft"]),
+            forward: keys(&["alt+ArrowRight"]),
+            favorite: keys(&["f"]),
+            upvote: keys(&["u"]),
+            downvote: keys(&["d"]),
+            focus_search: keys(&["/"]),
+            toggle_playback: keys(&["Space"]),
+            help: keys(&["?", "F1"]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::keyboard::key::Named;
+
+    use super::*;
+
+    fn character(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    #[test]
+    fn bindings_are_parsed_and_printed() {
+        for (input, output) in [
+            ("j", "j"),
+            ("ctrl+f", "ctrl+f"),
+            ("Alt+ArrowLeft", "alt+ArrowLeft"),
+            ("shift+f", "F"),
+            ("shift+Enter", "shift+Enter"),
+            ("+", "+"),
+            ("ctrl++", "ctrl++"),
+        ] {
+            let binding: KeyBinding = input.parse().unwrap();
+            assert_eq!(binding.to_string(), output, "{input}");
+        }
+
+        assert!("".parse::<KeyBinding>().is_err());
+        assert!("ctrl+".parse::<KeyBinding>().is_err());
+        assert!("hyper+j".parse::<KeyBinding>().is_err());
+    }
+
+    #[test]
+    fn keys_match_their_actions() {
+        let keymap = Keymap::default();
+        let none = Modifiers::empty();
+
+        assert_eq!(keymap.action(&character("j"), none), Some(Action::Next));
+        assert_eq!(
+            keymap.action(&Key::Named(Named::ArrowLeft), none),
+            Some(Action::Previous)
+        );
+        assert_eq!(
+            keymap.action(&Key::Named(Named::ArrowLeft), Modifiers::ALT),
+            Some(Action::Back)
+        );
+        // Shift is part of the character.
+        assert_eq!(
+            keymap.action(&character("?"), Modifiers::SHIFT),
+            Some(Action::Help)
+        );
+        assert_eq!(keymap.action(&character("F"), Modifiers::SHIFT), None);
+        assert_eq!(keymap.action(&character("f"), Modifiers::CTRL), None);
+        assert_eq!(
+            keymap.action(&Key::Named(Named::Enter), Modifiers::SHIFT),
+            None
+        );
+    }
+
+    #[test]
+    fn missing_actions_keep_their_defaults() {
+        let keymap: Keymap = toml::from_str(
+            r#"
+            next = ["n"]
+            favorite = []
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            keymap.action(&character("n"), Modifiers::empty()),
+            Some(Action::Next)
+        );
+        assert_eq!(keymap.action(&character("j"), Modifiers::empty()), None);
+        assert_eq!(keymap.action(&character("f"), Modifiers::empty()), None);
+        assert_eq!(keymap.previous, Keymap::default().previous);
+
+        assert!(toml::from_str::<Keymap>(r#"next = ["bogus+n"]"#).is_err());
+    }
+}
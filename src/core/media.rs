@@ -1,5 +1,6 @@
 use std::io::Cursor;
 use std::path::PathBuf;
+use std::sync::RwLock;
 
 use bytes::Bytes;
 use directories::ProjectDirs;
@@ -14,10 +15,17 @@ use thiserror::Error;
 use tracing::{debug, instrument, trace, warn};
 use url::Url;
 
-use super::http::CLIENT;
+use super::api::rate_limiter::MEDIA_LIMITER;
+use super::http::{get_bytes, get_bytes_once};
 use super::model::File;
 use super::model::Sample;
 
+mod cache;
+mod scheduler;
+
+pub use cache::{MediaCache, MediaKind, DEFAULT_MEMORY_BUDGET_MB};
+pub use scheduler::{ThumbnailJob, ThumbnailScheduler, THUMBNAIL_CONCURRENCY};
+
 const SIZE: u32 = 4096; // Textures larger than 4096x4096 tend to crash wgpu
 
 #[derive(Debug, Error)]
@@ -51,6 +59,8 @@ pub enum MediaError {
     Request(#[from] reqwest::Error),
 }
 
+/// Fetches a thumbnail, from the cache if it's there. A failed download isn't retried here, since
+/// [`ThumbnailScheduler`] retries it later without holding up the others.
 #[instrument(skip(url))]
 pub async fn fetch_preview(id: u32, url: String) -> Result<Handle, MediaError> {
     trace!("Fetching preview for {id}");
@@ -63,7 +73,7 @@ pub async fn fetch_preview(id: u32, url: String) -> Result<Handle, MediaError> {
     }
 
     trace!("Getting {id} from server ({url})");
-    let bytes = CLIENT.get(url).send().await?.bytes().await?;
+    let bytes = get_bytes_once(&MEDIA_LIMITER, &url).await?;
 
     trace!("Saving to {file_path:?}");
     std::fs::create_dir_all(file_path.parent().unwrap())?;
@@ -90,7 +100,7 @@ pub async fn fetch_image(id: u32, file: File) -> Result<Handle, MediaError> {
     } else {
         let url = file.url.as_ref().ok_or(MediaError::MissingUrl)?;
         trace!("Getting post {id} from server ({url})");
-        let bytes = CLIENT.get(url).send().await?.bytes().await?.into();
+        let bytes = get_bytes(&MEDIA_LIMITER, url).await?.into();
 
         trace!("Saving to {original_path:?}");
         std::fs::create_dir_all(original_path.parent().unwrap())?;
@@ -132,7 +142,7 @@ pub async fn fetch_sample(id: u32, file: Sample) -> Result<Handle, MediaError> {
 
     let url = file.url.as_ref().ok_or(MediaError::MissingUrl)?;
     trace!("Getting post {id} from server ({url})");
-    let bytes: Bytes = CLIENT.get(url).send().await?.bytes().await?;
+    let bytes: Bytes = get_bytes(&MEDIA_LIMITER, url).await?;
     trace!("Saving to {path:?}");
     std::fs::create_dir_all(path.parent().unwrap())?;
     std::fs::write(&path, &bytes)?;
@@ -149,7 +159,7 @@ pub async fn fetch_gif(id: u32, url: String) -> Result<Vec<u8>, MediaError> {
         std::fs::read(file_path)?
     } else {
         trace!("Getting {id} from server ({url})");
-        let bytes = CLIENT.get(url).send().await?.bytes().await?.into();
+        let bytes = get_bytes(&MEDIA_LIMITER, &url).await?.into();
 
         debug!("Saving to {file_path:?}");
         std::fs::create_dir_all(file_path.parent().unwrap())?;
@@ -167,7 +177,7 @@ pub async fn fetch_video(id: u32, url: String, ext: String) -> Result<Url, Media
         trace!("Loading {id} from cache ({file_path:?})");
     } else {
         trace!("Getting {id} from server ({url})");
-        let bytes = CLIENT.get(url).send().await?.bytes().await?;
+        let bytes = get_bytes(&MEDIA_LIMITER, &url).await?;
 
         trace!("Saving to {file_path:?}");
         std::fs::create_dir_all(&file_path.parent().unwrap())?;
@@ -257,7 +267,19 @@ pub fn build_video_pipeline(uri: &str) -> Result<Video, anyhow::Error> {
     Ok(video)
 }
 
+/// Replaces [`cache_dir`], like for tests that mustn't touch the real cache.
+static CACHE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Keeps downloaded media in `dir` from now on.
+pub fn set_cache_dir(dir: PathBuf) {
+    *CACHE_DIR.write().expect("cache dir poisoned!") = Some(dir);
+}
+
+/// Where downloaded media is kept.
 pub fn cache_dir() -> PathBuf {
+    if let Some(dir) = CACHE_DIR.read().expect("cache dir poisoned!").clone() {
+        return dir;
+    }
     ProjectDirs::from("xyz", "stripywalrus", "msg")
         .unwrap()
         .cache_dir()
@@ -0,0 +1,263 @@
+//! Downloaded media on disk, with recently used files also kept in memory.
+
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+
+use iced::widget::image::Handle;
+use rustc_hash::FxHashMap;
+use tracing::trace;
+
+/// Memory for media by default, in MiB.
+pub const DEFAULT_MEMORY_BUDGET_MB: usize = 256;
+
+/// Kinds of images kept for a post.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaKind {
+    Thumbnail,
+    Sample,
+    Image,
+}
+
+#[derive(Debug)]
+struct Loaded {
+    handle: Handle,
+    size: usize,
+    /// When it was last shown or inserted, by the cache's clock.
+    last_used: Cell<u64>,
+}
+
+/// Thumbnails, samples and images for posts, kept as files on disk.
+///
+/// Media is only ever read from disk when it's drawn, through [`Handle::from_path`]. Freshly
+/// downloaded media is kept in memory instead, up to a budget shared by all kinds, after which
+/// the least recently used is dropped back to its file.
+#[derive(Debug)]
+pub struct MediaCache {
+    thumbnails: FxHashMap<u32, PathBuf>,
+    samples: FxHashMap<u32, PathBuf>,
+    images: FxHashMap<u32, PathBuf>,
+    loaded: FxHashMap<(MediaKind, u32), Loaded>,
+    /// Bytes held by `loaded`.
+    used: usize,
+    budget: usize,
+    /// Counts uses, to tell which media was used least recently.
+    clock: Cell<u64>,
+}
+
+impl Default for MediaCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_BUDGET_MB * 1024 * 1024)
+    }
+}
+
+impl MediaCache {
+    /// An empty cache holding at most `budget` bytes of media in memory.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            thumbnails: FxHashMap::default(),
+            samples: FxHashMap::default(),
+            images: FxHashMap::default(),
+            loaded: FxHashMap::default(),
+            used: 0,
+            budget,
+            clock: Cell::new(0),
+        }
+    }
+
+    fn paths(&self, kind: MediaKind) -> &FxHashMap<u32, PathBuf> {
+        match kind {
+            MediaKind::Thumbnail => &self.thumbnails,
+            MediaKind::Sample => &self.samples,
+            MediaKind::Image => &self.images,
+        }
+    }
+
+    fn paths_mut(&mut self, kind: MediaKind) -> &mut FxHashMap<u32, PathBuf> {
+        match kind {
+            MediaKind::Thumbnail => &mut self.thumbnails,
+            MediaKind::Sample => &mut self.samples,
+            MediaKind::Image => &mut self.images,
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.set(self.clock.get() + 1);
+        self.clock.get()
+    }
+
+    /// Records that post `id`'s media is at `path`, without reading it.
+    pub fn insert_path(&mut self, kind: MediaKind, id: u32, path: PathBuf) {
+        self.paths_mut(kind).insert(id, path);
+    }
+
+    /// Keeps `handle` in memory as post `id`'s media, which is also saved at `path`.
+    pub fn insert(&mut self, kind: MediaKind, id: u32, path: PathBuf, handle: Handle) {
+        self.insert_path(kind, id, path);
+        let size = handle_size(&handle);
+        let loaded = Loaded {
+            handle,
+            size,
+            last_used: Cell::new(self.tick()),
+        };
+        if let Some(old) = self.loaded.insert((kind, id), loaded) {
+            self.used -= old.size;
+        }
+        self.used += size;
+        self.evict();
+    }
+
+    /// Drops the least recently used media from memory until it fits the budget.
+    fn evict(&mut self) {
+        while self.used > self.budget && self.loaded.len() > 1 {
+            let Some(&key) = self
+                .loaded
+                .iter()
+                .min_by_key(|(_, loaded)| loaded.last_used.get())
+                .map(|(key, _)| key)
+            else {
+                break;
+            };
+            if let Some(loaded) = self.loaded.remove(&key) {
+                trace!("Dropping {key:?} from memory");
+                self.used -= loaded.size;
+            }
+        }
+    }
+
+    /// Post `id`'s media: from memory if it's there, otherwise a handle that reads its file when
+    /// drawn.
+    pub fn get(&self, kind: MediaKind, id: u32) -> Option<Handle> {
+        if let Some(loaded) = self.loaded.get(&(kind, id)) {
+            loaded.last_used.set(self.tick());
+            return Some(loaded.handle.clone());
+        }
+        self.paths(kind).get(&id).map(Handle::from_path)
+    }
+
+    /// Where post `id`'s media is on disk.
+    pub fn path(&self, kind: MediaKind, id: u32) -> Option<&Path> {
+        self.paths(kind).get(&id).map(PathBuf::as_path)
+    }
+
+    pub fn contains(&self, kind: MediaKind, id: u32) -> bool {
+        self.paths(kind).contains_key(&id)
+    }
+
+    /// Files of `kind` on disk, by post.
+    pub fn files(&self, kind: MediaKind) -> impl Iterator<Item = (u32, &Path)> {
+        self.paths(kind)
+            .iter()
+            .map(|(&id, path)| (id, path.as_path()))
+    }
+
+    pub fn len(&self, kind: MediaKind) -> usize {
+        self.paths(kind).len()
+    }
+
+    /// Forgets media for posts that aren't kept.
+    pub fn retain(&mut self, keep: impl Fn(u32) -> bool) {
+        for kind in [MediaKind::Thumbnail, MediaKind::Sample, MediaKind::Image] {
+            self.paths_mut(kind).retain(|&id, _| keep(id));
+        }
+        self.loaded.retain(|&(_, id), _| keep(id));
+        self.used = self.loaded.values().map(|loaded| loaded.size).sum();
+    }
+
+    /// Forgets all media for post `id`.
+    pub fn remove(&mut self, id: u32) {
+        for kind in [MediaKind::Thumbnail, MediaKind::Sample, MediaKind::Image] {
+            self.paths_mut(kind).remove(&id);
+            if let Some(loaded) = self.loaded.remove(&(kind, id)) {
+                self.used -= loaded.size;
+            }
+        }
+    }
+
+    /// Bytes of media held in memory.
+    pub fn memory_used(&self) -> usize {
+        self.used
+    }
+
+    pub fn set_memory_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+}
+
+/// Memory held by a handle's data. Handles to files hold none until they're drawn.
+fn handle_size(handle: &Handle) -> usize {
+    match handle {
+        Handle::Path(..) => 0,
+        Handle::Bytes(_, bytes) => bytes.len(),
+        Handle::Rgba { pixels, .. } => pixels.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(size: usize) -> Handle {
+        Handle::from_bytes(vec![0; size])
+    }
+
+    fn in_memory(cache: &MediaCache, id: u32) -> bool {
+        matches!(cache.get(MediaKind::Thumbnail, id), Some(Handle::Bytes(..)))
+    }
+
+    #[test]
+    fn least_recently_used_media_is_dropped_to_disk() {
+        let mut cache = MediaCache::new(300);
+        for id in 1..=3 {
+            cache.insert(
+                MediaKind::Thumbnail,
+                id,
+                format!("{id}.jpg").into(),
+                image(100),
+            );
+        }
+        // Showing 1 makes 2 the least recently used.
+        assert!(in_memory(&cache, 1));
+        cache.insert(MediaKind::Sample, 1, "1-sample.jpg".into(), image(100));
+
+        assert_eq!(cache.memory_used(), 300);
+        assert!(in_memory(&cache, 1));
+        assert!(!in_memory(&cache, 2));
+        assert!(in_memory(&cache, 3));
+        // Still on disk.
+        assert_eq!(
+            cache.get(MediaKind::Thumbnail, 2),
+            Some(Handle::from_path("2.jpg"))
+        );
+    }
+
+    #[test]
+    fn paths_are_not_read_or_counted() {
+        let mut cache = MediaCache::new(0);
+        cache.insert_path(MediaKind::Image, 5, "5.png".into());
+
+        assert!(cache.contains(MediaKind::Image, 5));
+        assert!(!cache.contains(MediaKind::Thumbnail, 5));
+        assert_eq!(cache.memory_used(), 0);
+        assert_eq!(
+            cache.get(MediaKind::Image, 5),
+            Some(Handle::from_path("5.png"))
+        );
+    }
+
+    #[test]
+    fn shrinking_the_budget_evicts_and_retain_forgets() {
+        let mut cache = MediaCache::new(1000);
+        cache.insert(MediaKind::Thumbnail, 1, "1.jpg".into(), image(400));
+        cache.insert(MediaKind::Thumbnail, 2, "2.jpg".into(), image(400));
+
+        cache.set_memory_budget(500);
+        assert_eq!(cache.memory_used(), 400);
+        assert!(in_memory(&cache, 2));
+
+        cache.retain(|id| id != 2);
+        assert_eq!(cache.memory_used(), 0);
+        assert_eq!(cache.get(MediaKind::Thumbnail, 2), None);
+        assert_eq!(cache.len(MediaKind::Thumbnail), 1);
+    }
+}
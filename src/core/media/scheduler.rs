@@ -0,0 +1,266 @@
+//! Deciding which thumbnails to download, and when.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use tracing::{trace, warn};
+
+/// Thumbnails downloaded at once.
+pub const THUMBNAIL_CONCURRENCY: usize = 6;
+/// Tries at a thumbnail before giving up on it.
+const MAX_ATTEMPTS: u32 = 4;
+/// Delay before the first retry, doubled for each one after.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// A thumbnail download to start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThumbnailJob {
+    pub id: u32,
+    pub url: String,
+}
+
+#[derive(Debug)]
+struct Entry {
+    job: ThumbnailJob,
+    /// Views that want the thumbnail, like `search:wolf` or `followed`. It's dropped once none
+    /// of them do.
+    owners: Vec<String>,
+    /// Retries wait until this.
+    not_before: Option<Instant>,
+}
+
+/// Downloads thumbnails a few at a time, tiles on screen first, and each post once however many
+/// views show it. Failed downloads are tried again after a while.
+///
+/// This only keeps the books: the app starts the jobs from [`next_jobs`](Self::next_jobs) and
+/// reports back with [`finished`](Self::finished).
+#[derive(Debug)]
+pub struct ThumbnailScheduler {
+    concurrency: usize,
+    waiting: FxHashMap<u32, Entry>,
+    /// Posts in `waiting`, in the order they were queued.
+    queue: VecDeque<u32>,
+    in_flight: FxHashMap<u32, Entry>,
+    /// Failed tries for each post, until it's downloaded or given up on.
+    failures: FxHashMap<u32, u32>,
+    /// Posts on screen, downloaded before anything else.
+    visible: FxHashSet<u32>,
+}
+
+impl Default for ThumbnailScheduler {
+    fn default() -> Self {
+        Self::new(THUMBNAIL_CONCURRENCY)
+    }
+}
+
+impl ThumbnailScheduler {
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            waiting: FxHashMap::default(),
+            queue: VecDeque::new(),
+            in_flight: FxHashMap::default(),
+            failures: FxHashMap::default(),
+            visible: FxHashSet::default(),
+        }
+    }
+
+    /// Queues post `id`'s thumbnail for `owner`. One that's already queued or downloading isn't
+    /// queued again, but `owner` is added to it.
+    pub fn enqueue(&mut self, id: u32, url: String, owner: &str) {
+        let existing = match self.in_flight.get_mut(&id) {
+            Some(entry) => Some(entry),
+            None => self.waiting.get_mut(&id),
+        };
+        if let Some(entry) = existing {
+            if !entry.owners.iter().any(|o| o == owner) {
+                entry.owners.push(owner.to_string());
+            }
+            return;
+        }
+
+        trace!("Queueing thumbnail for {id}");
+        self.waiting.insert(
+            id,
+            Entry {
+                job: ThumbnailJob { id, url },
+                owners: vec![owner.to_string()],
+                not_before: None,
+            },
+        );
+        self.queue.push_back(id);
+    }
+
+    /// Puts the posts now on screen first in line.
+    pub fn set_visible(&mut self, ids: impl IntoIterator<Item = u32>) {
+        self.visible = ids.into_iter().collect();
+    }
+
+    /// Stops waiting on thumbnails for owners that aren't kept, like a search the user left.
+    /// Thumbnails another owner still wants stay queued. Downloads already started finish, since
+    /// they're cached either way, but aren't retried.
+    pub fn retain_owners(&mut self, keep: impl Fn(&str) -> bool) {
+        for entry in self.waiting.values_mut().chain(self.in_flight.values_mut()) {
+            entry.owners.retain(|owner| keep(owner));
+        }
+        let before = self.waiting.len();
+        self.waiting.retain(|_, entry| !entry.owners.is_empty());
+        self.queue.retain(|id| self.waiting.contains_key(id));
+        if self.waiting.len() < before {
+            trace!("Cancelled {} thumbnails", before - self.waiting.len());
+        }
+    }
+
+    /// Drops waiting thumbnails for posts that aren't kept, like ones just blacklisted.
+    pub fn retain_posts(&mut self, keep: impl Fn(u32) -> bool) {
+        self.waiting.retain(|&id, _| keep(id));
+        self.queue.retain(|id| self.waiting.contains_key(id));
+    }
+
+    /// Downloads to start now, keeping at most the scheduler's concurrency going at once.
+    pub fn next_jobs(&mut self, now: Instant) -> Vec<ThumbnailJob> {
+        let mut jobs = Vec::new();
+        while self.in_flight.len() < self.concurrency {
+            let ready = |id: &u32| self.waiting[id].not_before.is_none_or(|at| at <= now);
+            let next = self
+                .queue
+                .iter()
+                .position(|id| ready(id) && self.visible.contains(id))
+                .or_else(|| self.queue.iter().position(ready));
+            let Some(id) = next.and_then(|i| self.queue.remove(i)) else {
+                break;
+            };
+            let entry = self.waiting.remove(&id).expect("queued posts are waiting");
+            jobs.push(entry.job.clone());
+            self.in_flight.insert(id, entry);
+        }
+        jobs
+    }
+
+    /// Records a finished download. A failed one is queued again after a delay, which is
+    /// returned so the app can come back for it, until it's failed too often.
+    pub fn finished(&mut self, id: u32, succeeded: bool, now: Instant) -> Option<Duration> {
+        let mut entry = self.in_flight.remove(&id)?;
+        if succeeded {
+            self.failures.remove(&id);
+            return None;
+        }
+
+        let failures = self.failures.entry(id).or_default();
+        *failures += 1;
+        if *failures >= MAX_ATTEMPTS {
+            warn!("Giving up on thumbnail for {id} after {failures} tries");
+            self.failures.remove(&id);
+            return None;
+        }
+        if entry.owners.is_empty() {
+            return None;
+        }
+
+        let delay = RETRY_DELAY * 2u32.pow(*failures - 1);
+        trace!("Retrying thumbnail for {id} in {delay:?}");
+        entry.not_before = Some(now + delay);
+        self.waiting.insert(id, entry);
+        self.queue.push_back(id);
+        Some(delay)
+    }
+
+    /// Whether post `id`'s thumbnail is queued or downloading.
+    pub fn contains(&self, id: u32) -> bool {
+        self.in_flight.contains_key(&id) || self.waiting.contains_key(&id)
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(jobs: &[ThumbnailJob]) -> Vec<u32> {
+        jobs.iter().map(|job| job.id).collect()
+    }
+
+    fn scheduler_with(concurrency: usize, posts: &[u32], owner: &str) -> ThumbnailScheduler {
+        let mut scheduler = ThumbnailScheduler::new(concurrency);
+        for &id in posts {
+            scheduler.enqueue(id, format!("https://static/{id}.jpg"), owner);
+        }
+        scheduler
+    }
+
+    #[test]
+    fn concurrency_is_bounded_and_visible_posts_go_first() {
+        let now = Instant::now();
+        let mut scheduler = scheduler_with(2, &[1, 2, 3, 4, 5], "search:a");
+        scheduler.set_visible([4, 5]);
+
+        assert_eq!(ids(&scheduler.next_jobs(now)), vec![4, 5]);
+        assert!(scheduler.next_jobs(now).is_empty());
+
+        scheduler.finished(4, true, now);
+        assert_eq!(ids(&scheduler.next_jobs(now)), vec![1]);
+        assert_eq!(scheduler.in_flight(), 2);
+        assert_eq!(scheduler.waiting(), 2);
+    }
+
+    #[test]
+    fn posts_are_queued_once_and_cancelled_per_owner() {
+        let now = Instant::now();
+        let mut scheduler = scheduler_with(1, &[1, 2, 3], "search:a");
+        scheduler.enqueue(2, "https://static/2.jpg".into(), "followed");
+        assert_eq!(scheduler.waiting(), 3);
+
+        assert_eq!(ids(&scheduler.next_jobs(now)), vec![1]);
+        scheduler.enqueue(1, "https://static/1.jpg".into(), "followed");
+        assert_eq!(scheduler.waiting(), 2);
+
+        // Leaving the search keeps what the followed view still wants.
+        scheduler.retain_owners(|owner| owner != "search:a");
+        assert!(scheduler.contains(1));
+        assert!(scheduler.contains(2));
+        assert!(!scheduler.contains(3));
+    }
+
+    #[test]
+    fn failures_are_retried_with_backoff_then_dropped() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_with(4, &[7], "search:a");
+
+        let mut now = start;
+        let mut delays = Vec::new();
+        loop {
+            assert_eq!(ids(&scheduler.next_jobs(now)), vec![7]);
+            match scheduler.finished(7, false, now) {
+                Some(delay) => {
+                    // Not ready until the delay is up.
+                    assert!(scheduler.next_jobs(now).is_empty());
+                    now += delay;
+                    delays.push(delay);
+                }
+                None => break,
+            }
+        }
+
+        assert_eq!(delays, vec![RETRY_DELAY, RETRY_DELAY * 2, RETRY_DELAY * 4]);
+        assert!(!scheduler.contains(7));
+    }
+
+    #[test]
+    fn cancelled_downloads_are_not_retried() {
+        let now = Instant::now();
+        let mut scheduler = scheduler_with(1, &[1], "search:a");
+        scheduler.next_jobs(now);
+        scheduler.retain_owners(|_| false);
+
+        assert_eq!(scheduler.finished(1, false, now), None);
+        assert!(!scheduler.contains(1));
+    }
+}
@@ -1,8 +1,13 @@
 pub mod api;
+pub mod autocomplete;
 pub mod blacklist;
 pub mod config;
+pub mod dtext;
+#[cfg(any(test, feature = "test-util"))]
+pub mod fixture;
 pub mod followed;
 pub mod http;
+pub mod keymap;
 pub mod media;
 pub mod model;
 pub mod store;
@@ -12,11 +12,14 @@ const fn _default_0u32() -> u32 {
 }
 
 /// Represents a post on e621.
+///
+/// Stored posts are encoded positionally, so new fields must go at the end and have a default
+/// for older stores (and older API responses) that don't include them.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Post {
     pub id: u32,
-    created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
     pub file: File,
     pub preview: Preview,
     pub sample: Sample,
@@ -26,13 +29,37 @@ pub struct Post {
     pub is_favorited: bool,
     #[serde(default = "_default_0u32")]
     pub fav_count: u32,
-    //pub sources: Vec<String>,
-    //pub pools: Vec<u32>,
-    //relationships: Relationships,
     pub description: String,
+    #[serde(default)]
+    pub uploader_id: Option<u32>,
+    #[serde(default)]
+    pub uploader_name: Option<String>,
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub pools: Vec<u32>,
+    #[serde(default)]
+    pub relationships: Relationships,
+    /// Tags that can't be changed without moderator approval.
+    #[serde(default)]
+    pub locked_tags: Vec<String>,
+    /// Incremented on every change to the post.
+    #[serde(default)]
+    pub change_seq: u64,
+    #[serde(default)]
+    pub flags: Flags,
+    #[serde(default)]
+    pub approver_id: Option<u32>,
+    /// Length in seconds, for videos.
+    #[serde(default)]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub comment_count: u32,
+    #[serde(default)]
+    pub has_notes: bool,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum Rating {
     #[serde(rename = "s")]
     Safe,
@@ -46,22 +73,44 @@ pub enum Rating {
 pub struct File {
     pub ext: Option<String>,
     pub url: Option<String>,
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32,
+    /// Size in bytes.
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub md5: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Preview {
     pub url: Option<String>,
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Sample {
     pub has: bool,
     pub url: Option<String>,
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Score {
     pub total: i32,
+    #[serde(default)]
+    pub up: i32,
+    /// Number of downvotes, as a negative number.
+    #[serde(default)]
+    pub down: i32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,12 +123,35 @@ pub struct Tags {
     pub invalid: Vec<String>,
     pub meta: Vec<String>,
     pub lore: Vec<String>,
+    #[serde(default)]
+    pub contributor: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Relationships {
+    pub parent_id: Option<u32>,
+    pub has_children: bool,
+    pub has_active_children: bool,
+    pub children: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Flags {
+    pub pending: bool,
+    pub flagged: bool,
+    pub note_locked: bool,
+    pub status_locked: bool,
+    pub rating_locked: bool,
+    pub deleted: bool,
 }
 
 impl Tags {
     pub fn iter(&self) -> impl Iterator<Item = (&'static str, &Vec<String>)> {
         [
             ("artist", &self.artist),
+            ("contributor", &self.contributor),
             ("copyright", &self.copyright),
             ("character", &self.character),
             ("species", &self.species),
@@ -166,7 +238,7 @@ pub struct Comment {
     pub body: String,
     pub score: i32,
     pub created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 impl PartialEq for Comment {
@@ -174,3 +246,132 @@ impl PartialEq for Comment {
         self.id == other.id
     }
 }
+
+/// Represents a pool, an ordered set of posts such as the pages of a comic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pool {
+    pub id: u32,
+    /// Name with underscores for spaces, see [`Pool::display_name`].
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub creator_id: Option<u32>,
+    #[serde(default)]
+    pub creator_name: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub is_active: bool,
+    #[serde(default)]
+    pub category: PoolCategory,
+    /// Posts in reading order.
+    #[serde(default)]
+    pub post_ids: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoolCategory {
+    /// Pages meant to be read in order.
+    #[default]
+    Series,
+    Collection,
+}
+
+impl Pool {
+    pub fn display_name(&self) -> String {
+        self.name.replace('_', " ")
+    }
+
+    /// Where `post_id` is in the pool, starting from 0.
+    pub fn position(&self, post_id: u32) -> Option<usize> {
+        self.post_ids.iter().position(|&id| id == post_id)
+    }
+}
+
+/// A suggestion from e621's tag autocomplete.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagSuggestion {
+    pub id: u32,
+    pub name: String,
+    pub post_count: u32,
+    pub category: TagCategory,
+    /// Set when `name` was suggested through an alias, to the alias that matched.
+    #[serde(default)]
+    pub antecedent_name: Option<String>,
+}
+
+/// Tag categories, by their ID in the e621 API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum TagCategory {
+    #[default]
+    General,
+    Artist,
+    Contributor,
+    Copyright,
+    Character,
+    Species,
+    Invalid,
+    Meta,
+    Lore,
+}
+
+impl From<u8> for TagCategory {
+    fn from(id: u8) -> Self {
+        match id {
+            1 => TagCategory::Artist,
+            2 => TagCategory::Contributor,
+            3 => TagCategory::Copyright,
+            4 => TagCategory::Character,
+            5 => TagCategory::Species,
+            6 => TagCategory::Invalid,
+            7 => TagCategory::Meta,
+            8 => TagCategory::Lore,
+            _ => TagCategory::General,
+        }
+    }
+}
+
+impl From<TagCategory> for u8 {
+    fn from(category: TagCategory) -> Self {
+        category as u8
+    }
+}
+
+/// Represents a wiki page, which describes a tag.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WikiPage {
+    pub id: u32,
+    /// Name of the tag the page describes.
+    pub title: String,
+    /// Page contents, in DText.
+    #[serde(default)]
+    pub body: String,
+    /// Alternate names, often translations.
+    #[serde(default)]
+    pub other_names: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub is_locked: bool,
+}
+
+/// A tag alias or implication, from `antecedent_name` to `consequent_name`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagRelation {
+    pub id: u32,
+    pub antecedent_name: String,
+    pub consequent_name: String,
+    #[serde(default)]
+    pub status: String,
+}
+
+/// A tag that often appears alongside another.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelatedTag {
+    pub name: String,
+    #[serde(default)]
+    pub category: TagCategory,
+}
@@ -1,25 +1,44 @@
 use std::{
     fs,
-    io::{BufReader, BufWriter},
+    io::{self, BufWriter, ErrorKind, Write},
     path::{Path, PathBuf},
-    str::FromStr,
 };
 
+use chrono::{DateTime, TimeDelta, Utc};
 use directories::ProjectDirs;
 use iced::widget::image::Handle;
 use iced_gif::Frames;
 use rmp_serde::Serializer;
 use rustc_hash::{FxHashMap, FxHashSet};
 use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
 use thiserror::Error;
 use tracing::{info, trace, warn};
 use url::Url;
 
 use super::{
-    media::{gif_dir, image_dir, sample_dir, thumbnail_dir},
-    model::{Comment, Post, PostType, Vote},
+    blacklist::CompiledBlacklist,
+    media::{gif_dir, image_dir, sample_dir, thumbnail_dir, MediaCache, MediaKind},
+    model::{Comment, Post, PostType, TagSuggestion, Vote},
 };
 
+mod journal;
+mod migrate;
+mod writer;
+
+use journal::{journal_path, JournalEntry, PostRecord};
+pub use migrate::STORE_VERSION;
+pub use writer::StoreWriter;
+
+/// Most autocomplete prefixes to remember.
+const SUGGESTION_CACHE_SIZE: usize = 500;
+/// How long autocomplete results are reused before asking e621 again.
+const SUGGESTION_TTL: TimeDelta = TimeDelta::days(1);
+/// How long a post's comments are shown before fetching them again.
+const COMMENT_TTL: TimeDelta = TimeDelta::minutes(15);
+/// Journal size past which a flush writes a new snapshot instead.
+const COMPACT_AFTER: u64 = 16 * 1024 * 1024;
+
 #[derive(Debug, Error)]
 pub enum StoreError {
     #[error("IO error: {0}")]
@@ -30,24 +49,41 @@ pub enum StoreError {
 
     #[error("RMP decoding error: {0}")]
     RmpDecodeError(#[from] rmp_serde::decode::Error),
+
+    #[error("unknown store version {0}, maybe from a newer msg")]
+    UnknownVersion(u32),
+
+    #[error("unreadable journal entry at byte {offset}: {source}")]
+    JournalEntry {
+        offset: usize,
+        source: rmp_serde::decode::Error,
+    },
     //#[error("Voting error: {0}")]
     //VoteError(String),
 }
 
+impl StoreError {
+    /// Whether the store's contents couldn't be made sense of, as opposed to couldn't be read. Only
+    /// then is the file worth setting aside; reading it again later may work otherwise. A store
+    /// from a newer msg isn't undecodable, just not for this version.
+    pub fn is_undecodable(&self) -> bool {
+        matches!(
+            self,
+            StoreError::RmpDecodeError(_) | StoreError::JournalEntry { .. }
+        )
+    }
+}
+
 /// Stores media for posts.
 #[derive(Debug, Default)]
 pub struct PostStore {
     /// List of [`Post`]s.
     pub posts: FxHashMap<u32, Post>,
-    /// Post thumbnails, stored as [Handle]s of image data.
-    pub thumbnails: FxHashMap<u32, Handle>,
-    /// Post sample images, stored as [Handle]s of data. Essentially a lower-res original.
-    pub samples: FxHashMap<u32, Handle>,
-    /// Post images, stored as [Handle]s of image data.
-    pub images: FxHashMap<u32, Handle>,
-    /// Post GIFs, stored as raw bytes.
-    pub gifs: FxHashMap<u32, Vec<u8>>,
-    /// Post GIFs, stored as [Frames].
+    /// Post thumbnails, samples and images, read from disk as they're shown.
+    pub media: MediaCache,
+    /// Post GIFs, stored as the location on disk.
+    pub gifs: FxHashMap<u32, PathBuf>,
+    /// Post GIFs decoded this session, stored as [Frames].
     pub gif_frames: FxHashMap<u32, Frames>,
     /// Post videos, stored as the location on disk (as a [`Url`]).
     pub videos: FxHashMap<u32, Url>,
@@ -58,14 +94,92 @@ pub struct PostStore {
     /// List of posts (by ID) that have been favorited.
     pub favorites: FxHashSet<u32>,
 
-    /// Stored comments. Currently not kept across sessions.
-    pub comments: FxHashMap<u32, Vec<Comment>>,
+    /// Comments for each post, oldest first.
+    pub comments: FxHashMap<u32, CachedComments>,
+    /// Votes on comments, by comment ID. Like `votes`, only ones made within MSG.
+    pub comment_votes: FxHashMap<u32, Vote>,
 
     /// Stored results for queries. Not kept across sessions.
     pub results: FxHashMap<String, Vec<u32>>,
+
+    /// Posts hidden by the blacklist for each query, with the rule that hid them.
+    /// Not kept across sessions.
+    pub hidden: FxHashMap<String, PostList<String>>,
+
+    /// Every post fetched for each query in the order it arrived, blacklisted or not.
+    /// Used to rebuild `results` and `hidden` when the blacklist changes. Not kept across sessions.
+    pub fetched: FxHashMap<String, PostList<()>>,
+
+    /// Recent tag autocomplete results for each prefix, so typing doesn't repeat requests.
+    pub tag_suggestions: FxHashMap<String, CachedSuggestions>,
+
+    /// Posts changed since the last [`flush`](Self::flush). Downloaded media alone doesn't count,
+    /// since it's found again from the post's ID and saved with the next snapshot anyway.
+    dirty: FxHashSet<u32>,
+    /// Comments voted on since the last flush.
+    dirty_comment_votes: FxHashSet<u32>,
+    /// Posts purged since the last flush.
+    removed: FxHashSet<u32>,
+}
+
+/// Tag autocomplete results, and when they were fetched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedSuggestions {
+    pub fetched_at: DateTime<Utc>,
+    pub tags: Vec<TagSuggestion>,
+}
+
+/// A post's comments, and when they were fetched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedComments {
+    pub fetched_at: DateTime<Utc>,
+    pub comments: Vec<Comment>,
+}
+
+/// Posts in the order they were first added, each once, with a `T` for each. Looking a post up
+/// doesn't scan the list, so it stays cheap however far a search is scrolled.
+#[derive(Debug)]
+pub struct PostList<T> {
+    entries: Vec<(u32, T)>,
+    /// Where each post is in `entries`.
+    index: FxHashMap<u32, usize>,
+}
+
+impl<T> Default for PostList<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            index: FxHashMap::default(),
+        }
+    }
+}
+
+impl<T> PostList<T> {
+    /// Adds post `id` at the end, or replaces its value where it already is.
+    pub fn insert(&mut self, id: u32, value: T) {
+        match self.index.get(&id) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.index.insert(id, self.entries.len());
+                self.entries.push((id, value));
+            }
+        }
+    }
+
+    pub fn entries(&self) -> &[(u32, T)] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
 }
 
-/// Used for serializing [`PostStore`]s.
+/// Used for serializing [`PostStore`]s, as of [`STORE_VERSION`].
 #[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct PostStoreData {
@@ -80,13 +194,18 @@ pub struct PostStoreData {
     /// List of paths to GIFs stored on disk.
     pub gifs: FxHashMap<u32, PathBuf>,
     /// List of paths to videos stored on disk.
-    /// TODO: Make this PathBuf like the rest.
-    pub videos: FxHashMap<u32, String>,
+    pub videos: FxHashMap<u32, PathBuf>,
     /// Stored votes.
     /// Note that the e6 API has no way to see previous votes your account has made, so these are only votes made within MSG.
     pub votes: FxHashMap<u32, bool>,
     /// List of posts (by ID) that have been favorited.
     pub favorites: FxHashSet<u32>,
+    /// Recent tag autocomplete results.
+    pub tag_suggestions: FxHashMap<String, CachedSuggestions>,
+    /// Comments for posts in `posts`.
+    pub comments: FxHashMap<u32, CachedComments>,
+    /// Votes on comments, `true` for upvotes.
+    pub comment_votes: FxHashMap<u32, bool>,
 }
 
 impl PostStore {
@@ -99,6 +218,7 @@ impl PostStore {
     // --- Posts ---
 
     pub fn insert_post(&mut self, post: Post) {
+        self.dirty.insert(post.id);
         self.set_favorite(post.id, post.is_favorited);
         self.posts.insert(post.id, post);
     }
@@ -114,15 +234,41 @@ impl PostStore {
     }
 
     pub fn get_post_mut(&mut self, id: u32) -> Option<&mut Post> {
+        self.dirty.insert(id);
         self.posts.get_mut(&id)
     }
 
     // --- Comments ---
+
+    /// Replaces all of a post's comments with a fresh fetch.
+    pub fn set_comments(&mut self, post_id: u32, mut comments: Vec<Comment>) {
+        comments.sort_by_key(|comment| comment.id);
+        comments.dedup_by_key(|comment| comment.id);
+        self.dirty.insert(post_id);
+        self.comments.insert(
+            post_id,
+            CachedComments {
+                fetched_at: Utc::now(),
+                comments,
+            },
+        );
+    }
+
+    /// Adds a comment, or replaces the one with the same ID.
     pub fn insert_comment(&mut self, comment: Comment) {
-        self.comments
+        self.dirty.insert(comment.post_id);
+        // A post without fetched comments stays due for a refresh.
+        let cached = self
+            .comments
             .entry(comment.post_id)
-            .or_insert(Vec::new())
-            .push(comment);
+            .or_insert_with(|| CachedComments {
+                fetched_at: DateTime::UNIX_EPOCH,
+                comments: Vec::new(),
+            });
+        match cached.comments.binary_search_by_key(&comment.id, |c| c.id) {
+            Ok(index) => cached.comments[index] = comment,
+            Err(index) => cached.comments.insert(index, comment),
+        }
     }
 
     pub fn insert_comments(&mut self, comments: impl IntoIterator<Item = Comment>) {
@@ -132,12 +278,41 @@ impl PostStore {
     }
 
     pub fn get_comments(&self, id: u32) -> Option<&Vec<Comment>> {
-        self.comments.get(&id)
+        self.comments.get(&id).map(|cached| &cached.comments)
+    }
+
+    pub fn get_comment_mut(&mut self, post_id: u32, id: u32) -> Option<&mut Comment> {
+        self.dirty.insert(post_id);
+        self.comments
+            .get_mut(&post_id)?
+            .comments
+            .iter_mut()
+            .find(|comment| comment.id == id)
+    }
+
+    /// Whether a post's comments were never fetched, or were fetched too long ago.
+    pub fn comments_need_refresh(&self, post_id: u32) -> bool {
+        self.comments
+            .get(&post_id)
+            .is_none_or(|cached| Utc::now() - cached.fetched_at >= COMMENT_TTL)
+    }
+
+    pub fn set_comment_vote(&mut self, id: u32, vote: Option<Vote>) {
+        self.dirty_comment_votes.insert(id);
+        match vote {
+            Some(v) => self.comment_votes.insert(id, v),
+            None => self.comment_votes.remove(&id),
+        };
+    }
+
+    pub fn comment_vote_for(&self, id: u32) -> Option<Vote> {
+        self.comment_votes.get(&id).copied()
     }
 
     // --- Votes ---
 
     pub fn set_vote(&mut self, post_id: u32, vote: Option<Vote>) {
+        self.dirty.insert(post_id);
         match vote {
             Some(v) => {
                 self.votes.insert(post_id, v);
@@ -160,6 +335,7 @@ impl PostStore {
     }
 
     pub fn set_favorite(&mut self, id: u32, favorited: bool) {
+        self.dirty.insert(id);
         if favorited {
             self.favorites.insert(id);
         } else {
@@ -170,11 +346,12 @@ impl PostStore {
     // --- Thumbnails ---
 
     pub fn insert_thumbnail(&mut self, id: u32, handle: Handle) {
-        self.thumbnails.insert(id, handle);
+        let path = self.get_thumbnail_path(id);
+        self.media.insert(MediaKind::Thumbnail, id, path, handle);
     }
 
-    pub fn get_thumbnail(&self, id: u32) -> Option<&Handle> {
-        self.thumbnails.get(&id)
+    pub fn get_thumbnail(&self, id: u32) -> Option<Handle> {
+        self.media.get(MediaKind::Thumbnail, id)
     }
 
     pub fn get_thumbnail_path(&self, id: u32) -> PathBuf {
@@ -183,11 +360,12 @@ impl PostStore {
 
     // --- Samples ---
     pub fn insert_sample(&mut self, id: u32, handle: Handle) {
-        self.samples.insert(id, handle);
+        let path = self.get_sample_path(id);
+        self.media.insert(MediaKind::Sample, id, path, handle);
     }
 
-    pub fn get_sample(&self, id: u32) -> Option<&Handle> {
-        self.samples.get(&id)
+    pub fn get_sample(&self, id: u32) -> Option<Handle> {
+        self.media.get(MediaKind::Sample, id)
     }
 
     pub fn get_sample_path(&self, id: u32) -> PathBuf {
@@ -198,11 +376,12 @@ impl PostStore {
 
     pub fn insert_image(&mut self, id: u32, handle: Handle) {
         trace!(post_id = id, "Inserting image into store");
-        self.images.insert(id, handle);
+        let path = self.get_image_path(id);
+        self.media.insert(MediaKind::Image, id, path, handle);
     }
 
-    pub fn get_image(&self, id: u32) -> Option<&Handle> {
-        let result = self.images.get(&id);
+    pub fn get_image(&self, id: u32) -> Option<Handle> {
+        let result = self.media.get(MediaKind::Image, id);
         trace!(
             post_id = id,
             found = result.is_some(),
@@ -219,12 +398,13 @@ impl PostStore {
 
     // --- GIFs ---
 
-    pub fn insert_gif(&mut self, id: u32, gif: Vec<u8>) {
-        self.gifs.insert(id, gif);
+    /// Records that post `id`'s GIF has been saved to disk.
+    pub fn insert_gif(&mut self, id: u32) {
+        self.gifs.insert(id, self.get_gif_path(id));
     }
 
-    pub fn get_gif(&self, id: u32) -> Option<&Vec<u8>> {
-        self.gifs.get(&id)
+    pub fn get_gif_frames(&self, id: u32) -> Option<&Frames> {
+        self.gif_frames.get(&id)
     }
 
     pub fn get_gif_path(&self, id: u32) -> PathBuf {
@@ -262,18 +442,109 @@ impl PostStore {
         self.results.get(query)
     }
 
+    // --- Tag suggestions ---
+
+    /// Caches autocomplete results for `prefix`, dropping the oldest entry when full.
+    pub fn insert_suggestions(&mut self, prefix: &str, tags: Vec<TagSuggestion>) {
+        if self.tag_suggestions.len() >= SUGGESTION_CACHE_SIZE
+            && !self.tag_suggestions.contains_key(prefix)
+        {
+            let oldest = self
+                .tag_suggestions
+                .iter()
+                .min_by_key(|(_, cached)| cached.fetched_at)
+                .map(|(prefix, _)| prefix.clone());
+            if let Some(oldest) = oldest {
+                self.tag_suggestions.remove(&oldest);
+            }
+        }
+        self.tag_suggestions.insert(
+            prefix.to_string(),
+            CachedSuggestions {
+                fetched_at: Utc::now(),
+                tags,
+            },
+        );
+    }
+
+    /// Cached autocomplete results for `prefix`, unless they're too old to trust.
+    pub fn get_suggestions(&self, prefix: &str) -> Option<&[TagSuggestion]> {
+        self.tag_suggestions
+            .get(prefix)
+            .filter(|cached| Utc::now() - cached.fetched_at < SUGGESTION_TTL)
+            .map(|cached| cached.tags.as_slice())
+    }
+
+    // --- Hidden results ---
+
+    /// Records posts hidden from `query`'s results, along with the blacklist rule that matched.
+    pub fn update_hidden(&mut self, query: &str, hidden: &[(u32, String)]) {
+        let hidden_posts = self.hidden.entry(String::from(query)).or_default();
+        for (id, rule) in hidden {
+            hidden_posts.insert(*id, rule.clone());
+        }
+    }
+
+    pub fn get_hidden(&self, query: &str) -> Option<&[(u32, String)]> {
+        self.hidden.get(query).map(PostList::entries)
+    }
+
+    pub fn hidden_count(&self, query: &str) -> usize {
+        self.hidden.get(query).map_or(0, PostList::len)
+    }
+
+    /// Records the IDs of every post fetched for `query`, in order.
+    pub fn record_fetched(&mut self, query: &str, posts: &[u32]) {
+        let fetched = self.fetched.entry(String::from(query)).or_default();
+        for &post in posts {
+            fetched.insert(post, ());
+        }
+    }
+
+    /// Re-checks every fetched result set against `blacklist`, rebuilding `results` and `hidden`
+    /// so newly blacklisted posts are hidden and no-longer-blacklisted ones are restored in place.
+    pub fn refilter_results(&mut self, blacklist: &CompiledBlacklist) {
+        for (query, fetched) in &self.fetched {
+            let mut visible: Vec<u32> = Vec::new();
+            let mut hidden: PostList<String> = PostList::default();
+
+            for &(id, ()) in fetched.entries() {
+                let Some(post) = self.posts.get(&id) else {
+                    continue;
+                };
+                match blacklist.matching_rule(post) {
+                    Some(rule) => hidden.insert(id, rule.to_string()),
+                    None => visible.push(id),
+                }
+            }
+
+            trace!(
+                query,
+                visible = visible.len(),
+                hidden = hidden.len(),
+                "Refiltered results"
+            );
+            self.results.insert(query.clone(), visible);
+            if hidden.is_empty() {
+                self.hidden.remove(query);
+            } else {
+                self.hidden.insert(query.clone(), hidden);
+            }
+        }
+    }
+
     // --- Utilities ---
 
     pub fn has_thumbnail(&self, id: u32) -> bool {
-        self.thumbnails.contains_key(&id)
+        self.media.contains(MediaKind::Thumbnail, id)
     }
 
     pub fn has_sample(&self, id: u32) -> bool {
-        self.samples.contains_key(&id)
+        self.media.contains(MediaKind::Sample, id)
     }
 
     pub fn has_image(&self, id: u32) -> bool {
-        self.images.contains_key(&id)
+        self.media.contains(MediaKind::Image, id)
     }
 
     pub fn has_gif(&self, id: u32) -> bool {
@@ -284,33 +555,18 @@ impl PostStore {
         self.videos.contains_key(&id)
     }
 
-    pub fn save_to(&self, path: &Path) -> Result<(), StoreError> {
-        let data = PostStoreData {
+    /// Everything kept across sessions, as written to a snapshot.
+    pub fn data(&self) -> PostStoreData {
+        PostStoreData {
             posts: self.posts.clone(),
-            thumbnails: self
-                .thumbnails
-                .keys()
-                .map(|&id| (id, self.get_thumbnail_path(id)))
-                .collect(),
-            samples: self
-                .samples
-                .keys()
-                .map(|&id| (id, self.get_sample_path(id)))
-                .collect(),
-            images: self
-                .images
-                .keys()
-                .map(|&id| (id, self.get_image_path(id)))
-                .collect(),
-            gifs: self
-                .gifs
-                .keys()
-                .map(|&id| (id, self.get_gif_path(id)))
-                .collect(),
+            thumbnails: media_files(&self.media, MediaKind::Thumbnail),
+            samples: media_files(&self.media, MediaKind::Sample),
+            images: media_files(&self.media, MediaKind::Image),
+            gifs: self.gifs.clone(),
             videos: self
                 .videos
                 .iter()
-                .map(|(&id, url)| (id, url.to_string()))
+                .filter_map(|(&id, url)| Some((id, url.to_file_path().ok()?)))
                 .collect(),
             votes: self
                 .votes
@@ -318,63 +574,251 @@ impl PostStore {
                 .map(|(&id, &vote)| (id, vote.into()))
                 .collect(),
             favorites: self.favorites.clone(),
-        };
-
-        if !path.exists() {
-            std::fs::create_dir_all(path.parent().unwrap())?;
+            tag_suggestions: self
+                .tag_suggestions
+                .iter()
+                .filter(|(_, cached)| Utc::now() - cached.fetched_at < SUGGESTION_TTL)
+                .map(|(prefix, cached)| (prefix.clone(), cached.clone()))
+                .collect(),
+            comments: self
+                .comments
+                .iter()
+                .filter(|(post_id, _)| self.posts.contains_key(post_id))
+                .map(|(&post_id, cached)| (post_id, cached.clone()))
+                .collect(),
+            comment_votes: self
+                .comment_votes
+                .iter()
+                .map(|(&id, &vote)| (id, vote.into()))
+                .collect(),
         }
+    }
 
-        let file = std::fs::File::create(path)?;
-        let writer = BufWriter::new(file);
-        data.serialize(&mut Serializer::new(writer))?;
+    /// Writes a snapshot of the whole store to `path`, replacing its journal.
+    pub fn save_to(&mut self, path: &Path) -> Result<(), StoreError> {
+        let data = self.data();
+        write_atomically(path, |writer| {
+            migrate::write_header(writer)?;
+            data.serialize(&mut Serializer::new(writer))?;
+            Ok(())
+        })?;
+
+        // A crash before this replays the old journal over the new snapshot, which is harmless
+        // since every entry holds a post's whole state.
+        match fs::remove_file(journal_path(path)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+        self.dirty.clear();
+        self.dirty_comment_votes.clear();
+        self.removed.clear();
         Ok(())
     }
 
-    pub fn load_from(path: &Path) -> Result<Self, StoreError> {
-        let file = std::fs::File::open(path)?;
-        let reader = BufReader::new(file);
-        let data: PostStoreData = rmp_serde::decode::from_read(reader)?;
+    /// Whether anything has changed since the last flush or save.
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty() || !self.dirty_comment_votes.is_empty() || !self.removed.is_empty()
+    }
 
-        let mut store = PostStore::new();
-        store.posts = data.posts;
-        store.favorites = data.favorites;
+    /// Appends changes since the last flush to the journal next to the snapshot at `path`, so
+    /// they survive a crash. Once the journal grows large, a new snapshot is written instead.
+    ///
+    /// This waits on the disk; the app uses [`flush_to`](Self::flush_to) instead.
+    pub fn flush(&mut self, path: &Path) -> Result<(), StoreError> {
+        let entries = self.take_changes();
+        if entries.is_empty() {
+            return Ok(());
+        }
 
-        for (id, upvoted) in data.votes {
-            store.set_vote(id, Some(Vote::from(upvoted)));
+        let size = journal::append(&journal_path(path), &entries)?;
+        trace!("Journaled {} changes", entries.len());
+        if size > COMPACT_AFTER {
+            info!("Compacting store journal ({size} bytes)");
+            self.save_to(path)?;
+        }
+        Ok(())
+    }
+
+    /// Hands changes since the last flush to `writer`, which journals them on its own thread.
+    pub fn flush_to(&mut self, writer: &StoreWriter) {
+        let entries = self.take_changes();
+        if !entries.is_empty() {
+            writer.send(entries);
         }
+    }
 
-        for (id, path) in data.thumbnails {
-            if let Ok(bytes) = std::fs::read(path) {
-                store.insert_thumbnail(id, Handle::from_bytes(bytes));
-            }
+    /// Changes since the last flush, as journal entries.
+    fn take_changes(&mut self) -> Vec<JournalEntry> {
+        let removed = self.removed.iter().map(|&id| JournalEntry::RemovePost(id));
+        let posts = self
+            .dirty
+            .iter()
+            .filter(|id| !self.removed.contains(id))
+            .map(|&id| JournalEntry::Post(Box::new(self.record(id))));
+        let comment_votes = self.dirty_comment_votes.iter().map(|&id| {
+            JournalEntry::CommentVote(id, self.comment_votes.get(&id).map(|&vote| vote.into()))
+        });
+        let entries = removed
+            .chain(posts)
+            .chain(comment_votes)
+            .collect::<Vec<_>>();
+
+        self.dirty.clear();
+        self.dirty_comment_votes.clear();
+        self.removed.clear();
+        entries
+    }
+
+    fn record(&self, id: u32) -> PostRecord {
+        PostRecord {
+            id,
+            post: self.posts.get(&id).cloned(),
+            favorite: self.favorites.contains(&id),
+            vote: self.votes.get(&id).map(|&vote| vote.into()),
+            comments: self.comments.get(&id).cloned(),
+            thumbnail: self
+                .media
+                .path(MediaKind::Thumbnail, id)
+                .map(Path::to_path_buf),
+            sample: self
+                .media
+                .path(MediaKind::Sample, id)
+                .map(Path::to_path_buf),
+            image: self.media.path(MediaKind::Image, id).map(Path::to_path_buf),
+            gif: self.gifs.get(&id).cloned(),
+            video: self.videos.get(&id).and_then(|url| url.to_file_path().ok()),
         }
+    }
 
-        for (id, path) in data.samples {
-            if let Ok(bytes) = std::fs::read(&path) {
-                store.insert_sample(id, Handle::from_bytes(bytes));
+    /// Replays a journal entry on top of a snapshot.
+    fn apply(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::Post(record) => {
+                let PostRecord {
+                    id,
+                    post,
+                    favorite,
+                    vote,
+                    comments,
+                    thumbnail,
+                    sample,
+                    image,
+                    gif,
+                    video,
+                } = *record;
+                if let Some(post) = post {
+                    self.posts.insert(id, post);
+                }
+                self.set_favorite(id, favorite);
+                self.set_vote(id, vote.map(Vote::from));
+                match comments {
+                    Some(comments) => self.comments.insert(id, comments),
+                    None => self.comments.remove(&id),
+                };
+                for (kind, path) in [
+                    (MediaKind::Thumbnail, thumbnail),
+                    (MediaKind::Sample, sample),
+                    (MediaKind::Image, image),
+                ] {
+                    if let Some(path) = path {
+                        self.media.insert_path(kind, id, path);
+                    }
+                }
+                if let Some(gif) = gif {
+                    self.gifs.insert(id, gif);
+                }
+                if let Some(url) = video.and_then(|video| Url::from_file_path(video).ok()) {
+                    self.videos.insert(id, url);
+                }
             }
+            JournalEntry::RemovePost(id) => self.forget(id),
+            JournalEntry::CommentVote(id, vote) => self.set_comment_vote(id, vote.map(Vote::from)),
         }
+    }
 
-        for (id, path) in data.images {
-            if let Ok(bytes) = std::fs::read(&path) {
-                store.insert_image(id, Handle::from_bytes(bytes));
+    /// Drops everything stored for post `id`, except votes.
+    fn forget(&mut self, id: u32) {
+        self.posts.remove(&id);
+        self.comments.remove(&id);
+        self.media.remove(id);
+        self.gifs.remove(&id);
+        self.gif_frames.remove(&id);
+    }
+
+    /// Loads the post index from the snapshot at `path` and the journal next to it. Media files
+    /// are only noted, not read; see [`MediaCache`].
+    ///
+    /// A snapshot from an older version of msg is upgraded and saved again, after copying the
+    /// original next to it as e.g. `store.v1.mpk`.
+    pub fn load_from(path: &Path) -> Result<Self, StoreError> {
+        // Changes made before the first snapshot are only in the journal.
+        let (version, data) = match fs::read(path) {
+            Ok(bytes) => {
+                let (version, body) = migrate::split_header(&bytes)?;
+                (version, migrate::decode(version, body)?)
             }
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                (STORE_VERSION, PostStoreData::default())
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut store = PostStore::new();
+        store.posts = data.posts;
+        store.favorites = data.favorites;
+        store.tag_suggestions = data.tag_suggestions;
+        store.comments = data.comments;
+        store.comment_votes = data
+            .comment_votes
+            .into_iter()
+            .map(|(id, upvoted)| (id, Vote::from(upvoted)))
+            .collect();
+
+        for (id, upvoted) in data.votes {
+            store.set_vote(id, Some(Vote::from(upvoted)));
         }
 
-        for (id, path) in data.gifs {
-            if let Ok(bytes) = std::fs::read(&path) {
-                store.insert_gif(id, bytes);
+        // Media for posts that were purged is dropped with them.
+        for (kind, files) in [
+            (MediaKind::Thumbnail, data.thumbnails),
+            (MediaKind::Sample, data.samples),
+            (MediaKind::Image, data.images),
+        ] {
+            for (id, path) in files {
+                if store.posts.contains_key(&id) {
+                    store.media.insert_path(kind, id, path);
+                }
             }
         }
+        store.gifs = data.gifs;
+        store.gifs.retain(|id, _| store.posts.contains_key(id));
 
         for (id, path) in data.videos {
-            if let Ok(url) = Url::from_str(&path) {
+            if let Ok(url) = Url::from_file_path(path) {
                 store.insert_video(id, url);
             }
         }
 
+        let entries = journal::recover(&journal_path(path))?;
+        let replayed = entries.len();
+        for entry in entries {
+            store.apply(entry);
+        }
+        store.dirty.clear();
+        store.dirty_comment_votes.clear();
+
         let post_count = store.posts.len();
-        info!("Loaded {post_count} posts");
+        info!("Loaded {post_count} posts ({replayed} journal entries)");
+
+        if version < STORE_VERSION {
+            let backup = path.with_extension(format!("v{version}.mpk"));
+            info!("Upgrading store from version {version} to {STORE_VERSION}, backed up to {backup:?}");
+            // An earlier backup is older still, so it's the one to keep.
+            if !backup.exists() {
+                fs::copy(path, &backup)?;
+            }
+            store.save_to(path)?;
+        }
 
         Ok(store)
     }
@@ -428,12 +872,66 @@ impl PostStore {
             removed_posts += 1;
         }
 
+        let purged = self
+            .posts
+            .keys()
+            .copied()
+            .filter(|id| !self.favorites.contains(id))
+            .collect::<Vec<u32>>();
         self.posts.retain(|id, _post| self.favorites.contains(id));
+        self.comments
+            .retain(|post_id, _| self.favorites.contains(post_id));
+        self.media.retain(|id| self.favorites.contains(&id));
+        self.gifs.retain(|id, _| self.favorites.contains(id));
+        self.gif_frames.retain(|id, _| self.favorites.contains(id));
+        self.removed.extend(purged);
 
         Ok(removed_posts)
     }
 }
 
+/// Writes `path` by writing a temporary file next to it and renaming that over it, so a crash
+/// leaves either the old file or the new one, never half of either.
+pub fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<&mut fs::File>) -> Result<(), StoreError>,
+) -> Result<(), StoreError> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let mut temp = NamedTempFile::new_in(dir)?;
+    {
+        let mut writer = BufWriter::new(temp.as_file_mut());
+        write(&mut writer)?;
+        writer.flush()?;
+    }
+    temp.as_file().sync_all()?;
+    temp.persist(path).map_err(|err| err.error)?;
+    Ok(())
+}
+
+/// Moves an unreadable snapshot at `path` and its journal out of the way, so saving a fresh store
+/// doesn't overwrite them. Returns where the snapshot went.
+pub fn set_aside(path: &Path) -> io::Result<PathBuf> {
+    let stamp = Utc::now().format("%Y%m%d%H%M%S");
+    let aside = path.with_extension(format!("unreadable-{stamp}.mpk"));
+    fs::rename(path, &aside)?;
+    match fs::rename(journal_path(path), journal_path(&aside)) {
+        Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    Ok(aside)
+}
+
+fn media_files(media: &MediaCache, kind: MediaKind) -> FxHashMap<u32, PathBuf> {
+    media
+        .files(kind)
+        .map(|(id, path)| (id, path.to_path_buf()))
+        .collect()
+}
+
 pub fn poststore_path() -> Option<PathBuf> {
     ProjectDirs::from("xyz", "stripywalrus", "msg")
         .map(|dirs| dirs.data_local_dir().join("store.mpk"))
@@ -441,6 +939,8 @@ pub fn poststore_path() -> Option<PathBuf> {
 
 #[cfg(test)]
 mod tests {
+    use super::super::fixture;
+    use super::super::model::{Rating, TagCategory};
     use super::*;
 
     #[test]
@@ -448,4 +948,432 @@ mod tests {
         let path = poststore_path().expect("should resolve");
         assert!(path.ends_with("store.mpk"));
     }
+
+    #[test]
+    fn hidden_results_are_deduplicated() {
+        let mut store = PostStore::new();
+        store.update_hidden("canine", &[(1, "feline".into()), (2, "rating:e".into())]);
+        store.update_hidden("canine", &[(2, "score:<0".into()), (3, "feline".into())]);
+
+        assert_eq!(store.hidden_count("canine"), 3);
+        assert_eq!(
+            store.get_hidden("canine").unwrap()[1],
+            (2, "score:<0".to_string())
+        );
+        assert_eq!(store.hidden_count("feline"), 0);
+    }
+
+    fn post(id: u32, general: &[&str]) -> Post {
+        fixture::post(serde_json::json!({ "id": id, "tags": { "general": general } }))
+    }
+
+    #[test]
+    fn refilter_hides_and_restores_in_order() {
+        use super::super::blacklist::Blacklist;
+
+        let mut store = PostStore::new();
+        store.insert_posts([post(3, &["a"]), post(2, &["b"]), post(1, &["a", "c"])]);
+        store.record_fetched("q", &[3, 2, 1]);
+        store.insert_results("q", &[3, 2, 1]);
+
+        let compile = |rules: &[&str]| {
+            CompiledBlacklist::compile(&Blacklist {
+                rules: rules.iter().map(|r| r.to_string()).collect(),
+            })
+        };
+
+        store.refilter_results(&compile(&["a"]));
+        assert_eq!(store.get_results("q").unwrap(), &vec![2]);
+        assert_eq!(
+            store.get_hidden("q").unwrap(),
+            &vec![(3, "a".to_string()), (1, "a".to_string())]
+        );
+
+        store.refilter_results(&compile(&["c"]));
+        assert_eq!(store.get_results("q").unwrap(), &vec![3, 2]);
+        assert_eq!(store.hidden_count("q"), 1);
+
+        store.refilter_results(&compile(&[]));
+        assert_eq!(store.get_results("q").unwrap(), &vec![3, 2, 1]);
+        assert!(store.get_hidden("q").is_none());
+    }
+
+    fn suggestion(name: &str) -> TagSuggestion {
+        TagSuggestion {
+            id: 1,
+            name: name.into(),
+            post_count: 10,
+            category: TagCategory::Species,
+            antecedent_name: Some("alias".into()),
+        }
+    }
+
+    #[test]
+    fn tag_suggestions_expire_and_persist() {
+        let mut store = PostStore::new();
+        store.insert_suggestions("wol", vec![suggestion("wolf")]);
+        store.insert_suggestions("dra", vec![suggestion("dragon")]);
+        store.tag_suggestions.get_mut("dra").unwrap().fetched_at -= TimeDelta::days(2);
+
+        assert_eq!(store.get_suggestions("wol").unwrap()[0].name, "wolf");
+        assert!(store.get_suggestions("dra").is_none());
+        assert!(store.get_suggestions("fox").is_none());
+
+        let temp_dir = tempfile::TempDir::new().expect("Couldn't make TempDir");
+        let path = temp_dir.path().join("store.mpk");
+        store.save_to(&path).expect("Couldn't save store");
+        let loaded = PostStore::load_from(&path).expect("Couldn't load store");
+
+        assert_eq!(
+            loaded.get_suggestions("wol"),
+            Some(&[suggestion("wolf")][..])
+        );
+        assert!(!loaded.tag_suggestions.contains_key("dra"));
+    }
+
+    #[test]
+    fn tag_suggestion_cache_is_bounded() {
+        let mut store = PostStore::new();
+        for i in 0..SUGGESTION_CACHE_SIZE {
+            store.insert_suggestions(&format!("tag{i}"), Vec::new());
+        }
+        store.tag_suggestions.get_mut("tag7").unwrap().fetched_at -= TimeDelta::hours(1);
+
+        store.insert_suggestions("newest", Vec::new());
+        assert_eq!(store.tag_suggestions.len(), SUGGESTION_CACHE_SIZE);
+        assert!(!store.tag_suggestions.contains_key("tag7"));
+        assert!(store.get_suggestions("newest").is_some());
+    }
+
+    fn comment(id: u32, post_id: u32, body: &str) -> Comment {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "post_id": post_id,
+            "creator_name": "commenter",
+            "body": body,
+            "score": 0,
+            "created_at": "2024-01-02T00:00:00.000-05:00",
+            "updated_at": "2024-01-02T00:00:00.000-05:00"
+        }))
+        .expect("fixture should deserialize")
+    }
+
+    fn bodies(store: &PostStore, post_id: u32) -> Vec<&str> {
+        store
+            .get_comments(post_id)
+            .unwrap()
+            .iter()
+            .map(|c| c.body.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn comments_are_deduplicated_by_id() {
+        let mut store = PostStore::new();
+        store.set_comments(
+            1,
+            vec![comment(3, 1, "c"), comment(1, 1, "a"), comment(3, 1, "c")],
+        );
+        store.insert_comment(comment(1, 1, "a, edited"));
+        store.insert_comment(comment(2, 1, "b"));
+        store.insert_comments([comment(2, 1, "b")]);
+
+        assert_eq!(bodies(&store, 1), vec!["a, edited", "b", "c"]);
+    }
+
+    #[test]
+    fn comments_refresh_after_ttl() {
+        let mut store = PostStore::new();
+        assert!(store.comments_need_refresh(1));
+
+        store.set_comments(1, vec![comment(1, 1, "a")]);
+        assert!(!store.comments_need_refresh(1));
+        store.comments.get_mut(&1).unwrap().fetched_at -= COMMENT_TTL;
+        assert!(store.comments_need_refresh(1));
+
+        // Posting on a post whose comments were never fetched doesn't count as fetching them.
+        store.insert_comment(comment(5, 2, "new"));
+        assert!(store.comments_need_refresh(2));
+    }
+
+    #[test]
+    fn comments_and_votes_persist() {
+        let mut store = PostStore::new();
+        store.insert_posts([post(1, &[])]);
+        store.set_comments(1, vec![comment(1, 1, "kept")]);
+        store.set_comments(2, vec![comment(2, 2, "post isn't stored")]);
+        store.set_comment_vote(1, Some(Vote::Downvote));
+
+        let temp_dir = tempfile::TempDir::new().expect("Couldn't make TempDir");
+        let path = temp_dir.path().join("store.mpk");
+        store.save_to(&path).expect("Couldn't save store");
+        let loaded = PostStore::load_from(&path).expect("Couldn't load store");
+
+        assert_eq!(bodies(&loaded, 1), vec!["kept"]);
+        assert!(!loaded.comments_need_refresh(1));
+        assert!(loaded.get_comments(2).is_none());
+        assert_eq!(loaded.comment_vote_for(1), Some(Vote::Downvote));
+    }
+
+    #[test]
+    fn media_is_noted_on_load_not_read() {
+        let mut store = PostStore::new();
+        store.insert_post(post(1, &[]));
+        store.insert_thumbnail(1, Handle::from_bytes(vec![0; 64]));
+        // Never saved to disk, and its post isn't kept.
+        store.insert_thumbnail(2, Handle::from_bytes(vec![0; 64]));
+        assert_eq!(store.media.memory_used(), 128);
+
+        let temp_dir = tempfile::TempDir::new().expect("Couldn't make TempDir");
+        let path = temp_dir.path().join("store.mpk");
+        store.save_to(&path).expect("Couldn't save store");
+        let loaded = PostStore::load_from(&path).expect("Couldn't load store");
+
+        assert_eq!(loaded.media.memory_used(), 0);
+        assert_eq!(
+            loaded.get_thumbnail(1),
+            Some(Handle::from_path(loaded.get_thumbnail_path(1)))
+        );
+        assert!(!loaded.has_thumbnail(2));
+    }
+
+    #[test]
+    fn changes_are_journaled_before_any_snapshot() {
+        let temp_dir = tempfile::TempDir::new().expect("Couldn't make TempDir");
+        let path = temp_dir.path().join("store.mpk");
+
+        let mut store = PostStore::new();
+        store.insert_post(post(1, &[]));
+        store.insert_post(post(2, &[]));
+        store.set_favorite(1, true);
+        store.set_vote(2, Some(Vote::Downvote));
+        store.set_comment_vote(9, Some(Vote::Upvote));
+        store.flush(&path).expect("Couldn't flush store");
+        assert!(!store.is_dirty());
+
+        store.set_favorite(1, false);
+        store.flush(&path).expect("Couldn't flush store");
+        assert!(!path.exists());
+
+        let loaded = PostStore::load_from(&path).expect("Couldn't load store");
+        assert!(loaded.get_post(1).is_some() && loaded.get_post(2).is_some());
+        assert!(!loaded.is_favorited(1));
+        assert_eq!(loaded.vote_for(2), Some(Vote::Downvote));
+        assert_eq!(loaded.comment_vote_for(9), Some(Vote::Upvote));
+        assert!(!loaded.is_dirty());
+    }
+
+    #[test]
+    fn writer_journals_changes_in_order() {
+        let temp_dir = tempfile::TempDir::new().expect("Couldn't make TempDir");
+        let path = temp_dir.path().join("store.mpk");
+        let mut writer = StoreWriter::new(path.clone());
+
+        let mut store = PostStore::new();
+        store.insert_post(post(1, &[]));
+        store.flush_to(&writer);
+        store.set_favorite(1, true);
+        store.flush_to(&writer);
+        store.set_favorite(1, false);
+        store.flush_to(&writer);
+
+        // Media and re-reads alone aren't worth a journal entry.
+        store.insert_thumbnail(1, Handle::from_bytes(vec![0; 4]));
+        store.get_post(1);
+        assert!(!store.is_dirty());
+
+        writer.finish();
+        let loaded = PostStore::load_from(&path).expect("Couldn't load store");
+        assert!(loaded.get_post(1).is_some());
+        assert!(!loaded.is_favorited(1));
+    }
+
+    #[test]
+    fn torn_journal_entries_are_dropped() {
+        let temp_dir = tempfile::TempDir::new().expect("Couldn't make TempDir");
+        let path = temp_dir.path().join("store.mpk");
+
+        let mut store = PostStore::new();
+        store.insert_post(post(1, &[]));
+        store.flush(&path).expect("Couldn't flush store");
+        // As if the app died partway through the next append.
+        let mut journal = fs::OpenOptions::new()
+            .append(true)
+            .open(journal_path(&path))
+            .unwrap();
+        journal.write_all(&[200, 0, 0, 0, 1, 2, 3]).unwrap();
+
+        let mut loaded = PostStore::load_from(&path).expect("Couldn't load store");
+        assert!(loaded.get_post(1).is_some());
+
+        // Later appends aren't stuck behind the torn entry.
+        loaded.insert_post(post(2, &[]));
+        loaded.flush(&path).expect("Couldn't flush store");
+        let reloaded = PostStore::load_from(&path).expect("Couldn't load store");
+        assert!(reloaded.get_post(1).is_some() && reloaded.get_post(2).is_some());
+    }
+
+    #[test]
+    fn unreadable_journal_entries_are_kept() {
+        let temp_dir = tempfile::TempDir::new().expect("Couldn't make TempDir");
+        let path = temp_dir.path().join("store.mpk");
+
+        let mut store = PostStore::new();
+        store.insert_post(post(1, &[]));
+        store.flush(&path).expect("Couldn't flush store");
+        // A whole entry that doesn't decode, like one from a newer msg, then a good one.
+        let mut journal = fs::OpenOptions::new()
+            .append(true)
+            .open(journal_path(&path))
+            .unwrap();
+        journal.write_all(&[3, 0, 0, 0, 0xc1, 0xc1, 0xc1]).unwrap();
+        store.insert_post(post(2, &[]));
+        store.flush(&path).expect("Couldn't flush store");
+        let before = fs::read(journal_path(&path)).unwrap();
+
+        let err = PostStore::load_from(&path).unwrap_err();
+        assert!(matches!(err, StoreError::JournalEntry { .. }), "{err:?}");
+        assert_eq!(fs::read(journal_path(&path)).unwrap(), before);
+    }
+
+    #[test]
+    fn saving_replaces_the_journal() {
+        let temp_dir = tempfile::TempDir::new().expect("Couldn't make TempDir");
+        let path = temp_dir.path().join("store.mpk");
+
+        let mut store = PostStore::new();
+        store.insert_post(post(1, &[]));
+        store.flush(&path).expect("Couldn't flush store");
+        assert!(journal_path(&path).exists());
+
+        store.set_favorite(1, true);
+        store.save_to(&path).expect("Couldn't save store");
+        assert!(!journal_path(&path).exists());
+        assert!(!store.is_dirty());
+
+        let loaded = PostStore::load_from(&path).expect("Couldn't load store");
+        assert!(loaded.is_favorited(1));
+    }
+
+    #[test]
+    fn full_post_survives_save_and_load() {
+        let post: Post = serde_json::from_value(serde_json::json!({
+            "id": 4000000,
+            "created_at": "2024-01-01T00:00:00.000-05:00",
+            "updated_at": "2024-02-01T00:00:00.000-05:00",
+            "file": {
+                "width": 1920, "height": 1080, "ext": "webm", "size": 5242880,
+                "md5": "d41d8cd98f00b204e9800998ecf8427e",
+                "url": "https://static1.e621.net/data/d4/1d/d41d8cd98f00b204e9800998ecf8427e.webm"
+            },
+            "preview": { "width": 150, "height": 84, "url": null },
+            "sample": { "has": true, "height": 480, "width": 850, "url": null, "alternates": {} },
+            "score": { "up": 12, "down": -2, "total": 10 },
+            "tags": {
+                "general": ["solo"], "artist": ["someone"], "contributor": ["helper"],
+                "copyright": [], "character": [], "species": [], "invalid": [], "meta": [],
+                "lore": []
+            },
+            "locked_tags": ["solo"],
+            "change_seq": 55555555,
+            "flags": {
+                "pending": false, "flagged": true, "note_locked": false,
+                "status_locked": false, "rating_locked": true, "deleted": false
+            },
+            "rating": "q",
+            "fav_count": 7,
+            "sources": ["https://example.com/source"],
+            "pools": [1234],
+            "relationships": {
+                "parent_id": 3999999, "has_children": true,
+                "has_active_children": true, "children": [4000001]
+            },
+            "approver_id": 17,
+            "uploader_id": 42,
+            "uploader_name": "Uploader",
+            "description": "a description",
+            "comment_count": 3,
+            "is_favorited": false,
+            "has_notes": true,
+            "duration": 12.5,
+            "some_future_field": { "ignored": true }
+        }))
+        .expect("fixture should deserialize");
+
+        let mut store = PostStore::new();
+        store.insert_post(post);
+
+        let temp_dir = tempfile::TempDir::new().expect("Couldn't make TempDir");
+        let path = temp_dir.path().join("store.mpk");
+        store.save_to(&path).expect("Couldn't save store");
+        let loaded = PostStore::load_from(&path).expect("Couldn't load store");
+        let post = loaded.get_post(4000000).expect("post should be stored");
+
+        assert_eq!((post.file.width, post.file.height), (1920, 1080));
+        assert_eq!(post.file.size, 5242880);
+        assert_eq!(
+            post.file.md5.as_deref(),
+            Some("d41d8cd98f00b204e9800998ecf8427e")
+        );
+        assert_eq!((post.score.up, post.score.down), (12, -2));
+        assert_eq!(post.tags.contributor, vec!["helper"]);
+        assert_eq!(post.locked_tags, vec!["solo"]);
+        assert_eq!(post.change_seq, 55555555);
+        assert!(post.flags.flagged && post.flags.rating_locked && !post.flags.deleted);
+        assert_eq!(post.sources, vec!["https://example.com/source"]);
+        assert_eq!(post.pools, vec![1234]);
+        assert_eq!(post.relationships.parent_id, Some(3999999));
+        assert_eq!(post.relationships.children, vec![4000001]);
+        assert_eq!(post.approver_id, Some(17));
+        assert_eq!(post.duration, Some(12.5));
+        assert_eq!(post.comment_count, 3);
+        assert!(post.has_notes);
+    }
+
+    #[test]
+    fn legacy_post_records_still_decode() {
+        // Posts as stored before the full schema, encoded positionally like `save_to` does.
+        let created_at: DateTime<Utc> = "2024-01-01T05:00:00Z".parse().unwrap();
+        let tags: [Vec<String>; 8] = [
+            vec!["solo".into()],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        ];
+        let legacy = (
+            1234u32,
+            created_at,
+            created_at,
+            (Some("png"), Some("https://static1.e621.net/data/a.png")),
+            (None::<String>,),
+            (false, None::<String>),
+            (5i32,),
+            tags,
+            Rating::Explicit,
+            true,
+            9u32,
+            "old description",
+        );
+        let mut bytes = Vec::new();
+        legacy
+            .serialize(&mut Serializer::new(&mut bytes))
+            .expect("Couldn't encode legacy post");
+
+        let post: Post = rmp_serde::from_slice(&bytes).expect("legacy post should decode");
+        assert_eq!(post.id, 1234);
+        assert_eq!(post.file.ext.as_deref(), Some("png"));
+        assert_eq!(post.file.width, 0);
+        assert_eq!(post.score.total, 5);
+        assert_eq!(post.tags.general, vec!["solo"]);
+        assert_eq!(post.rating, Rating::Explicit);
+        assert_eq!(post.fav_count, 9);
+        assert_eq!(post.description, "old description");
+        assert!(post.uploader_id.is_none());
+        assert!(post.pools.is_empty());
+        assert!(post.relationships.parent_id.is_none());
+    }
 }
@@ -0,0 +1,106 @@
+//! Append-only log of changes to a [`PostStore`](super::PostStore) since its last snapshot.
+//!
+//! Each entry is a little-endian `u32` length followed by that many bytes of MessagePack. A crash
+//! partway through an append leaves a torn entry at the end, which is dropped when reading.
+//!
+//! Entries always use the current [`STORE_VERSION`](super::STORE_VERSION), since upgrading a
+//! snapshot folds its journal into it.
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::super::model::Post;
+use super::{CachedComments, StoreError};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum JournalEntry {
+    /// Everything stored for a post, replacing what was there.
+    Post(Box<PostRecord>),
+    /// A post dropped by a purge.
+    RemovePost(u32),
+    /// A vote on a comment, `true` for upvotes, or `None` if it was taken back.
+    CommentVote(u32, Option<bool>),
+}
+
+/// A post and everything stored alongside it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostRecord {
+    pub id: u32,
+    pub post: Option<Post>,
+    pub favorite: bool,
+    /// `true` for upvotes.
+    pub vote: Option<bool>,
+    pub comments: Option<CachedComments>,
+    pub thumbnail: Option<PathBuf>,
+    pub sample: Option<PathBuf>,
+    pub image: Option<PathBuf>,
+    pub gif: Option<PathBuf>,
+    pub video: Option<PathBuf>,
+}
+
+/// Where the journal for the snapshot at `snapshot` is kept.
+pub fn journal_path(snapshot: &Path) -> PathBuf {
+    snapshot.with_extension("journal")
+}
+
+/// Appends `entries` to the journal at `path` and syncs it to disk. Returns the journal's size
+/// afterwards.
+pub fn append(path: &Path, entries: &[JournalEntry]) -> Result<u64, StoreError> {
+    let mut buf = Vec::new();
+    for entry in entries {
+        let bytes = rmp_serde::to_vec(entry)?;
+        buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&bytes);
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&buf)?;
+    file.sync_data()?;
+    Ok(file.metadata()?.len())
+}
+
+/// Reads every entry from the journal at `path`. An incomplete entry at the very end is a torn
+/// append, which is cut off so later appends aren't stuck behind it. A whole entry that can't be
+/// decoded is an error, and the journal is left as it is. A missing journal has no entries.
+pub fn recover(path: &Path) -> Result<Vec<JournalEntry>, StoreError> {
+    // Journals are compacted long before they'd be too big to read in one go.
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut entries = Vec::new();
+    let mut rest = bytes.as_slice();
+    while let Some((len, after)) = rest.split_first_chunk::<4>() {
+        let len = u32::from_le_bytes(*len) as usize;
+        let Some((entry, after)) = after.split_at_checked(len) else {
+            break;
+        };
+        let offset = bytes.len() - rest.len();
+        let entry = rmp_serde::from_slice(entry).map_err(|err| StoreError::JournalEntry {
+            offset,
+            source: err,
+        })?;
+        entries.push(entry);
+        rest = after;
+    }
+
+    if !rest.is_empty() {
+        warn!("Dropping {} bytes from the end of {path:?}", rest.len());
+        let valid = bytes.len() - rest.len();
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(valid as u64)?;
+    }
+    Ok(entries)
+}
@@ -0,0 +1,87 @@
+//! Snapshot versions, and upgrading snapshots written by older versions of msg.
+//!
+//! A snapshot starts with [`MAGIC`] and its schema version as a little-endian `u32`, followed by
+//! the MessagePack [`PostStoreData`]. Version 1 snapshots predate the header and are only the
+//! MessagePack.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Deserialize;
+use url::Url;
+
+use super::super::model::Post;
+use super::{CachedComments, CachedSuggestions, PostStoreData, StoreError};
+
+/// Schema of the snapshots this version of msg writes.
+pub const STORE_VERSION: u32 = 2;
+const MAGIC: &[u8; 4] = b"MSGS";
+
+/// Writes the header for a snapshot of the current version.
+pub fn write_header(writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&STORE_VERSION.to_le_bytes())
+}
+
+/// Splits a snapshot into its version and the MessagePack after the header.
+pub fn split_header(bytes: &[u8]) -> Result<(u32, &[u8]), StoreError> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return Ok((1, bytes));
+    };
+    // A header cut short is as unreadable as a bad body.
+    let (version, body) = rest.split_first_chunk::<4>().ok_or_else(|| {
+        rmp_serde::decode::Error::Syntax("snapshot header is cut short".to_owned())
+    })?;
+    Ok((u32::from_le_bytes(*version), body))
+}
+
+/// Decodes a snapshot of `version`, upgrading it to the current schema. Each old schema converts
+/// into the one after it, so a new version only needs a conversion from the last.
+pub fn decode(version: u32, body: &[u8]) -> Result<PostStoreData, StoreError> {
+    match version {
+        1 => Ok(rmp_serde::from_slice::<StoreDataV1>(body)?.into()),
+        STORE_VERSION => Ok(rmp_serde::from_slice(body)?),
+        _ => Err(StoreError::UnknownVersion(version)),
+    }
+}
+
+/// [`PostStoreData`] as of version 1, which kept videos as `file://` URLs.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct StoreDataV1 {
+    posts: FxHashMap<u32, Post>,
+    thumbnails: FxHashMap<u32, PathBuf>,
+    samples: FxHashMap<u32, PathBuf>,
+    images: FxHashMap<u32, PathBuf>,
+    gifs: FxHashMap<u32, PathBuf>,
+    videos: FxHashMap<u32, String>,
+    votes: FxHashMap<u32, bool>,
+    favorites: FxHashSet<u32>,
+    tag_suggestions: FxHashMap<String, CachedSuggestions>,
+    comments: FxHashMap<u32, CachedComments>,
+    comment_votes: FxHashMap<u32, bool>,
+}
+
+impl From<StoreDataV1> for PostStoreData {
+    fn from(old: StoreDataV1) -> Self {
+        PostStoreData {
+            posts: old.posts,
+            thumbnails: old.thumbnails,
+            samples: old.samples,
+            images: old.images,
+            gifs: old.gifs,
+            // Anything that isn't a file URL never pointed at a video msg downloaded.
+            videos: old
+                .videos
+                .into_iter()
+                .filter_map(|(id, url)| Some((id, Url::parse(&url).ok()?.to_file_path().ok()?)))
+                .collect(),
+            votes: old.votes,
+            favorites: old.favorites,
+            tag_suggestions: old.tag_suggestions,
+            comments: old.comments,
+            comment_votes: old.comment_votes,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Journaling store changes on a thread of their own, so the UI never waits on the disk.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use tracing::{error, info, trace, warn};
+
+use super::journal::{self, journal_path, JournalEntry};
+use super::{PostStore, COMPACT_AFTER};
+
+/// How long changes are gathered before they're written, so a burst of them is synced once.
+const BATCH_DELAY: Duration = Duration::from_millis(250);
+
+/// Appends changes from [`PostStore::flush_to`] to the journal next to a snapshot, and folds the
+/// journal into a new snapshot once it grows large. Everything is written in the order it was
+/// sent.
+#[derive(Debug)]
+pub struct StoreWriter {
+    changes: Option<Sender<Vec<JournalEntry>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StoreWriter {
+    /// Starts a writer for the snapshot at `path`.
+    pub fn new(path: PathBuf) -> Self {
+        let (changes, queue) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("store writer".to_owned())
+            .spawn(move || run(&path, queue))
+            .map_err(|err| error!("Couldn't start store writer: {err}"))
+            .ok();
+        Self {
+            changes: thread.is_some().then_some(changes),
+            thread,
+        }
+    }
+
+    pub(super) fn send(&self, entries: Vec<JournalEntry>) {
+        let sent = self
+            .changes
+            .as_ref()
+            .is_some_and(|changes| changes.send(entries).is_ok());
+        if !sent {
+            warn!("Store writer isn't running, changes weren't journaled");
+        }
+    }
+
+    /// Waits for everything sent so far to be written, and stops the writer. A snapshot can be
+    /// saved safely after this.
+    pub fn finish(&mut self) {
+        self.changes.take();
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("Store writer panicked");
+            }
+        }
+    }
+}
+
+impl Drop for StoreWriter {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+fn run(path: &Path, queue: Receiver<Vec<JournalEntry>>) {
+    while let Ok(mut entries) = queue.recv() {
+        let deadline = Instant::now() + BATCH_DELAY;
+        while let Ok(more) = queue.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            entries.extend(more);
+        }
+
+        match journal::append(&journal_path(path), &entries) {
+            Ok(size) if size > COMPACT_AFTER => {
+                info!("Compacting store journal ({size} bytes)");
+                // The snapshot and journal on disk hold everything sent so far, so the new
+                // snapshot is built from them rather than from the app's store.
+                if let Err(err) =
+                    PostStore::load_from(path).and_then(|mut store| store.save_to(path))
+                {
+                    warn!("Couldn't compact store journal: {err}");
+                }
+            }
+            Ok(_) => trace!("Journaled {} changes", entries.len()),
+            Err(err) => warn!("Couldn't journal PostStore changes: {err}"),
+        }
+    }
+}
@@ -1,15 +1,20 @@
+use byte_unit::{Byte, UnitType};
 use chrono::{prelude::*, TimeDelta};
 
 use iced::font::Weight;
 use iced::widget::image::Handle;
 use iced::widget::text::Shaping;
-use iced::widget::{button, column, container, image, row, scrollable, text, Container};
+use iced::widget::{
+    button, column, container, image, row, scrollable, text, text_editor, Container,
+};
 use iced::widget::{Column, Row, Text};
 use iced::{Alignment, Element, Length, Theme};
 use iced_gif::Gif;
 
 use crate::app::message::{PostMessage, ViewMessage};
-use crate::app::state::ViewMode;
+use crate::app::render_dtext;
+use crate::app::state::{CommentDraft, ViewMode};
+use crate::core::dtext::cache::{DTextCache, DTextSource};
 use crate::core::model::{Comment, Vote};
 use crate::{
     app::message::{DetailMessage, FollowedMessage, MediaMessage},
@@ -20,22 +25,23 @@ use crate::{
 use super::time_ago::relative_time_ago;
 use super::video_player::VideoPlayerWidget;
 
+/// `username` is who's logged in, if anyone, so they can write and edit comments.
 pub fn render_detail<'a>(
     post: &'a Post,
     store: &'a PostStore,
     video_player: &'a Option<VideoPlayerWidget>,
+    draft: &'a CommentDraft,
+    dtext: &DTextCache,
+    username: Option<&'a str>,
 ) -> Element<'a, Message> {
-    let mut media_panel = column![
+    let media_panel = column![
         render_media(post, store, video_player),
         vote_bar(post, store),
-        text(post.description.clone()).shaping(Shaping::Advanced),
+        render_dtext(dtext, DTextSource::Description(post.id), store),
+        render_comments(post, store, draft, dtext, username),
     ];
 
-    if let Some(comments) = store.get_comments(post.id) {
-        media_panel = media_panel.push(render_comments(&comments));
-    }
-
-    let info_panel = info_panel(&post);
+    let info_panel = info_panel(post, store);
 
     row![
         scrollable(media_panel.width(Length::FillPortion(9)).padding(16)),
@@ -54,11 +60,8 @@ fn render_media<'a>(
             get_image(store.get_image(post.id), store.get_sample(post.id))
         }
         Some(crate::core::model::PostType::Gif) => {
-            if let Some(_) = store.get_gif(post.id) {
-                match store.gif_frames.get(&post.id) {
-                    Some(frames) => container(Gif::new(frames)),
-                    None => container(Text::new("Failed to parse GIF")),
-                }
+            if let Some(frames) = store.get_gif_frames(post.id) {
+                container(Gif::new(frames))
             } else {
                 container(Text::new("Gif loading..."))
             }
@@ -77,10 +80,7 @@ fn render_media<'a>(
     }
 }
 
-fn get_image<'a>(
-    fullsize: Option<&'a Handle>,
-    sample: Option<&'a Handle>,
-) -> Container<'a, Message> {
+fn get_image<'a>(fullsize: Option<Handle>, sample: Option<Handle>) -> Container<'a, Message> {
     if let Some(img) = fullsize {
         return container(image(img));
     }
@@ -90,8 +90,8 @@ fn get_image<'a>(
     container(Text::new("Image loading..."))
 }
 
-fn info_panel<'a>(post: &'a Post) -> Column<'a, Message> {
-    let mut panel = column![];
+fn info_panel<'a>(post: &'a Post, store: &PostStore) -> Column<'a, Message> {
+    let mut panel = column![post_info(post, store), text("")];
 
     for (category, tags) in post.tags.iter().filter(|(_, tags)| !tags.is_empty()) {
         let header = text(format!("{category}:")).size(16);
@@ -106,6 +106,103 @@ fn info_panel<'a>(post: &'a Post) -> Column<'a, Message> {
     panel.spacing(10)
 }
 
+/// File details, provenance and status of the post.
+fn post_info<'a>(post: &'a Post, store: &PostStore) -> Column<'a, Message> {
+    let mut info = column![text("info:").size(16)].spacing(4);
+
+    let mut file = format!(
+        "{}x{} {}, {:.2}",
+        post.file.width,
+        post.file.height,
+        post.file.ext.as_deref().unwrap_or("?"),
+        Byte::from_u64(post.file.size).get_appropriate_unit(UnitType::Binary)
+    );
+    if let Some(duration) = post.duration {
+        file.push_str(&format!(", {duration:.1}s"));
+    }
+    info = info.push(text(file));
+
+    if let Some(md5) = &post.file.md5 {
+        info = info.push(text(format!("md5: {md5}")).size(12));
+    }
+
+    let uploader = match (&post.uploader_name, post.uploader_id) {
+        (Some(name), _) => name.clone(),
+        (None, Some(id)) => format!("user #{id}"),
+        (None, None) => "unknown".to_string(),
+    };
+    info = info.push(text(format!("uploaded by {uploader}")));
+    if let Some(approver_id) = post.approver_id {
+        info = info.push(text(format!("approved by user #{approver_id}")));
+    }
+
+    let created_at: DateTime<Local> = DateTime::from(post.created_at);
+    info = info.push(text(format!(
+        "posted {}",
+        relative_time_ago(Local::now() - created_at)
+    )));
+
+    info = info.push(text(format!(
+        "score {} (+{} / {}), {} favs, {} comments",
+        post.score.total, post.score.up, post.score.down, post.fav_count, post.comment_count
+    )));
+
+    let status: Vec<&str> = [
+        (post.flags.pending, "pending"),
+        (post.flags.flagged, "flagged"),
+        (post.flags.deleted, "deleted"),
+        (post.flags.rating_locked, "rating locked"),
+        (post.flags.note_locked, "notes locked"),
+        (post.flags.status_locked, "status locked"),
+        (post.has_notes, "has notes"),
+    ]
+    .into_iter()
+    .filter_map(|(set, label)| set.then_some(label))
+    .collect();
+    if !status.is_empty() {
+        info = info.push(text(status.join(", ")));
+    }
+
+    if let Some(parent_id) = post.relationships.parent_id {
+        info = info.push(post_link("parent", parent_id, store));
+    }
+    for &child_id in &post.relationships.children {
+        info = info.push(post_link("child", child_id, store));
+    }
+    for &pool_id in &post.pools {
+        info = info.push(
+            button(text(format!("pool #{pool_id}")))
+                .padding(4)
+                .style(button::text)
+                .on_press(Message::View(ViewMessage::Show(ViewMode::Pool(pool_id)))),
+        );
+    }
+
+    for source in &post.sources {
+        info = info.push(text(source).size(12).shaping(Shaping::Advanced));
+    }
+
+    if !post.locked_tags.is_empty() {
+        info = info.push(text(format!("locked tags: {}", post.locked_tags.join(" "))).size(12));
+    }
+
+    info
+}
+
+/// Links to a related post, if it's been loaded.
+fn post_link<'a>(label: &str, id: u32, store: &PostStore) -> Element<'a, Message> {
+    let link = button(text(format!("{label} #{id}")))
+        .padding(4)
+        .style(button::text);
+
+    match store.get_post(id) {
+        Some(_) => link
+            .on_press(Message::View(ViewMessage::Show(ViewMode::Detail(id))))
+            .into(),
+        None => link.into(),
+    }
+}
+
 fn render_tag(tag: &String) -> Element<'_, Message> {
     row![
         button("f")
@@ -128,32 +225,104 @@ fn render_tag(tag: &String) -> Element<'_, Message> {
                 Some(1)
             ))))
             .padding(4),
+        button("?")
+            .on_press(Message::View(ViewMessage::Show(ViewMode::Wiki(
+                tag.clone()
+            ))))
+            .style(button::text)
+            .padding(4)
+            .width(24),
     ]
     .into()
 }
 
-fn render_comments<'a>(comments: &'a [Comment]) -> Column<'a, Message> {
-    let all_comments = column(
-        comments
-            .iter()
-            .map(|comment| render_comment(comment))
-            .collect::<Vec<_>>(),
-    );
+fn render_comments<'a>(
+    post: &'a Post,
+    store: &'a PostStore,
+    draft: &'a CommentDraft,
+    dtext: &DTextCache,
+    username: Option<&'a str>,
+) -> Column<'a, Message> {
+    let comments = store.get_comments(post.id).map_or(&[][..], Vec::as_slice);
+
+    let header = row![
+        text(format!("{} comments", comments.len())).size(16),
+        button("refresh")
+            .on_press(Message::Detail(DetailMessage::RefreshComments))
+            .style(button::text),
+    ]
+    .align_y(Alignment::Center)
+    .spacing(8);
 
-    all_comments.spacing(10)
+    let mut all_comments = column![header].spacing(10);
+    for comment in comments {
+        let own = username.is_some_and(|name| name.eq_ignore_ascii_case(&comment.creator_name));
+        all_comments = all_comments.push(render_comment(comment, store, dtext, own));
+    }
+    if username.is_some() {
+        all_comments = all_comments.push(comment_editor(store, draft, dtext));
+    }
+
+    all_comments
 }
 
-fn render_comment<'a>(comment: &'a Comment) -> Element<'a, Message> {
+fn render_comment<'a>(
+    comment: &'a Comment,
+    store: &PostStore,
+    dtext: &DTextCache,
+    own: bool,
+) -> Element<'a, Message> {
     let created_at: DateTime<Local> = DateTime::from(comment.created_at);
     let time_ago: TimeDelta = Local::now() - created_at;
+    let vote = store.comment_vote_for(comment.id);
+
+    let vote_button = |label: &'a str, direction: Vote| {
+        let active = vote == Some(direction);
+        button(text(label).shaping(Shaping::Advanced))
+            .on_press(Message::Detail(DetailMessage::VoteComment(
+                comment.post_id,
+                comment.id,
+                (!active).then_some(direction),
+            )))
+            .style(if active {
+                button::primary
+            } else {
+                button::text
+            })
+            .padding(2)
+    };
+
+    let mut header = row![
+        text(comment.creator_name.clone())
+            .font(iced::font::Font {
+                weight: Weight::Bold,
+                ..Default::default()
+            })
+            .width(Length::Fill),
+        vote_button("↑", Vote::Upvote),
+        text(comment.score),
+        vote_button("↓", Vote::Downvote),
+    ]
+    .align_y(Alignment::Center)
+    .spacing(4);
+    if own {
+        header = header.push(
+            button("edit")
+                .on_press(Message::Detail(DetailMessage::EditComment(comment.id)))
+                .style(button::text)
+                .padding(2),
+        );
+    }
+
+    let mut footer = relative_time_ago(time_ago);
+    if comment.updated_at > comment.created_at {
+        footer.push_str(" (edited)");
+    }
 
     container(column![
-        text(comment.creator_name.clone()).font(iced::font::Font {
-            weight: Weight::Bold,
-            ..Default::default()
-        }),
-        text(comment.body.clone()).shaping(Shaping::Advanced),
-        text(relative_time_ago(time_ago)).size(10)
+        header,
+        render_dtext(dtext, DTextSource::Comment(comment.id), store),
+        text(footer).size(10)
     ])
     .width(Length::Fill)
     .style(container::bordered_box)
@@ -161,6 +330,55 @@ fn render_comment<'a>(comment: &'a Comment) -> Element<'a, Message> {
     .into()
 }
 
+/// Editor for a new comment, or one being edited, with a rendered preview.
+fn comment_editor<'a>(
+    store: &PostStore,
+    draft: &'a CommentDraft,
+    dtext: &DTextCache,
+) -> Column<'a, Message> {
+    let title = match draft.editing {
+        Some(id) => format!("Editing comment #{id}"),
+        None => String::from("Add a comment"),
+    };
+
+    let body: Element<'a, Message> = if draft.preview {
+        container(render_dtext(dtext, DTextSource::Draft, store))
+            .width(Length::Fill)
+            .style(container::bordered_box)
+            .padding(8)
+            .into()
+    } else {
+        text_editor(&draft.content)
+            .placeholder("DText formatting works here")
+            .on_action(|action| Message::Detail(DetailMessage::CommentEdited(action)))
+            .height(120)
+            .into()
+    };
+
+    let ready = !draft.sending && !draft.content.text().trim().is_empty();
+    let mut buttons = row![
+        button(if draft.preview { "edit" } else { "preview" })
+            .on_press(Message::Detail(DetailMessage::ToggleCommentPreview))
+            .style(button::secondary),
+        button(if draft.editing.is_some() {
+            "save"
+        } else {
+            "post"
+        })
+        .on_press_maybe(ready.then_some(Message::Detail(DetailMessage::SubmitComment))),
+    ]
+    .spacing(8);
+    if draft.editing.is_some() {
+        buttons = buttons.push(
+            button("cancel")
+                .on_press(Message::Detail(DetailMessage::CancelComment))
+                .style(button::secondary),
+        );
+    }
+
+    column![text(title).size(16), body, buttons].spacing(6)
+}
+
 fn vote_bar<'a>(post: &'a Post, store: &'a PostStore) -> Row<'a, Message> {
     let vote_status = store.vote_for(post.id);
     let is_favorited = store.is_favorited(post.id);
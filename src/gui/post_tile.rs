@@ -1,22 +1,28 @@
 use iced::{
-    widget::{button, column, container, image, image::Handle, row, text, Button, Column},
-    Element, Length,
+    widget::{button, column, container, image, image::Handle, row, space, text, Button, Column},
+    Border, Element, Length, Theme,
 };
 use std::cmp::min;
+use std::ops::Range;
 
 use crate::{
-    app::{
-        message::{SearchMessage, ViewMessage},
-        state::ViewMode,
-        Message,
-    },
+    app::{message::ViewMessage, state::ViewMode, Message},
     core::{
         model::{Post, Rating},
         store::PostStore,
     },
 };
 
-pub fn render<'a>(post: &Post, thumbnail: Option<&Handle>, width: f32) -> Element<'a, Message> {
+/// Renders a post tile. If `hidden_by` is set, the post was hidden by that blacklist rule and has
+/// since been revealed, so it's drawn as usual but with the rule in place of its metadata. A
+/// `highlighted` tile, picked with the keyboard, gets an outline.
+pub fn render<'a>(
+    post: &Post,
+    thumbnail: Option<Handle>,
+    width: f32,
+    hidden_by: Option<&str>,
+    highlighted: bool,
+) -> Element<'a, Message> {
     let rating_text = match post.rating {
         Rating::Safe => text("S").color(iced::Color::from_rgb(0.3, 0.9, 0.3)),
         Rating::Questionable => text("Q").color(iced::Color::from_rgb(0.9, 0.7, 0.2)),
@@ -27,7 +33,7 @@ pub fn render<'a>(post: &Post, thumbnail: Option<&Handle>, width: f32) -> Elemen
     let thumbnail_size = width * (5.0 / 6.0);
 
     let preview: Element<_> = if let Some(img) = thumbnail {
-        image(img.clone())
+        image(img)
             .width(Length::Fixed(thumbnail_size))
             .height(Length::Fixed(thumbnail_size))
             .into()
@@ -50,48 +56,156 @@ pub fn render<'a>(post: &Post, thumbnail: Option<&Handle>, width: f32) -> Elemen
     ]
     .spacing(8);
 
-    let layout = column![preview, meta].spacing(4).padding(8);
-
-    let height = width * (1.25);
+    let layout = match hidden_by {
+        Some(rule) => column![preview, text(format!("hidden by: {rule}")).size(10)],
+        None => column![preview, meta],
+    }
+    .spacing(4)
+    .padding(8);
 
     Button::new(layout)
         .on_press(Message::View(ViewMessage::Show(ViewMode::Detail(post.id))))
         .width(Length::Fixed(width))
-        .height(Length::Fixed(height))
+        .height(Length::Fixed(tile_height(width)))
         .padding(4)
+        .style(move |theme: &Theme, status| {
+            let mut style = button::primary(theme, status);
+            if highlighted {
+                style.border = Border {
+                    color: theme.palette().text,
+                    width: 3.0,
+                    radius: 2.0.into(),
+                };
+            }
+            style
+        })
         .into()
 }
 
+pub fn tile_height(tile_width: f32) -> f32 {
+    tile_width * 1.25
+}
+
+/// How many tiles fit in a row of the grid.
+pub fn columns(window_width: usize, posts_per_row: usize, tile_width: usize) -> usize {
+    min(window_width / tile_width, posts_per_row).max(1)
+}
+
 pub fn grid_view<'a>(
     posts: &[Post],
     store: &PostStore,
     window_width: usize,
     posts_per_row: usize,
     tile_width: usize,
-    load_more: bool,
+    highlighted: Option<u32>,
 ) -> Column<'a, Message> {
     let mut grid = column![];
 
-    let chunks = min(window_width / tile_width, posts_per_row);
+    let chunks = columns(window_width, posts_per_row, tile_width);
 
     for chunk in posts.iter().collect::<Vec<_>>().chunks(chunks) {
         let mut r = row![];
 
         for post in chunk {
             let img = store.get_thumbnail(post.id);
-            r = r.push(render(post, img, tile_width as f32));
+            let highlighted = highlighted == Some(post.id);
+            r = r.push(render(post, img, tile_width as f32, None, highlighted));
         }
 
         grid = grid.push(container(r).center_x(Length::Fill).width(Length::Fill));
     }
 
-    if load_more {
-        grid = grid.push(
-            button("load more")
-                .on_press(Message::Search(SearchMessage::LoadMorePosts))
-                .padding(8),
-        );
+    grid.width(Length::Fill)
+}
+
+/// Rows built above and below the ones in view, so they're ready before scrolling reaches them.
+const OVERSCAN_ROWS: usize = 2;
+
+/// Rows of a grid that overlap the pixels from `top` to `bottom`, measured from the top of the
+/// grid.
+pub fn visible_rows(top: f32, bottom: f32, row_height: f32, rows: usize) -> Range<usize> {
+    let first = ((top / row_height).max(0.0) as usize).min(rows);
+    let last = ((bottom / row_height).ceil().max(0.0) as usize).clamp(first, rows);
+    first..last
+}
+
+/// Lays out a tile for each of `items`, `columns` to a row, only building the rows that overlap
+/// `visible`, a range of pixels from the top of the grid. The other rows are left as empty space
+/// of the same height, so the scrollbar and scroll position don't change as the rows in view do.
+fn virtual_rows<'a, T>(
+    items: &[T],
+    columns: usize,
+    tile_width: usize,
+    visible: (f32, f32),
+    mut tile: impl FnMut(&T) -> Option<Element<'a, Message>>,
+) -> Column<'a, Message> {
+    let row_height = tile_height(tile_width as f32);
+    let rows = items.len().div_ceil(columns);
+    let in_view = visible_rows(visible.0, visible.1, row_height, rows);
+    let first = in_view.start.saturating_sub(OVERSCAN_ROWS);
+    let last = (in_view.end + OVERSCAN_ROWS).min(rows);
+
+    let mut grid = column![space().height(first as f32 * row_height)];
+    let shown = &items[(first * columns).min(items.len())..(last * columns).min(items.len())];
+    for chunk in shown.chunks(columns) {
+        let mut r = row![];
+        for item in chunk {
+            if let Some(tile) = tile(item) {
+                r = r.push(tile);
+            }
+        }
+        grid = grid.push(container(r).center_x(Length::Fill).width(Length::Fill));
     }
 
-    grid.width(Length::Fill)
+    grid.push(space().height((rows - last) as f32 * row_height))
+        .width(Length::Fill)
+}
+
+/// Grid of the posts in `ids`, only building the rows in view. See [`virtual_rows`].
+pub fn virtual_grid_view<'a>(
+    ids: &[u32],
+    store: &PostStore,
+    columns: usize,
+    tile_width: usize,
+    visible: (f32, f32),
+    highlighted: Option<u32>,
+) -> Column<'a, Message> {
+    virtual_rows(ids, columns, tile_width, visible, |&id| {
+        let post = store.get_post(id)?;
+        Some(render(
+            post,
+            store.get_thumbnail(id),
+            tile_width as f32,
+            None,
+            highlighted == Some(id),
+        ))
+    })
+}
+
+/// Grid of posts hidden by the blacklist, each paired with the rule that hid it. Like
+/// [`virtual_grid_view`], only the rows in `visible` are built.
+pub fn hidden_grid_view<'a>(
+    hidden: &[(&Post, &str)],
+    store: &PostStore,
+    columns: usize,
+    tile_width: usize,
+    visible: (f32, f32),
+) -> Column<'a, Message> {
+    virtual_rows(hidden, columns, tile_width, visible, |(post, rule)| {
+        let img = store.get_thumbnail(post.id);
+        Some(render(post, img, tile_width as f32, Some(*rule), false))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_in_view() {
+        assert_eq!(visible_rows(0.0, 500.0, 225.0, 10), 0..3);
+        assert_eq!(visible_rows(450.0, 900.0, 225.0, 10), 2..4);
+        assert_eq!(visible_rows(-16.0, 100.0, 225.0, 0), 0..0);
+        assert_eq!(visible_rows(5000.0, 5500.0, 225.0, 10), 10..10);
+    }
 }
@@ -14,11 +14,12 @@ use rustc_hash::FxHashMap;
 use crate::{
     app::{
         message::{FollowedMessage, SettingsMessage, ViewMessage},
+        state::BlacklistSync,
         Message,
     },
     core::{
-        config::{MsgTheme, ViewConfig},
-        media::{cache_dir, gif_dir, image_dir, sample_dir, thumbnail_dir, video_dir},
+        config::{ApiHost, MsgTheme, ViewConfig},
+        media::{cache_dir, gif_dir, image_dir, sample_dir, thumbnail_dir, video_dir, MediaKind},
         store::PostStore,
     },
 };
@@ -27,10 +28,14 @@ pub fn render_settings<'a>(
     username: &'a str,
     api_key: &'a str,
     blacklist_content: &'a Content,
+    blacklist_sync: Option<&'a BlacklistSync>,
+    sync_status: Option<&'a str>,
     followed_tags: &'a FxHashMap<String, Option<u32>>,
     cache: &'a PostStore,
     new_followed_tag: &'a str,
     view_config: &'a ViewConfig,
+    host: &'a ApiHost,
+    custom_host: &'a str,
 ) -> Element<'a, Message> {
     let username_input = text_input("username", username)
         .on_input(|user| Message::Settings(SettingsMessage::UsernameChanged(user)));
@@ -48,9 +53,11 @@ pub fn render_settings<'a>(
     scrollable(
         column![
             text("e621 login").size(16),
+            host_settings(host, custom_host),
             username_input,
             api_key_input,
             blacklist_editor,
+            blacklist_sync_settings(blacklist_sync, sync_status),
             followed_tag_settings(followed_tags, new_followed_tag),
             text("cache info").size(16),
             cache_info,
@@ -64,23 +71,118 @@ pub fn render_settings<'a>(
     .into()
 }
 
+fn blacklist_sync_settings<'a>(
+    blacklist_sync: Option<&'a BlacklistSync>,
+    sync_status: Option<&'a str>,
+) -> Element<'a, Message> {
+    let mut sync = column![row![
+        button("fetch from account")
+            .on_press(Message::Settings(SettingsMessage::FetchAccountBlacklist)),
+        button("upload to account").on_press(Message::Settings(SettingsMessage::PushBlacklist)),
+    ]
+    .spacing(8)];
+
+    if let Some(status) = sync_status {
+        sync = sync.push(text(status).size(12));
+    }
+
+    if let Some(pending) = blacklist_sync {
+        let diff = &pending.diff;
+        let summary = if pending.upload {
+            format!(
+                "Uploading adds {} rules to the account and removes {}, {} unchanged",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.unchanged
+            )
+        } else {
+            format!(
+                "Account blacklist: {} new, {} only here, {} unchanged",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.unchanged
+            )
+        };
+        let mut changes = column![text(summary)];
+        for rule in &diff.added {
+            changes = changes.push(text(format!("+ {rule}")).style(text::success));
+        }
+        for rule in &diff.removed {
+            changes = changes.push(text(format!("- {rule}")).style(text::danger));
+        }
+
+        let cancel =
+            button("cancel").on_press(Message::Settings(SettingsMessage::DiscardAccountBlacklist));
+        let actions = if pending.upload {
+            row![
+                button("upload")
+                    .on_press(Message::Settings(SettingsMessage::ConfirmPushBlacklist))
+                    .style(danger),
+                cancel,
+            ]
+        } else {
+            row![
+                button("merge").on_press(Message::Settings(SettingsMessage::MergeAccountBlacklist)),
+                button("replace")
+                    .on_press(Message::Settings(
+                        SettingsMessage::ReplaceWithAccountBlacklist
+                    ))
+                    .style(danger),
+                cancel,
+            ]
+        };
+        sync = sync.push(changes.spacing(2)).push(actions.spacing(8));
+    }
+
+    container(sync.spacing(8).padding(8))
+        .style(container::bordered_box)
+        .into()
+}
+
+fn host_settings<'a>(host: &'a ApiHost, custom_host: &'a str) -> Element<'a, Message> {
+    let host_options = [
+        ApiHost::E621,
+        ApiHost::E926,
+        ApiHost::Custom(custom_host.to_string()),
+    ];
+
+    let mut settings = column![row![
+        text("Site"),
+        pick_list(host_options, Some(host), |host| {
+            Message::Settings(SettingsMessage::HostSelected(host))
+        })
+    ]
+    .spacing(8)];
+
+    if let ApiHost::Custom(_) = host {
+        settings = settings.push(
+            text_input("https://e621.example.com", custom_host)
+                .on_input(|url| Message::Settings(SettingsMessage::CustomHostChanged(url))),
+        );
+    }
+
+    container(settings.spacing(4).padding(8))
+        .style(container::bordered_box)
+        .into()
+}
+
 fn cache_info<'a>(cache: &'a PostStore) -> Element<'a, Message> {
     let info_lines = column![
         text(format!("Cache size: {}", get_directory_size(cache_dir()))),
         text(format!("Posts stored: {}", cache.posts.len())),
         text(format!(
             "Thumbnails cached: {} ({})",
-            cache.thumbnails.len(),
+            cache.media.len(MediaKind::Thumbnail),
             get_directory_size(thumbnail_dir())
         )),
         text(format!(
             "Samples cached: {} ({})",
-            cache.samples.len(),
+            cache.media.len(MediaKind::Sample),
             get_directory_size(sample_dir())
         )),
         text(format!(
             "Images cached: {} ({})",
-            cache.images.len(),
+            cache.media.len(MediaKind::Image),
             get_directory_size(image_dir())
         )),
         text(format!(
@@ -89,6 +191,10 @@ fn cache_info<'a>(cache: &'a PostStore) -> Element<'a, Message> {
             get_directory_size(gif_dir())
         )),
         text(format!("Gif framesets stored: {}", cache.gif_frames.len())),
+        text(format!(
+            "Media in memory: {:.2}",
+            Byte::from_u64(cache.media.memory_used() as u64).get_appropriate_unit(UnitType::Binary)
+        )),
         text(format!(
             "Videos cached: {} ({})",
             cache.videos.len(),
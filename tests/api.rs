@@ -0,0 +1,580 @@
+//! Integration tests for `core::api` and `core::followed`, against [`common::FakeE621`].
+
+mod common;
+
+use common::FakeE621;
+use msg::api::{
+    autocomplete_tags, create_comment, favorite_post, fetch_all_comments, fetch_blacklisted_tags,
+    fetch_comments, fetch_pool, fetch_pool_posts, fetch_post, fetch_posts, fetch_wiki,
+    search_pools, unfavorite_post, update_blacklisted_tags, update_comment, vote_comment,
+    vote_post, ApiError, FetchPoint,
+};
+use msg::config::Auth;
+use msg::followed::{check_for_updates, check_pool_updates, FollowedPool, FollowedTag};
+use msg::model::{TagCategory, Vote};
+
+fn ids(posts: &[msg::model::Post]) -> Vec<u32> {
+    posts.iter().map(|post| post.id).collect()
+}
+
+async fn server_with_posts(count: u32) -> FakeE621 {
+    let server = FakeE621::start().await;
+    for id in 1..=count {
+        let parity = if id % 2 == 0 { "even" } else { "odd" };
+        server.add_post(id, &["numbered", parity]);
+    }
+    server
+}
+
+#[tokio::test(start_paused = true)]
+async fn fetch_points_paginate() {
+    let server = server_with_posts(10).await;
+    server.set_page_size(3);
+    let base = &server.base_url;
+    let tags = String::from("numbered");
+
+    let first = fetch_posts(base, None, tags.clone(), None).await.unwrap();
+    assert_eq!(ids(&first), vec![10, 9, 8]);
+
+    let second = fetch_posts(base, None, tags.clone(), Some(FetchPoint::Page(2)))
+        .await
+        .unwrap();
+    assert_eq!(ids(&second), vec![7, 6, 5]);
+
+    let before = fetch_posts(base, None, tags.clone(), Some(FetchPoint::Before(5)))
+        .await
+        .unwrap();
+    assert_eq!(ids(&before), vec![4, 3, 2]);
+
+    let after = fetch_posts(base, None, tags.clone(), Some(FetchPoint::After(8)))
+        .await
+        .unwrap();
+    assert_eq!(ids(&after), vec![10, 9]);
+
+    let past_the_end = fetch_posts(base, None, tags, Some(FetchPoint::Page(5)))
+        .await
+        .unwrap();
+    assert!(past_the_end.is_empty());
+
+    let pages: Vec<Option<String>> = server
+        .requests()
+        .iter()
+        .map(|req| req.query.get("page").cloned())
+        .collect();
+    assert_eq!(
+        pages,
+        vec![
+            None,
+            Some("2".into()),
+            Some("b5".into()),
+            Some("a8".into()),
+            Some("5".into())
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn paging_by_id_survives_new_uploads() {
+    let server = server_with_posts(10).await;
+    server.set_page_size(3);
+    let base = &server.base_url;
+    let tags = String::from("numbered");
+
+    let first = fetch_posts(base, None, tags.clone(), None).await.unwrap();
+    assert_eq!(ids(&first), vec![10, 9, 8]);
+
+    // Uploads push everything down a page, so page 2 would repeat the end of page 1.
+    server.add_post(11, &["numbered"]);
+    server.add_post(12, &["numbered"]);
+
+    let point = FetchPoint::following(&tags, 1, first.last().map(|post| post.id));
+    let second = fetch_posts(base, None, tags, Some(point)).await.unwrap();
+    assert_eq!(ids(&second), vec![7, 6, 5]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn tags_are_sent_and_filtered() {
+    let server = server_with_posts(6).await;
+
+    let odd = fetch_posts(&server.base_url, None, "numbered odd".into(), None)
+        .await
+        .unwrap();
+    assert_eq!(ids(&odd), vec![5, 3, 1]);
+    assert_eq!(
+        server.requests()[0].query.get("tags").map(String::as_str),
+        Some("numbered odd")
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn auth_header_is_only_sent_when_logged_in() {
+    let server = server_with_posts(1).await;
+    let auth = FakeE621::auth();
+
+    fetch_posts(&server.base_url, None, "numbered".into(), None)
+        .await
+        .unwrap();
+    fetch_posts(&server.base_url, Some(&auth), "numbered".into(), None)
+        .await
+        .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].authorization, None);
+    assert_eq!(requests[1].authorization, Some(FakeE621::auth_header()));
+}
+
+#[tokio::test(start_paused = true)]
+async fn wrong_api_key_is_unauthorized() {
+    let server = server_with_posts(1).await;
+    let auth = Auth {
+        username: common::USERNAME.into(),
+        api_key: "wrong".into(),
+    };
+
+    let err = fetch_posts(&server.base_url, Some(&auth), "numbered".into(), None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ApiError::Unauthorized), "{err:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn single_posts_are_fetched_by_id() {
+    let server = server_with_posts(3).await;
+
+    let post = fetch_post(&server.base_url, None, 2).await.unwrap();
+    assert_eq!(post.id, 2);
+    assert_eq!(server.requests().last().unwrap().path, "/posts/2.json");
+
+    let err = fetch_post(&server.base_url, None, 99).await.unwrap_err();
+    assert!(matches!(err, ApiError::NotFound), "{err:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn votes_round_trip() {
+    let server = server_with_posts(3).await;
+    let auth = FakeE621::auth();
+    let base = &server.base_url;
+
+    let vote = vote_post(base, &auth, 2, Some(Vote::Upvote)).await.unwrap();
+    assert_eq!(vote, Some(Vote::Upvote));
+    assert_eq!(server.vote(2), Some(1));
+
+    // Sending the same vote again, like a retry would, keeps it.
+    let vote = vote_post(base, &auth, 2, Some(Vote::Upvote)).await.unwrap();
+    assert_eq!(vote, Some(Vote::Upvote));
+    assert_eq!(server.vote(2), Some(1));
+
+    let vote = vote_post(base, &auth, 2, Some(Vote::Downvote))
+        .await
+        .unwrap();
+    assert_eq!(vote, Some(Vote::Downvote));
+    assert_eq!(server.vote(2), Some(-1));
+
+    let vote = vote_post(base, &auth, 2, None).await.unwrap();
+    assert_eq!(vote, None);
+    assert_eq!(server.vote(2), None);
+
+    let err = vote_post(base, &auth, 99, Some(Vote::Upvote))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ApiError::NotFound), "{err:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn favorites_round_trip() {
+    let server = server_with_posts(3).await;
+    let auth = FakeE621::auth();
+    let base = &server.base_url;
+
+    favorite_post(base, &auth, 3).await.unwrap();
+    assert!(server.is_favorited(3));
+
+    // Favoriting twice isn't an error.
+    favorite_post(base, &auth, 3).await.unwrap();
+
+    let posts = fetch_posts(base, Some(&auth), "numbered".into(), None)
+        .await
+        .unwrap();
+    let favorited: Vec<u32> = posts
+        .iter()
+        .filter(|post| post.is_favorited)
+        .map(|post| post.id)
+        .collect();
+    assert_eq!(favorited, vec![3]);
+
+    unfavorite_post(base, &auth, 3).await.unwrap();
+    assert!(!server.is_favorited(3));
+}
+
+#[tokio::test(start_paused = true)]
+async fn error_responses_are_typed() {
+    let server = server_with_posts(1).await;
+    let auth = FakeE621::auth();
+    let base = &server.base_url;
+
+    server.fail_next(403, r#"{"success":false,"reason":"Post is locked"}"#);
+    let err = favorite_post(base, &auth, 1).await.unwrap_err();
+    assert!(
+        matches!(&err, ApiError::Forbidden(reason) if reason == "Post is locked"),
+        "{err:?}"
+    );
+
+    server.fail_next(
+        422,
+        r#"{"success":false,"message":"You cannot search for more than 40 tags","code":null}"#,
+    );
+    let err = fetch_posts(base, None, "numbered".into(), None)
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "You cannot search for more than 40 tags");
+
+    // Error pages aren't parsed as posts.
+    server.fail_next(404, "<html><body>Not found</body></html>");
+    let err = fetch_posts(base, None, "numbered".into(), None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ApiError::NotFound), "{err:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn transient_errors_are_retried() {
+    let server = server_with_posts(2).await;
+
+    server.fail_next(503, "<html>Down for maintenance</html>");
+    server.fail_next(502, "<html>Bad gateway</html>");
+    let posts = fetch_posts(&server.base_url, None, "numbered".into(), None)
+        .await
+        .unwrap();
+
+    assert_eq!(ids(&posts), vec![2, 1]);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn comments_are_fetched_per_post() {
+    let server = server_with_posts(2).await;
+    server.add_comment(1, 1, "first!");
+    server.add_comment(2, 1, "second");
+
+    let comments = fetch_comments(&server.base_url, None, 1, None)
+        .await
+        .unwrap();
+    let bodies: Vec<&str> = comments.iter().map(|c| c.body.as_str()).collect();
+    assert_eq!(bodies, vec!["first!", "second"]);
+
+    let none = fetch_comments(&server.base_url, None, 2, None)
+        .await
+        .unwrap();
+    assert!(none.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn all_comment_pages_are_fetched() {
+    let server = server_with_posts(2).await;
+    for id in 1..=80 {
+        server.add_comment(id, 1, &format!("comment {id}"));
+    }
+    server.add_comment(81, 2, "elsewhere");
+
+    let comments = fetch_all_comments(&server.base_url, None, 1).await.unwrap();
+    let comment_ids: Vec<u32> = comments.iter().map(|c| c.id).collect();
+    assert_eq!(comment_ids, (1..=80).collect::<Vec<_>>());
+    assert_eq!(server.requests().len(), 2);
+
+    // Bad JSON is an error, not an empty comment section.
+    server.fail_next(200, r#"{"comments": "nope"}"#);
+    let err = fetch_all_comments(&server.base_url, None, 1)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ApiError::Deserialize(_)), "{err:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn comments_are_posted_edited_and_voted_on() {
+    let server = server_with_posts(1).await;
+    let auth = FakeE621::auth();
+    let base = &server.base_url;
+
+    let comment = create_comment(base, &auth, 1, "[b]nice[/b]").await.unwrap();
+    assert_eq!((comment.post_id, comment.body.as_str()), (1, "[b]nice[/b]"));
+
+    update_comment(base, &auth, comment.id, "edited")
+        .await
+        .unwrap();
+    let comments = fetch_comments(base, None, 1, None).await.unwrap();
+    assert_eq!(comments[0].body, "edited");
+
+    let vote = vote_comment(base, &auth, comment.id, Some(Vote::Downvote))
+        .await
+        .unwrap();
+    assert_eq!(vote, Some(Vote::Downvote));
+    assert_eq!(server.comment_vote(comment.id), Some(-1));
+    // Voting the same way again keeps the vote rather than taking it back.
+    vote_comment(base, &auth, comment.id, Some(Vote::Downvote))
+        .await
+        .unwrap();
+    assert_eq!(server.comment_vote(comment.id), Some(-1));
+    vote_comment(base, &auth, comment.id, None).await.unwrap();
+    assert_eq!(server.comment_vote(comment.id), None);
+
+    let err = create_comment(base, &Auth::default(), 1, "anonymous")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ApiError::Unauthorized), "{err:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn comments_are_not_posted_twice() {
+    let server = server_with_posts(1).await;
+    let auth = FakeE621::auth();
+
+    server.fail_next(500, "");
+    let err = create_comment(&server.base_url, &auth, 1, "once")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ApiError::Status { .. }), "{err:?}");
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn blacklist_round_trips_through_account() {
+    let server = FakeE621::start().await;
+    let auth = FakeE621::auth();
+    server.set_blacklisted_tags("gore\nscat rating:e");
+
+    let rules = fetch_blacklisted_tags(&server.base_url, &auth)
+        .await
+        .unwrap();
+    assert_eq!(rules, vec!["gore", "scat rating:e"]);
+
+    let new_rules = vec!["gore".to_string(), "feral -solo".to_string()];
+    update_blacklisted_tags(&server.base_url, &auth, &new_rules)
+        .await
+        .unwrap();
+    assert_eq!(server.blacklisted_tags(), "gore\nferal -solo");
+}
+
+#[tokio::test(start_paused = true)]
+async fn followed_tags_only_get_new_posts() {
+    let server = server_with_posts(6).await;
+    let followed = vec![
+        FollowedTag {
+            tag: "odd".into(),
+            last_seen: Some(3),
+        },
+        FollowedTag {
+            tag: "even".into(),
+            last_seen: None,
+        },
+    ];
+
+    let updates = check_for_updates(&server.base_url, &followed, None)
+        .await
+        .unwrap();
+    assert_eq!(ids(&updates["odd"]), vec![5]);
+    assert_eq!(ids(&updates["even"]), vec![6, 4, 2]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn followed_tags_stop_when_unauthorized() {
+    let server = server_with_posts(2).await;
+    let followed = vec![FollowedTag {
+        tag: "odd".into(),
+        last_seen: None,
+    }];
+    let auth = Auth {
+        username: common::USERNAME.into(),
+        api_key: "wrong".into(),
+    };
+
+    let err = check_for_updates(&server.base_url, &followed, Some(&auth))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ApiError::Unauthorized), "{err:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn pools_are_searched_by_name() {
+    let server = FakeE621::start().await;
+    server.add_pool(1, "Some_Comic", &[]);
+    server.add_pool(2, "Another_Comic_Part_2", &[]);
+    server.add_pool(3, "Sketches", &[]);
+    server.add_pool(4, "Cats_&_Dogs", &[]);
+    server.add_pool(5, "Cats_Only", &[]);
+
+    let pools = search_pools(&server.base_url, None, "comic", None)
+        .await
+        .unwrap();
+    let names: Vec<String> = pools.iter().map(|pool| pool.display_name()).collect();
+    assert_eq!(names, vec!["Some Comic", "Another Comic Part 2"]);
+
+    let pools = search_pools(&server.base_url, None, "another comic*", None)
+        .await
+        .unwrap();
+    assert_eq!(pools.len(), 1);
+    assert_eq!(
+        server.requests()[1]
+            .query
+            .get("search[name_matches]")
+            .map(String::as_str),
+        Some("another_comic*")
+    );
+
+    let pools = search_pools(&server.base_url, None, "cats & dogs", None)
+        .await
+        .unwrap();
+    assert_eq!(pools.len(), 1);
+    assert_eq!(pools[0].id, 4);
+}
+
+#[tokio::test(start_paused = true)]
+async fn pool_posts_come_back_in_reading_order() {
+    let server = server_with_posts(100).await;
+    // Out of ID order, spanning more than one page of results, with a deleted page.
+    let mut pages: Vec<u32> = (1..=90).rev().collect();
+    pages.swap(0, 5);
+    pages.push(500);
+    server.add_pool(9, "Long_Comic", &pages);
+
+    let pool = fetch_pool(&server.base_url, None, 9).await.unwrap();
+    assert_eq!(pool.post_ids, pages);
+
+    let posts = fetch_pool_posts(&server.base_url, None, &pool)
+        .await
+        .unwrap();
+    assert_eq!(ids(&posts), pages[..90].to_vec());
+    assert_eq!(server.requests().len(), 3);
+
+    let err = fetch_pool(&server.base_url, None, 404).await.unwrap_err();
+    assert!(matches!(err, ApiError::NotFound), "{err:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn followed_pools_report_new_pages() {
+    let server = server_with_posts(6).await;
+    server.add_pool(1, "Comic", &[2, 4]);
+    server.add_pool(2, "Finished", &[1, 3]);
+
+    let comic = fetch_pool(&server.base_url, None, 1).await.unwrap();
+    let finished = fetch_pool(&server.base_url, None, 2).await.unwrap();
+    let followed = vec![FollowedPool::new(&comic), FollowedPool::new(&finished)];
+
+    server.add_pool(1, "Comic", &[2, 4, 5, 6]);
+    let updates = check_pool_updates(&server.base_url, &followed, None)
+        .await
+        .unwrap();
+
+    assert_eq!(updates.len(), 2);
+    assert_eq!(updates[0].pool.id, 1);
+    assert_eq!(ids(&updates[0].posts), vec![5, 6]);
+    assert!(updates[1].posts.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn followed_pools_skip_ones_that_fail() {
+    let server = server_with_posts(6).await;
+    server.add_pool(1, "Comic", &[2]);
+    server.add_pool(2, "Other_Comic", &[1]);
+    let followed = vec![
+        FollowedPool::new(&fetch_pool(&server.base_url, None, 1).await.unwrap()),
+        FollowedPool::new(&fetch_pool(&server.base_url, None, 2).await.unwrap()),
+    ];
+    server.add_pool(1, "Comic", &[2, 4]);
+    server.add_pool(2, "Other_Comic", &[1, 3]);
+
+    server.fail_next_to("/posts.json", 422, r#"{"success":false,"reason":"nope"}"#);
+    let updates = check_pool_updates(&server.base_url, &followed, None)
+        .await
+        .unwrap();
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].pool.id, 2);
+    assert_eq!(ids(&updates[0].posts), vec![3]);
+
+    // Nothing else would get through either.
+    server.fail_next_to("/posts.json", 401, r#"{"success":false,"reason":"nope"}"#);
+    let err = check_pool_updates(&server.base_url, &followed, None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ApiError::Unauthorized), "{err:?}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn tags_autocomplete_with_aliases() {
+    let server = FakeE621::start().await;
+    server.add_tag("wolf", 5, 200_000);
+    server.add_tag("wolfy_nail", 1, 300);
+    server.add_tag("canine", 5, 900_000);
+    server.add_alias("wolves", "wolf");
+    server.add_alias("dog", "canine");
+
+    let suggestions = autocomplete_tags(&server.base_url, None, "wol")
+        .await
+        .unwrap();
+    let found: Vec<(&str, Option<&str>)> = suggestions
+        .iter()
+        .map(|tag| (tag.name.as_str(), tag.antecedent_name.as_deref()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("wolf", None),
+            ("wolf", Some("wolves")),
+            ("wolfy_nail", None)
+        ]
+    );
+    assert_eq!(suggestions[0].category, TagCategory::Species);
+    assert_eq!(suggestions[2].category, TagCategory::Artist);
+
+    // Too short to autocomplete, so nothing is sent.
+    let none = autocomplete_tags(&server.base_url, None, "wo")
+        .await
+        .unwrap();
+    assert!(none.is_empty());
+    assert_eq!(server.requests().len(), 1);
+
+    // The prefix goes through whole, not cut off at the `&`.
+    server.add_tag("r&b", 0, 40);
+    server.add_tag("rabbit", 5, 80_000);
+    let suggestions = autocomplete_tags(&server.base_url, None, "r&b")
+        .await
+        .unwrap();
+    let names: Vec<&str> = suggestions.iter().map(|tag| tag.name.as_str()).collect();
+    assert_eq!(names, vec!["r&b"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn wiki_view_collects_page_and_tag_relations() {
+    let server = server_with_posts(4).await;
+    server.add_wiki_page("odd", "Posts with an [b]odd[/b] ID.");
+    server.add_alias("uneven", "odd");
+    server.add_implication("odd", "numbered");
+
+    let wiki = fetch_wiki(&server.base_url, None, "odd").await.unwrap();
+    assert_eq!(wiki.page.unwrap().body, "Posts with an [b]odd[/b] ID.");
+    assert_eq!(wiki.aliases.len(), 1);
+    assert_eq!(wiki.aliases[0].antecedent_name, "uneven");
+    assert_eq!(wiki.implications[0].consequent_name, "numbered");
+    let related: Vec<&str> = wiki.related.iter().map(|tag| tag.name.as_str()).collect();
+    assert_eq!(related, vec!["numbered"]);
+
+    // Tags without a page or any relations still get a view.
+    let wiki = fetch_wiki(&server.base_url, None, "a/b (c)").await.unwrap();
+    assert!(wiki.page.is_none());
+    assert!(wiki.aliases.is_empty() && wiki.implications.is_empty());
+    assert_eq!(
+        server.requests()[4]
+            .query
+            .get("search[title]")
+            .map(String::as_str),
+        Some("a/b (c)")
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn wiki_view_fails_on_malformed_tag_relations() {
+    let server = server_with_posts(4).await;
+    server.fail_next_to("/tag_implications.json", 200, r#"{"tag_implications": {}}"#);
+
+    let err = fetch_wiki(&server.base_url, None, "odd").await.unwrap_err();
+    assert!(matches!(err, ApiError::Deserialize(_)), "{err:?}");
+}
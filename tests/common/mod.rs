@@ -0,0 +1,771 @@
+//! An in-process fake e621 for integration tests.
+//!
+//! Implements just enough of the API for msg: post search with `page`/`a`/`b` pagination,
+//! pools, tag autocomplete, wiki pages, comments, votes, favorites, the user's blacklist and media files. Every request is recorded
+//! so tests can check what was sent, and errors can be queued up with [`FakeE621::fail_next`].
+
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
+use msg::config::Auth;
+use msg::fixture::post_json;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const USERNAME: &str = "tester";
+pub const API_KEY: &str = "secret_key";
+pub const USER_ID: u32 = 42;
+
+/// A request the fake server received.
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub authorization: Option<String>,
+    pub body: String,
+}
+
+#[derive(Default)]
+struct State {
+    /// Posts by ID, as API JSON.
+    posts: BTreeMap<u32, Value>,
+    pools: BTreeMap<u32, Value>,
+    /// Tags by name, as autocomplete JSON.
+    tags: BTreeMap<String, Value>,
+    /// Alias name to the tag it points at.
+    aliases: BTreeMap<String, String>,
+    implications: Vec<(String, String)>,
+    /// Wiki bodies by title.
+    wiki_pages: BTreeMap<String, String>,
+    comments: Vec<Value>,
+    votes: HashMap<u32, i8>,
+    comment_votes: HashMap<u32, i8>,
+    favorites: HashSet<u32>,
+    blacklisted_tags: String,
+    files: HashMap<String, Vec<u8>>,
+    failures: VecDeque<(u16, String)>,
+    path_failures: HashMap<String, (u16, String)>,
+    requests: Vec<Recorded>,
+    page_size: usize,
+}
+
+pub struct FakeE621 {
+    pub base_url: String,
+    state: Arc<Mutex<State>>,
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: u16, value: Value) -> Self {
+        Response {
+            status,
+            content_type: "application/json",
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    fn empty(status: u16) -> Self {
+        Response {
+            status,
+            content_type: "text/plain",
+            body: Vec::new(),
+        }
+    }
+
+    fn error(status: u16, reason: &str) -> Self {
+        Response::json(status, json!({ "success": false, "reason": reason }))
+    }
+}
+
+impl FakeE621 {
+    /// Starts the server on a free local port.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State {
+            page_size: 75,
+            ..Default::default()
+        }));
+
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((socket, _)) = listener.accept().await else {
+                    break;
+                };
+                tokio::spawn(serve(socket, server_state.clone()));
+            }
+        });
+
+        FakeE621 { base_url, state }
+    }
+
+    /// Auth that the server accepts.
+    pub fn auth() -> Auth {
+        Auth {
+            username: USERNAME.to_string(),
+            api_key: API_KEY.to_string(),
+        }
+    }
+
+    /// The `Authorization` header sent for [`FakeE621::auth`].
+    pub fn auth_header() -> String {
+        let credentials = format!("{USERNAME}:{API_KEY}");
+        format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        )
+    }
+
+    pub fn set_page_size(&self, size: usize) {
+        self.state.lock().unwrap().page_size = size;
+    }
+
+    /// Adds a post with the given general tags and a preview served from `/data/preview/{id}.jpg`.
+    pub fn add_post(&self, id: u32, tags: &[&str]) {
+        let post = post_json(json!({
+            "id": id,
+            "file": {
+                "width": 640, "height": 480, "size": 1234,
+                "md5": format!("{id:032x}"),
+                "url": format!("{}/data/{id}.png", self.base_url)
+            },
+            "preview": {
+                "width": 150, "height": 113,
+                "url": format!("{}/data/preview/{id}.jpg", self.base_url)
+            },
+            "sample": { "width": 640, "height": 480 },
+            "score": { "up": 0, "down": 0, "total": 0 },
+            "tags": {
+                "general": tags, "artist": [], "contributor": [], "species": [], "meta": []
+            },
+            "fav_count": 0,
+            "uploader_id": 1,
+            "uploader_name": "uploader"
+        }));
+        self.state.lock().unwrap().posts.insert(id, post);
+    }
+
+    pub fn add_pool(&self, id: u32, name: &str, post_ids: &[u32]) {
+        let pool = json!({
+            "id": id,
+            "name": name,
+            "created_at": "2024-01-01T00:00:00.000-05:00",
+            "updated_at": "2024-01-03T00:00:00.000-05:00",
+            "creator_id": 1,
+            "creator_name": "uploader",
+            "description": "",
+            "is_active": true,
+            "category": "series",
+            "post_ids": post_ids,
+            "post_count": post_ids.len()
+        });
+        self.state.lock().unwrap().pools.insert(id, pool);
+    }
+
+    pub fn add_tag(&self, name: &str, category: u8, post_count: u32) {
+        let mut state = self.state.lock().unwrap();
+        let id = state.tags.len() + 1;
+        state.tags.insert(
+            name.to_string(),
+            json!({
+                "id": id,
+                "name": name,
+                "post_count": post_count,
+                "category": category,
+                "antecedent_name": null
+            }),
+        );
+    }
+
+    pub fn add_alias(&self, alias: &str, tag: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .aliases
+            .insert(alias.to_string(), tag.to_string());
+    }
+
+    /// Makes `tag` imply `implied`.
+    pub fn add_implication(&self, tag: &str, implied: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .implications
+            .push((tag.to_string(), implied.to_string()));
+    }
+
+    pub fn add_wiki_page(&self, title: &str, body: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .wiki_pages
+            .insert(title.to_string(), body.to_string());
+    }
+
+    pub fn add_comment(&self, id: u32, post_id: u32, body: &str) {
+        self.state.lock().unwrap().comments.push(json!({
+            "id": id,
+            "post_id": post_id,
+            "creator_name": "commenter",
+            "body": body,
+            "score": 0,
+            "created_at": "2024-01-02T00:00:00.000-05:00",
+            "updated_at": "2024-01-02T00:00:00.000-05:00"
+        }));
+    }
+
+    pub fn add_file(&self, path: &str, bytes: Vec<u8>) {
+        self.state
+            .lock()
+            .unwrap()
+            .files
+            .insert(path.to_string(), bytes);
+    }
+
+    pub fn set_blacklisted_tags(&self, tags: &str) {
+        self.state.lock().unwrap().blacklisted_tags = tags.to_string();
+    }
+
+    pub fn blacklisted_tags(&self) -> String {
+        self.state.lock().unwrap().blacklisted_tags.clone()
+    }
+
+    /// Answers the next request with `status` and `body` instead of handling it.
+    pub fn fail_next(&self, status: u16, body: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .push_back((status, body.to_string()));
+    }
+
+    /// Answers the next request for `path` with `status` and `body` instead of handling it.
+    pub fn fail_next_to(&self, path: &str, status: u16, body: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .path_failures
+            .insert(path.to_string(), (status, body.to_string()));
+    }
+
+    pub fn vote(&self, post_id: u32) -> Option<i8> {
+        self.state.lock().unwrap().votes.get(&post_id).copied()
+    }
+
+    pub fn comment_vote(&self, comment_id: u32) -> Option<i8> {
+        self.state
+            .lock()
+            .unwrap()
+            .comment_votes
+            .get(&comment_id)
+            .copied()
+    }
+
+    pub fn is_favorited(&self, post_id: u32) -> bool {
+        self.state.lock().unwrap().favorites.contains(&post_id)
+    }
+
+    pub fn requests(&self) -> Vec<Recorded> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+async fn serve(mut socket: TcpStream, state: Arc<Mutex<State>>) {
+    let Some(request) = read_request(&mut socket).await else {
+        return;
+    };
+    let response = handle(&state, request);
+
+    let head = format!(
+        "HTTP/1.1 {} Fake\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    let _ = socket.write_all(head.as_bytes()).await;
+    let _ = socket.write_all(&response.body).await;
+    let _ = socket.shutdown().await;
+}
+
+async fn read_request(socket: &mut TcpStream) -> Option<Recorded> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?.to_string();
+
+    let mut headers = HashMap::new();
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let content_length: usize = headers
+        .get("content-length")
+        .and_then(|len| len.parse().ok())
+        .unwrap_or(0);
+    while buf.len() < header_end + content_length {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let url = url::Url::parse(&format!("http://fake{target}")).ok()?;
+    Some(Recorded {
+        method,
+        path: url.path().to_string(),
+        query: url.query_pairs().into_owned().collect(),
+        authorization: headers.get("authorization").cloned(),
+        body: String::from_utf8_lossy(&buf[header_end..]).to_string(),
+    })
+}
+
+fn handle(state: &Mutex<State>, request: Recorded) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push(request.clone());
+
+    let failure = state
+        .failures
+        .pop_front()
+        .or_else(|| state.path_failures.remove(&request.path));
+    if let Some((status, body)) = failure {
+        return Response {
+            status,
+            content_type: "application/json",
+            body: body.into_bytes(),
+        };
+    }
+
+    let authed = match &request.authorization {
+        None => false,
+        Some(header) if *header == FakeE621::auth_header() => true,
+        Some(_) => return Response::error(401, "Authentication failed"),
+    };
+
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["posts.json"]) => list_posts(&state, &request, authed),
+        ("GET", ["pools.json"]) => {
+            let pattern = request
+                .query
+                .get("search[name_matches]")
+                .cloned()
+                .unwrap_or_else(|| "*".into())
+                .to_lowercase();
+            let pools: Vec<&Value> = state
+                .pools
+                .values()
+                .filter(|pool| {
+                    let name = pool["name"].as_str().unwrap_or_default().to_lowercase();
+                    msg::blacklist::wildcard_match(&pattern, &name)
+                })
+                .collect();
+            Response::json(200, json!(pools))
+        }
+        ("GET", ["tags", "autocomplete.json"]) => {
+            let prefix = request
+                .query
+                .get("search[name_matches]")
+                .cloned()
+                .unwrap_or_default();
+            let mut tags: Vec<Value> = state
+                .tags
+                .values()
+                .filter(|tag| {
+                    tag["name"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .starts_with(&prefix)
+                })
+                .cloned()
+                .collect();
+            for (alias, name) in &state.aliases {
+                if let Some(tag) = state.tags.get(name).filter(|_| alias.starts_with(&prefix)) {
+                    let mut tag = tag.clone();
+                    tag["antecedent_name"] = json!(alias);
+                    tags.push(tag);
+                }
+            }
+            tags.sort_by_key(|tag| std::cmp::Reverse(tag["post_count"].as_u64()));
+            tags.truncate(10);
+            Response::json(200, json!(tags))
+        }
+        ("GET", ["wiki_pages.json"]) => {
+            let title = request
+                .query
+                .get("search[title]")
+                .cloned()
+                .unwrap_or_default();
+            let pages: Vec<Value> = state
+                .wiki_pages
+                .iter()
+                .enumerate()
+                .filter(|(_, (name, _))| **name == title)
+                .map(|(i, (name, body))| {
+                    json!({
+                        "id": i + 1,
+                        "title": name,
+                        "body": body,
+                        "other_names": [],
+                        "created_at": "2024-01-01T00:00:00.000-05:00",
+                        "updated_at": "2024-01-02T00:00:00.000-05:00",
+                        "is_locked": false
+                    })
+                })
+                .collect();
+            Response::json(200, json!(pages))
+        }
+        ("GET", [kind @ ("tag_aliases.json" | "tag_implications.json")]) => {
+            let name = request
+                .query
+                .get("search[name_matches]")
+                .cloned()
+                .unwrap_or_default();
+            let pairs: Vec<(String, String)> = if *kind == "tag_aliases.json" {
+                state
+                    .aliases
+                    .iter()
+                    .map(|(a, b)| (a.clone(), b.clone()))
+                    .collect()
+            } else {
+                state.implications.clone()
+            };
+            let relations: Vec<Value> = pairs
+                .iter()
+                .enumerate()
+                .filter(|(_, (a, b))| *a == name || *b == name)
+                .map(|(i, (a, b))| {
+                    json!({
+                        "id": i + 1,
+                        "antecedent_name": a,
+                        "consequent_name": b,
+                        "status": "active"
+                    })
+                })
+                .collect();
+            if relations.is_empty() {
+                let key = kind.trim_end_matches(".json");
+                Response::json(200, json!({ key: [] }))
+            } else {
+                Response::json(200, json!(relations))
+            }
+        }
+        ("GET", ["related_tag.json"]) => {
+            // Tags sharing a post with the query, most shared first.
+            let query = request
+                .query
+                .get("search[query]")
+                .cloned()
+                .unwrap_or_default();
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for post in state.posts.values() {
+                let tags: Vec<&str> = post["tags"]["general"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .collect();
+                if tags.contains(&query.as_str()) {
+                    for tag in tags {
+                        *counts.entry(tag.to_string()).or_default() += 1;
+                    }
+                }
+            }
+            let mut related: Vec<(String, usize)> = counts.into_iter().collect();
+            related.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+            let tags: Vec<Value> = related.iter().map(|(tag, _)| json!([tag, 0])).collect();
+            Response::json(
+                200,
+                json!({ "query": query, "category": null, "tags": tags }),
+            )
+        }
+        ("GET", ["posts", id]) => {
+            let id = id.trim_end_matches(".json").parse::<u32>().unwrap_or(0);
+            match state.posts.get(&id) {
+                Some(post) => Response::json(200, json!({ "post": post })),
+                None => Response::error(404, "Not found"),
+            }
+        }
+        ("GET", ["pools", id]) => {
+            let id = id.trim_end_matches(".json").parse::<u32>().unwrap_or(0);
+            match state.pools.get(&id) {
+                Some(pool) => Response::json(200, pool.clone()),
+                None => Response::error(404, "Not found"),
+            }
+        }
+        ("GET", ["comments.json"]) => {
+            let post_id: Option<u32> = request
+                .query
+                .get("search[post_id]")
+                .and_then(|id| id.parse().ok());
+            let number = |key: &str| request.query.get(key).and_then(|n| n.parse::<usize>().ok());
+            let (limit, page) = (number("limit").unwrap_or(25), number("page").unwrap_or(1));
+            let comments: Vec<&Value> = state
+                .comments
+                .iter()
+                .filter(|c| post_id.is_none() || c["post_id"].as_u64() == post_id.map(u64::from))
+                .skip(page.saturating_sub(1) * limit)
+                .take(limit)
+                .collect();
+            if comments.is_empty() {
+                Response::json(200, json!({ "comments": [] }))
+            } else {
+                Response::json(200, json!(comments))
+            }
+        }
+        ("POST", ["comments.json"]) => {
+            if !authed {
+                return Response::error(401, "You must be logged in");
+            }
+            let body: Value = serde_json::from_str(&request.body).unwrap_or_default();
+            let Some(post_id) = body["comment"]["post_id"].as_u64() else {
+                return Response::error(422, "post_id is required");
+            };
+            let id = state.comments.len() as u64 + 1;
+            let comment = json!({
+                "id": id,
+                "post_id": post_id,
+                "creator_name": "user",
+                "body": body["comment"]["body"],
+                "score": 0,
+                "created_at": "2024-01-03T00:00:00.000-05:00",
+                "updated_at": "2024-01-03T00:00:00.000-05:00"
+            });
+            state.comments.push(comment.clone());
+            Response::json(201, comment)
+        }
+        ("PATCH", ["comments", id]) => {
+            if !authed {
+                return Response::error(401, "You must be logged in");
+            }
+            let id = id.trim_end_matches(".json").parse::<u64>().unwrap_or(0);
+            let body: Value = serde_json::from_str(&request.body).unwrap_or_default();
+            match state.comments.iter_mut().find(|c| c["id"] == id) {
+                Some(comment) => {
+                    comment["body"] = body["comment"]["body"].clone();
+                    Response::empty(204)
+                }
+                None => Response::error(404, "Not found"),
+            }
+        }
+        (method, ["comments", id, "votes.json"]) => {
+            if !authed {
+                return Response::error(401, "You must be logged in");
+            }
+            let Some(id) = id.parse::<u32>().ok() else {
+                return Response::error(404, "Not found");
+            };
+            match method {
+                "POST" => {
+                    let body: Value = serde_json::from_str(&request.body).unwrap_or_default();
+                    let score = cast_vote(&mut state.comment_votes, id, &body);
+                    Response::json(200, json!({ "score": score, "our_score": score }))
+                }
+                "DELETE" => {
+                    state.comment_votes.remove(&id);
+                    Response::empty(200)
+                }
+                _ => Response::error(405, "Method not allowed"),
+            }
+        }
+        (method, ["posts", id, "votes.json"]) => {
+            if !authed {
+                return Response::error(401, "You must be logged in");
+            }
+            let Some(id) = id
+                .parse::<u32>()
+                .ok()
+                .filter(|id| state.posts.contains_key(id))
+            else {
+                return Response::error(404, "Not found");
+            };
+            match method {
+                "POST" => {
+                    let body: Value = serde_json::from_str(&request.body).unwrap_or_default();
+                    let score = cast_vote(&mut state.votes, id, &body);
+                    Response::json(
+                        200,
+                        json!({ "score": score, "up": 0, "down": 0, "our_score": score }),
+                    )
+                }
+                "DELETE" => {
+                    state.votes.remove(&id);
+                    Response::empty(200)
+                }
+                _ => Response::error(405, "Method not allowed"),
+            }
+        }
+        ("POST", ["favorites.json"]) => {
+            if !authed {
+                return Response::error(401, "You must be logged in");
+            }
+            let body: Value = serde_json::from_str(&request.body).unwrap_or_default();
+            let Some(id) = body["post_id"].as_u64().map(|id| id as u32) else {
+                return Response::error(422, "post_id is required");
+            };
+            if !state.posts.contains_key(&id) {
+                return Response::error(404, "Not found");
+            }
+            if !state.favorites.insert(id) {
+                return Response::json(
+                    422,
+                    json!({
+                        "success": false,
+                        "message": "You have already favorited this post",
+                        "code": null
+                    }),
+                );
+            }
+            Response::json(201, state.posts[&id].clone())
+        }
+        ("DELETE", ["favorites", id]) => {
+            if !authed {
+                return Response::error(401, "You must be logged in");
+            }
+            let id = id.trim_end_matches(".json").parse::<u32>().unwrap_or(0);
+            state.favorites.remove(&id);
+            Response::empty(204)
+        }
+        ("GET", ["users", name]) => {
+            let name = name.trim_end_matches(".json");
+            if name != USERNAME && name != USER_ID.to_string() {
+                return Response::error(404, "Not found");
+            }
+            let mut user = json!({ "id": USER_ID, "name": USERNAME });
+            if authed {
+                user["blacklisted_tags"] = json!(state.blacklisted_tags);
+            }
+            Response::json(200, user)
+        }
+        ("PATCH", ["users", id]) => {
+            if !authed {
+                return Response::error(401, "You must be logged in");
+            }
+            if id.trim_end_matches(".json") != USER_ID.to_string() {
+                return Response::error(403, "Access Denied");
+            }
+            let form: HashMap<String, String> =
+                url::form_urlencoded::parse(request.body.as_bytes())
+                    .into_owned()
+                    .collect();
+            if let Some(tags) = form.get("user[blacklisted_tags]") {
+                state.blacklisted_tags = tags.clone();
+            }
+            Response::empty(204)
+        }
+        ("GET", ["data", ..]) => match state.files.get(request.path.trim_start_matches("/data/")) {
+            Some(bytes) => Response {
+                status: 200,
+                content_type: "application/octet-stream",
+                body: bytes.clone(),
+            },
+            None => Response::empty(404),
+        },
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+fn list_posts(state: &State, request: &Recorded, authed: bool) -> Response {
+    let tags: Vec<&str> = request
+        .query
+        .get("tags")
+        .map(|tags| tags.split_whitespace().collect())
+        .unwrap_or_default();
+    let page = request.query.get("page").map(String::as_str).unwrap_or("1");
+    let limit = request
+        .query
+        .get("limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(state.page_size);
+
+    let matching = state.posts.values().rev().filter(|post| {
+        tags.iter().all(|tag| match tag.strip_prefix("pool:") {
+            Some(pool_id) => state
+                .pools
+                .get(&pool_id.parse().unwrap_or(0))
+                .and_then(|pool| pool["post_ids"].as_array())
+                .is_some_and(|ids| ids.contains(&post["id"])),
+            None => post["tags"]["general"]
+                .as_array()
+                .is_some_and(|general| general.iter().any(|t| t == tag)),
+        })
+    });
+
+    let post_id = |post: &&Value| post["id"].as_u64().unwrap_or(0) as u32;
+    let selected: Vec<Value> = if let Some(before) = page.strip_prefix('b') {
+        let before: u32 = before.parse().unwrap_or(0);
+        matching
+            .filter(|post| post_id(post) < before)
+            .take(limit)
+            .cloned()
+            .collect()
+    } else if let Some(after) = page.strip_prefix('a') {
+        let after: u32 = after.parse().unwrap_or(0);
+        matching
+            .filter(|post| post_id(post) > after)
+            .take(limit)
+            .cloned()
+            .collect()
+    } else {
+        let Ok(page) = page.parse::<usize>() else {
+            return Response::error(422, "Invalid page");
+        };
+        matching
+            .skip(page.saturating_sub(1) * limit)
+            .take(limit)
+            .cloned()
+            .collect()
+    };
+
+    let posts: Vec<Value> = selected
+        .into_iter()
+        .map(|mut post| {
+            let id = post["id"].as_u64().unwrap_or(0) as u32;
+            post["is_favorited"] = json!(authed && state.favorites.contains(&id));
+            post
+        })
+        .collect();
+
+    Response::json(200, json!({ "posts": posts }))
+}
+
+/// Records a vote the way e621 does: voting the same way again takes the vote back, unless
+/// `no_unvote` is set. Returns the score now on record.
+fn cast_vote(votes: &mut HashMap<u32, i8>, id: u32, body: &Value) -> i8 {
+    let score = body["score"].as_i64().unwrap_or(0) as i8;
+    let no_unvote = body["no_unvote"].as_bool().unwrap_or(false);
+    if votes.get(&id) == Some(&score) && !no_unvote {
+        votes.remove(&id);
+        return 0;
+    }
+    votes.insert(id, score);
+    score
+}
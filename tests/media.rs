@@ -0,0 +1,102 @@
+//! Integration tests for `core::media`, against [`common::FakeE621`].
+
+mod common;
+
+use std::io::Cursor;
+
+use common::FakeE621;
+use msg::media::{cache_dir, fetch_gif, fetch_image, fetch_preview, set_cache_dir};
+use msg::model::File;
+use tempfile::TempDir;
+
+/// Points the media cache at a fresh directory. Every test in this file shares it, so they use
+/// different post IDs.
+fn temp_cache() -> &'static TempDir {
+    static CACHE: std::sync::OnceLock<TempDir> = std::sync::OnceLock::new();
+    CACHE.get_or_init(|| {
+        let dir = TempDir::new().expect("Couldn't make TempDir");
+        set_cache_dir(dir.path().to_path_buf());
+        dir
+    })
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image::DynamicImage::new_rgb8(width, height)
+        .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+        .unwrap();
+    bytes
+}
+
+#[tokio::test(start_paused = true)]
+async fn previews_are_downloaded_and_cached() {
+    let cache = temp_cache();
+    let server = FakeE621::start().await;
+    server.add_file("preview/1.jpg", b"not really a jpeg".to_vec());
+    let url = format!("{}/data/preview/1.jpg", server.base_url);
+
+    fetch_preview(1, url.clone()).await.unwrap();
+    assert_eq!(cache_dir(), cache.path());
+    assert_eq!(
+        std::fs::read(cache.path().join("thumbnails").join("1.jpg")).unwrap(),
+        b"not really a jpeg"
+    );
+
+    // The second fetch comes from disk.
+    fetch_preview(1, url).await.unwrap();
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn images_are_downloaded_and_converted() {
+    let cache = temp_cache();
+    let server = FakeE621::start().await;
+    server.add_file("2.png", png(4, 3));
+
+    let file: File = serde_json::from_value(serde_json::json!({
+        "ext": "png",
+        "url": format!("{}/data/2.png", server.base_url)
+    }))
+    .unwrap();
+    fetch_image(2, file).await.unwrap();
+
+    assert!(cache.path().join("original").join("2.png").exists());
+    let resized = image::open(cache.path().join("resized").join("2.png")).unwrap();
+    assert_eq!((resized.width(), resized.height()), (4, 3));
+}
+
+#[tokio::test(start_paused = true)]
+async fn missing_media_is_an_error() {
+    temp_cache();
+    let server = FakeE621::start().await;
+
+    let result = fetch_gif(3, format!("{}/data/3.gif", server.base_url)).await;
+    assert!(result.is_err());
+    assert!(!cache_dir().join("gifs").join("3.gif").exists());
+}
+
+#[tokio::test(start_paused = true)]
+async fn failed_downloads_are_retried() {
+    temp_cache();
+    let server = FakeE621::start().await;
+    server.add_file("4.gif", b"gif".to_vec());
+    server.fail_next(502, "<html>Bad gateway</html>");
+
+    fetch_gif(4, format!("{}/data/4.gif", server.base_url))
+        .await
+        .unwrap();
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn failed_previews_are_left_to_the_scheduler() {
+    temp_cache();
+    let server = FakeE621::start().await;
+    server.add_file("preview/5.jpg", b"thumbnail".to_vec());
+    server.fail_next(502, "<html>Bad gateway</html>");
+    let url = format!("{}/data/preview/5.jpg", server.base_url);
+
+    assert!(fetch_preview(5, url.clone()).await.is_err());
+    assert_eq!(server.requests().len(), 1);
+    fetch_preview(5, url).await.unwrap();
+}
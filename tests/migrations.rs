@@ -0,0 +1,247 @@
+//! Integration tests for upgrading stores and configs written by older versions of msg, against
+//! the files in `tests/fixtures`.
+//!
+//! `store-v1.mpk` was written by `PostStore::save_to` as of f7bf7c9, the last msg without store
+//! versions, from the same posts as [`fixture_store`] with `HOME=/home/user`. Its posts, files and
+//! the store itself have fewer fields than today's. The current version's fixture can be written
+//! again with `cargo test --test migrations -- --ignored`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use msg::config::{ApiHost, Config, ConfigError, MsgTheme, CONFIG_VERSION};
+use msg::media::MediaKind;
+use msg::model::{Post, Vote};
+use msg::store::{PostStore, StoreError, STORE_VERSION};
+use serde_json::json;
+use tempfile::TempDir;
+use url::Url;
+
+const THUMBNAIL: &str = "/home/user/.cache/msg/thumbnails/1.jpg";
+const IMAGE: &str = "/home/user/.cache/msg/resized/1.png";
+const VIDEO: &str = "/home/user/.cache/msg/videos/2.webm";
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// Copies fixture `name` into a fresh directory as `file`, returning the directory and the copy.
+fn copy_fixture(name: &str, file: &str) -> (TempDir, PathBuf) {
+    let dir = TempDir::new().expect("Couldn't make TempDir");
+    let path = dir.path().join(file);
+    fs::copy(fixture(name), &path).expect("Couldn't copy fixture");
+    (dir, path)
+}
+
+fn post(id: u32, ext: &str, tag: &str) -> Post {
+    msg::fixture::post(json!({
+        "id": id,
+        "file": {
+            "ext": ext,
+            "url": format!("https://static1.e621.net/data/{id:032x}.{ext}")
+        },
+        "score": { "up": 3, "down": 0, "total": 3 },
+        "tags": { "general": [tag], "artist": ["someone"], "species": [], "meta": [] },
+        "fav_count": 1,
+        "uploader_id": null,
+        "uploader_name": null
+    }))
+}
+
+/// What every store fixture holds: a favorited image and an upvoted video, with only the fields
+/// msg kept for them in version 1.
+fn fixture_store() -> PostStore {
+    let mut store = PostStore::new();
+    store.insert_post(post(1, "png", "wolf"));
+    store.insert_post(post(2, "webm", "fox"));
+    store.set_favorite(1, true);
+    store.set_vote(2, Some(Vote::Upvote));
+    store
+        .media
+        .insert_path(MediaKind::Thumbnail, 1, THUMBNAIL.into());
+    store.media.insert_path(MediaKind::Image, 1, IMAGE.into());
+    store.insert_video(
+        2,
+        Url::from_file_path(VIDEO).expect("path should be absolute"),
+    );
+    store
+}
+
+fn assert_fixture_store(store: &PostStore) {
+    let mut ids = store.posts.keys().copied().collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(ids, vec![1, 2]);
+    let video = store.get_post(2).unwrap();
+    assert_eq!(video.file.ext.as_deref(), Some("webm"));
+    assert_eq!(video.tags.general, vec!["fox"]);
+    assert_eq!(video.tags.artist, vec!["someone"]);
+    assert_eq!((video.score.total, video.fav_count), (3, 1));
+    // Fields added since are left at their defaults.
+    assert_eq!((video.file.width, video.comment_count), (0, 0));
+    assert!(video.sources.is_empty());
+
+    assert!(store.is_favorited(1));
+    assert!(!store.is_favorited(2));
+    assert_eq!(store.votes.get(&2), Some(&Vote::Upvote));
+    assert_eq!(
+        store.media.path(MediaKind::Thumbnail, 1),
+        Some(Path::new(THUMBNAIL))
+    );
+    assert_eq!(
+        store.media.path(MediaKind::Image, 1),
+        Some(Path::new(IMAGE))
+    );
+    assert_eq!(
+        store.get_video(2).and_then(|url| url.to_file_path().ok()),
+        Some(PathBuf::from(VIDEO))
+    );
+}
+
+#[test]
+#[ignore = "rewrites the fixture for the current store version"]
+fn write_store_fixture() {
+    fixture_store()
+        .save_to(&fixture(&format!("store-v{STORE_VERSION}.mpk")))
+        .expect("Couldn't save store");
+}
+
+#[test]
+fn v1_store_is_upgraded_and_backed_up() {
+    let (dir, path) = copy_fixture("store-v1.mpk", "store.mpk");
+
+    let store = PostStore::load_from(&path).expect("Couldn't load v1 store");
+    assert_fixture_store(&store);
+
+    let backup = dir.path().join("store.v1.mpk");
+    assert_eq!(
+        fs::read(&backup).expect("Couldn't read backup"),
+        fs::read(fixture("store-v1.mpk")).unwrap()
+    );
+    // Saved again as the current version, which loads without another upgrade.
+    fs::remove_file(&backup).unwrap();
+    assert_fixture_store(&PostStore::load_from(&path).expect("Couldn't load upgraded store"));
+    assert!(!backup.exists());
+}
+
+#[test]
+fn current_store_loads_as_is() {
+    let name = format!("store-v{STORE_VERSION}.mpk");
+    let (dir, path) = copy_fixture(&name, "store.mpk");
+
+    assert_fixture_store(&PostStore::load_from(&path).expect("Couldn't load store"));
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    assert_eq!(fs::read(&path).unwrap(), fs::read(fixture(&name)).unwrap());
+}
+
+#[test]
+fn newer_store_is_refused_and_left_alone() {
+    let (_dir, path) = copy_fixture(&format!("store-v{STORE_VERSION}.mpk"), "store.mpk");
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[4..8].copy_from_slice(&(STORE_VERSION + 1).to_le_bytes());
+    fs::write(&path, &bytes).unwrap();
+
+    match PostStore::load_from(&path) {
+        Err(err @ StoreError::UnknownVersion(_)) => {
+            // Left for the newer msg that wrote it.
+            assert!(!err.is_undecodable());
+            assert!(matches!(err, StoreError::UnknownVersion(v) if v == STORE_VERSION + 1));
+        }
+        other => panic!("expected an unknown version, got {other:?}"),
+    }
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+}
+
+#[test]
+fn only_undecodable_stores_are_set_aside() {
+    let (dir, path) = copy_fixture(&format!("store-v{STORE_VERSION}.mpk"), "store.mpk");
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..6]).unwrap();
+    let err = PostStore::load_from(&path).expect_err("a cut-short header shouldn't load");
+    assert!(err.is_undecodable(), "{err:?}");
+
+    let aside = msg::store::set_aside(&path).expect("Couldn't set store aside");
+    assert_eq!(aside.parent(), Some(dir.path()));
+    assert_eq!(fs::read(&aside).unwrap(), &bytes[..6]);
+    assert!(!path.exists());
+
+    // A store that can't be read at all may read fine next time.
+    let unreadable = dir.path().join("dir.mpk");
+    fs::create_dir(&unreadable).unwrap();
+    let err = PostStore::load_from(&unreadable).expect_err("a directory shouldn't load");
+    assert!(matches!(err, StoreError::IOError(_)), "{err:?}");
+    assert!(!err.is_undecodable());
+}
+
+fn assert_fixture_config(config: &Config) {
+    assert_eq!(config.version, CONFIG_VERSION);
+    let auth = config.auth.as_ref().expect("auth should be kept");
+    assert_eq!(auth.username, "tester");
+    assert_eq!(config.blacklist.rules, vec!["gore", "rating:e"]);
+    assert_eq!(config.followed_tags[0].tag, "wolf");
+    assert_eq!(config.followed_tags[0].last_seen, Some(4));
+    assert_eq!(config.view.theme, MsgTheme::Light);
+    assert_eq!(config.view.posts_per_row, 4);
+    assert!(config.view.download_sample);
+    assert!(!config.view.download_fullsize);
+}
+
+#[test]
+fn v0_config_is_upgraded_and_backed_up() {
+    let (dir, path) = copy_fixture("config-v0.toml", "config.toml");
+
+    let config = Config::load_from(&path).expect("Couldn't load v0 config");
+    assert_fixture_config(&config);
+    assert_eq!(config.followed_tags[1].last_seen, None);
+    assert_eq!(config.host, ApiHost::E621);
+    assert_eq!(config.retry, Default::default());
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("config.v0.toml")).expect("Couldn't read backup"),
+        fs::read_to_string(fixture("config-v0.toml")).unwrap()
+    );
+    let saved = fs::read_to_string(&path).unwrap();
+    assert!(saved.starts_with(&format!("version = {CONFIG_VERSION}\n")));
+    assert_eq!(Config::load_from(&path).unwrap(), config);
+}
+
+#[test]
+fn current_config_loads_as_is() {
+    let name = format!("config-v{CONFIG_VERSION}.toml");
+    let (dir, path) = copy_fixture(&name, "config.toml");
+
+    let config = Config::load_from(&path).expect("Couldn't load config");
+    assert_fixture_config(&config);
+    assert_eq!(config.host, ApiHost::E926);
+    assert_eq!(config.followed_pools[0].name, "a_comic");
+    assert_eq!(config.retry.max_retries, 5);
+    assert_eq!(config.cache.memory_budget_mb, 64);
+
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        fs::read_to_string(fixture(&name)).unwrap()
+    );
+}
+
+#[test]
+fn newer_config_is_refused() {
+    let dir = TempDir::new().expect("Couldn't make TempDir");
+    let path = dir.path().join("config.toml");
+    fs::write(&path, format!("version = {}\n", CONFIG_VERSION + 1)).unwrap();
+
+    match Config::load_from(&path) {
+        Err(err @ ConfigError::UnknownVersion(_)) => {
+            assert!(!err.is_undecodable());
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "unknown config version {}, maybe from a newer msg",
+                    CONFIG_VERSION + 1
+                )
+            );
+        }
+        other => panic!("expected an unknown version, got {other:?}"),
+    }
+}